@@ -0,0 +1,52 @@
+use std::fs;
+use std::hint::black_box;
+use std::path::Path;
+use std::sync::Arc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tempfile::TempDir;
+use code_reviewer::backend::mock::MockBackend;
+use code_reviewer::CodeReviewTool;
+
+/// Tree sizes to read, in files; `CODEBASE_BENCH_FILES=5000,20000` measures others.
+const DEFAULT_SIZES: [usize; 2] = [100, 1000];
+const FILES_PER_DIRECTORY: usize = 50;
+const FILE_LINES: usize = 200;
+
+fn sizes() -> Vec<usize> {
+    match std::env::var("CODEBASE_BENCH_FILES") {
+        Ok(sizes) => sizes.split(',').filter_map(|size| size.trim().parse().ok()).collect(),
+        Err(_) => DEFAULT_SIZES.to_vec(),
+    }
+}
+
+/// A tree of `files` source files, `FILES_PER_DIRECTORY` to a directory, the same on every
+/// run. Returns it with its size in bytes.
+fn tree(files: usize) -> (TempDir, u64) {
+    let dir = TempDir::new().unwrap();
+    let content: String = (0..FILE_LINES).map(|line| format!("    let value_{} = compute({});\n", line, line)).collect();
+    for file in 0..files {
+        let directory = dir.path().join(format!("src/module_{:03}", file / FILES_PER_DIRECTORY));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join(format!("file_{:05}.rs", file)), &content).unwrap();
+    }
+    (dir, (files * content.len()) as u64)
+}
+
+fn tokenize_codebase(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let reviewer = CodeReviewTool::new(Arc::new(MockBackend::default()), None).with_progress(false);
+
+    let mut group = c.benchmark_group("tokenize_codebase");
+    group.sample_size(10);
+    for files in sizes() {
+        let (dir, bytes) = tree(files);
+        group.throughput(Throughput::Bytes(bytes));
+        group.bench_with_input(BenchmarkId::from_parameter(files), dir.path(), |b, root: &Path| {
+            b.iter(|| runtime.block_on(reviewer.tokenize_codebase_async(black_box(root))).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tokenize_codebase);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::hint::black_box;
+use std::sync::Arc;
+use criterion::{criterion_group, criterion_main, Criterion};
+use code_reviewer::backend::mock::MockBackend;
+use code_reviewer::{diff, imports, CodeReviewTool};
+
+const FILES: usize = 100;
+const HUNKS_PER_FILE: usize = 10;
+const CONTEXT_FILES: usize = 500;
+const CONTEXT_FILE_LINES: usize = 400;
+
+fn path(index: usize) -> String {
+    format!("src/module_{:03}.rs", index)
+}
+
+/// A diff of `FILES` files with `HUNKS_PER_FILE` hunks each, the same on every run.
+fn diff() -> String {
+    let mut diff = String::new();
+    for file in 0..FILES {
+        let path = path(file);
+        diff.push_str(&format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", path));
+        for hunk in 0..HUNKS_PER_FILE {
+            let start = hunk * 30 + 1;
+            diff.push_str(&format!("@@ -{},7 +{},8 @@ fn function_{}() {{\n", start, start + hunk, hunk));
+            for line in 0..3 {
+                diff.push_str(&format!("     let value_{} = compute({});\n", line, line));
+            }
+            diff.push_str("-    let result = value_0 + value_1;\n");
+            diff.push_str("+    let result = value_0.checked_add(value_1)\n");
+            diff.push_str("+        .expect(\"overflow\");\n");
+            for line in 3..6 {
+                diff.push_str(&format!("     let value_{} = compute({});\n", line, line));
+            }
+        }
+    }
+    diff
+}
+
+/// A diff in which each of `FILES` files adds imports of `CONTEXT_FILES / FILES` others, so
+/// that every context file is imported once.
+fn import_diff() -> String {
+    let imports_per_file = CONTEXT_FILES / FILES;
+    let mut diff = String::new();
+    for file in 0..FILES {
+        let path = path(file);
+        diff.push_str(&format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n", path));
+        diff.push_str(&format!("@@ -1,1 +1,{} @@\n", imports_per_file + 1));
+        for import in 0..imports_per_file {
+            diff.push_str(&format!("+use crate::module_{:03};\n", (file * imports_per_file + import + 1) % CONTEXT_FILES));
+        }
+        diff.push_str(" use std::fmt;\n");
+    }
+    diff
+}
+
+/// `CONTEXT_FILES` files long enough to be truncated, the first of them touched by `diff()`.
+fn codebase() -> BTreeMap<String, String> {
+    (0..CONTEXT_FILES)
+        .map(|file| {
+            let content: String = (0..CONTEXT_FILE_LINES)
+                .map(|line| format!("    let value_{} = compute({});\n", line, line))
+                .collect();
+            (path(file), content)
+        })
+        .collect()
+}
+
+fn chunk_diff(c: &mut Criterion) {
+    let diff = diff();
+    c.bench_function("chunk_diff 1000 hunks", |b| b.iter(|| diff::chunk_diff(black_box(&diff), 16 * 1024)));
+}
+
+fn imported_files(c: &mut Criterion) {
+    let diff = import_diff();
+    let codebase = codebase();
+    let known: BTreeSet<&str> = codebase.keys().map(String::as_str).collect();
+    c.bench_function("imported_files 500 context files", |b| {
+        b.iter(|| imports::imported_files(black_box(&diff), black_box(&known)))
+    });
+}
+
+fn review_prompt(c: &mut Criterion) {
+    let diff = diff();
+    let codebase = codebase();
+    let reviewer = CodeReviewTool::new(Arc::new(MockBackend::default()), None).with_progress(false);
+    c.bench_function("review_prompt 500 context files", |b| {
+        b.iter(|| reviewer.review_prompt(black_box(&diff), black_box(&codebase), CONTEXT_FILES))
+    });
+}
+
+criterion_group!(benches, chunk_diff, imported_files, review_prompt);
+criterion_main!(benches);
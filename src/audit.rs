@@ -0,0 +1,275 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::LazyLock;
+use regex::Regex;
+use serde::Serialize;
+use crate::findings::{self, Finding};
+use crate::language::LanguageProfile;
+
+/// The OWASP Top 10 concerns a security audit rates a change for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCategory {
+    Injection,
+    Authentication,
+    SensitiveDataExposure,
+    Ssrf,
+    PathTraversal,
+    Deserialization,
+    InsecureRandomness,
+}
+
+impl AuditCategory {
+    pub const ALL: [AuditCategory; 7] = [
+        AuditCategory::Injection,
+        AuditCategory::Authentication,
+        AuditCategory::SensitiveDataExposure,
+        AuditCategory::Ssrf,
+        AuditCategory::PathTraversal,
+        AuditCategory::Deserialization,
+        AuditCategory::InsecureRandomness,
+    ];
+
+    /// What the model is asked to look for under this category.
+    fn description(self) -> &'static str {
+        match self {
+            AuditCategory::Injection => "SQL, NoSQL, shell command, LDAP or template injection, and cross-site scripting",
+            AuditCategory::Authentication => "missing or bypassable authentication and authorization, weak session or token handling",
+            AuditCategory::SensitiveDataExposure => "secrets in code or logs, unencrypted sensitive data, overly detailed errors",
+            AuditCategory::Ssrf => "server-side requests to URLs or hosts an attacker can influence",
+            AuditCategory::PathTraversal => "file paths built from input that can escape the intended directory",
+            AuditCategory::Deserialization => "untrusted data deserialized into arbitrary types, or without size and depth limits",
+            AuditCategory::InsecureRandomness => "predictable random numbers used for tokens, keys, nonces or passwords",
+        }
+    }
+
+    /// Other names models use for the category, lowercase.
+    fn aliases(self) -> &'static [&'static str] {
+        match self {
+            AuditCategory::Injection => &["injection"],
+            AuditCategory::Authentication => &["authentication", "broken authentication", "authentication and authorization"],
+            AuditCategory::SensitiveDataExposure => &["sensitive data exposure", "sensitive data", "data exposure"],
+            AuditCategory::Ssrf => &["ssrf", "server-side request forgery"],
+            AuditCategory::PathTraversal => &["path traversal", "directory traversal"],
+            AuditCategory::Deserialization => &["deserialization", "insecure deserialization", "deserialization issues"],
+            AuditCategory::InsecureRandomness => &["insecure randomness", "randomness"],
+        }
+    }
+}
+
+impl fmt::Display for AuditCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AuditCategory::Injection => "Injection",
+            AuditCategory::Authentication => "Authentication",
+            AuditCategory::SensitiveDataExposure => "Sensitive data exposure",
+            AuditCategory::Ssrf => "SSRF",
+            AuditCategory::PathTraversal => "Path traversal",
+            AuditCategory::Deserialization => "Deserialization",
+            AuditCategory::InsecureRandomness => "Insecure randomness",
+        })
+    }
+}
+
+/// How a change fares in one category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "description", rename_all = "snake_case")]
+pub enum AuditRating {
+    /// The change has nothing to do with the category
+    #[serde(rename = "n/a")]
+    NotApplicable,
+    /// The change touches the category and handles it safely
+    Ok,
+    /// What is wrong, as the model described it
+    Risk(String),
+}
+
+impl fmt::Display for AuditRating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditRating::NotApplicable => f.write_str("N/A"),
+            AuditRating::Ok => f.write_str("OK"),
+            AuditRating::Risk(description) => write!(f, "RISK: {}", description),
+        }
+    }
+}
+
+/// One category's rating.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CategoryRating {
+    pub category: AuditCategory,
+    #[serde(flatten)]
+    pub rating: AuditRating,
+}
+
+/// The ratings of a `--security-audit`, in the order of [`AuditCategory::ALL`]. Categories the
+/// model didn't rate are left out.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SecurityAuditReport {
+    pub ratings: Vec<CategoryRating>,
+}
+
+// `Injection: OK`, `- **SSRF**: RISK: ...` or `3. Path traversal - N/A`
+static RATING_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:[-*+]|\d+[.)])?\s*[*_`]*(?P<category>[A-Za-z][A-Za-z /-]*?)[*_`]*\s*[:-]\s*[*_`]*(?P<rating>(?i:N/?A|OK|RISK))\b[*_`]*\s*[:-]?\s*(?P<description>.*)$")
+        .unwrap()
+});
+
+impl SecurityAuditReport {
+    /// Reads the ratings out of the model's answer, one `Category: N/A`, `Category: OK` or
+    /// `Category: RISK: description` line each, ignoring markdown around them and other lines.
+    /// Only the first rating of a category counts.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut ratings: Vec<CategoryRating> = Vec::new();
+        for line in text.lines() {
+            let Some(caps) = RATING_LINE.captures(line.trim()) else {
+                continue;
+            };
+            let name = caps["category"].trim().to_lowercase();
+            let Some(category) = AuditCategory::ALL.into_iter().find(|category| category.aliases().contains(&name.as_str())) else {
+                continue;
+            };
+            if ratings.iter().any(|rated| rated.category == category) {
+                continue;
+            }
+            let rating = match caps["rating"].to_uppercase().as_str() {
+                "OK" => AuditRating::Ok,
+                "RISK" => AuditRating::Risk(caps["description"].trim().to_string()),
+                _ => AuditRating::NotApplicable,
+            };
+            ratings.push(CategoryRating { category, rating });
+        }
+        if ratings.is_empty() {
+            return Err("no category ratings in the security audit".into());
+        }
+        ratings.sort_by_key(|rated| rated.category);
+        Ok(SecurityAuditReport { ratings })
+    }
+
+    /// The categories rated as a risk, as security findings located where the description
+    /// points.
+    pub fn findings(&self) -> Vec<Finding> {
+        // Under a security heading, so the findings are security ones whatever their wording
+        let mut risks = String::from("## Security\n\n");
+        for rated in &self.ratings {
+            if let AuditRating::Risk(description) = &rated.rating {
+                risks.push_str(&format!("- {} risk: {}\n", rated.category, description));
+            }
+        }
+        findings::parse_findings(&risks)
+    }
+
+    /// The report as a markdown review.
+    pub fn render(&self) -> String {
+        let mut review = String::from("## Security Audit\n\n");
+        for rated in &self.ratings {
+            review.push_str(&format!("- **{}**: {}\n", rated.category, rated.rating));
+        }
+        review
+    }
+}
+
+/// What to look at first in `language`, for the audit prompt.
+fn language_focus(language: LanguageProfile) -> &'static str {
+    match language {
+        LanguageProfile::Rust => {
+            "`std::process::Command` arguments built from input, paths joined from input, `unsafe` code parsing untrusted bytes, serde deserializing untrusted data, and `rand` generators used for secrets instead of `OsRng`"
+        }
+        LanguageProfile::Python => {
+            "SQL built with f-strings or `%`, `subprocess` with `shell=True`, `eval`/`exec`, `pickle` and `yaml.load`, `os.path.join` with input, and `random` used instead of `secrets`"
+        }
+        LanguageProfile::TypeScript => {
+            "`innerHTML`, `eval` and `dangerouslySetInnerHTML`, query strings built by concatenation, `fetch` of user-supplied URLs, `path.join` with input, prototype pollution, and `Math.random` used for tokens"
+        }
+        LanguageProfile::Go => {
+            "SQL built with `fmt.Sprintf`, `exec.Command` with input, `http.Get` of user-supplied URLs, `filepath.Join` with input, and `math/rand` used instead of `crypto/rand`"
+        }
+        LanguageProfile::Java => {
+            "JDBC statements built by concatenation, `Runtime.exec`, `ObjectInputStream` and XML parsers with external entities, `File` paths from input, and `java.util.Random` used instead of `SecureRandom`"
+        }
+        LanguageProfile::Cpp => {
+            "`system` and `popen` with input, format strings from input, paths from input, buffers sized from untrusted lengths, and `rand()` used for secrets"
+        }
+    }
+}
+
+/// The instructions that replace the generic review request in `--security-audit`, with the
+/// things to look at first in `languages`.
+pub fn instructions(languages: &[LanguageProfile]) -> String {
+    let mut instructions = String::from(
+        "\nAudit these changes for security only. Rate them for each of these OWASP Top 10 categories:\n",
+    );
+    for category in AuditCategory::ALL {
+        instructions.push_str(&format!("- {}: {}\n", category, category.description()));
+    }
+    for language in languages {
+        instructions.push_str(&format!("\nIn the {} code, look first at {}.\n", language, language_focus(*language)));
+    }
+    instructions.push_str(
+        "\nAnswer with exactly one line per category, in the order above, of the form `Category: N/A`, \
+        `Category: OK` or `Category: RISK: <description>`. Use N/A when the changes have nothing to do \
+        with the category, OK when they touch it safely, and RISK when they introduce or keep a \
+        vulnerability; the description says what is wrong, where as `file:line`, and how to fix it.",
+    );
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::findings::{Category, Severity};
+    use super::*;
+
+    const ANSWER: &str = "\
+Here is the audit:
+
+- **Injection**: RISK: `src/db.rs:12` the query is built with format!, use bind parameters
+- **Authentication**: N/A
+2. Sensitive data exposure - OK
+SSRF: n/a
+Path traversal: RISK - `src/files.rs:40` the name from the request is joined to the upload directory
+Injection: OK
+";
+
+    #[test]
+    fn parses_the_ratings() {
+        let report = SecurityAuditReport::parse(ANSWER).unwrap();
+        let ratings: Vec<(AuditCategory, &AuditRating)> = report.ratings.iter().map(|rated| (rated.category, &rated.rating)).collect();
+        assert_eq!(
+            ratings,
+            [
+                (AuditCategory::Injection, &AuditRating::Risk("`src/db.rs:12` the query is built with format!, use bind parameters".to_string())),
+                (AuditCategory::Authentication, &AuditRating::NotApplicable),
+                (AuditCategory::SensitiveDataExposure, &AuditRating::Ok),
+                (AuditCategory::Ssrf, &AuditRating::NotApplicable),
+                (
+                    AuditCategory::PathTraversal,
+                    &AuditRating::Risk("`src/files.rs:40` the name from the request is joined to the upload directory".to_string()),
+                ),
+            ]
+        );
+        assert!(SecurityAuditReport::parse("Looks secure to me.").is_err());
+    }
+
+    #[test]
+    fn risks_become_security_findings() {
+        let findings = SecurityAuditReport::parse(ANSWER).unwrap().findings();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].file.as_deref(), Some("src/db.rs"));
+        assert_eq!(findings[0].line, Some(12));
+        assert_eq!(findings[0].category, Category::Security);
+        assert!(findings[0].severity >= Severity::Warning);
+        assert_eq!(findings[1].message, "Path traversal risk: `src/files.rs:40` the name from the request is joined to the upload directory");
+    }
+
+    #[test]
+    fn serializes_each_rating_with_its_status() {
+        let report = SecurityAuditReport::parse("SSRF: N/A\nInjection: RISK: eval of input\n").unwrap();
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({ "ratings": [
+                { "category": "injection", "status": "risk", "description": "eval of input" },
+                { "category": "ssrf", "status": "n/a" },
+            ] })
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::error::Error;
+use std::time::Instant;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use crate::debug_enabled;
+use crate::proxy::Proxies;
+use crate::tls::TlsFiles;
+use super::{Backend, ChatMessage, Generation};
+
+const API_VERSION: &str = "2023-06-01";
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    messages: Vec<Message<'a>>,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'static str,
+    content: &'a str,
+}
+
+/// One `data:` payload of the server-sent event stream.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart { message: StreamMessage },
+    ContentBlockDelta { delta: Delta },
+    MessageDelta { usage: Usage },
+    Error { error: ApiError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamMessage {
+    usage: Usage,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Delta {
+    TextDelta { text: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Usage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: ApiError,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+/// Generates reviews through the Anthropic Messages API, sending the review prompt as a
+/// single user message, or the whole conversation, and reading the streamed reply.
+pub struct AnthropicBackend {
+    url: String,
+    api_key: String,
+    max_tokens: u32,
+    client: Client,
+}
+
+impl AnthropicBackend {
+    /// A backend for the Messages API at `url`, with completions limited to `max_tokens`.
+    pub fn new(url: String, api_key: String, max_tokens: u32) -> Self {
+        AnthropicBackend {
+            url,
+            api_key,
+            max_tokens,
+            client: Client::new(),
+        }
+    }
+
+    /// Connects to the API through `proxies`, authenticating and verifying it with `tls`.
+    pub fn with_connection(mut self, proxies: &Proxies, tls: &TlsFiles) -> Result<Self, Box<dyn Error>> {
+        self.client = tls.apply(proxies.apply(Client::builder())?)?.build()?;
+        Ok(self)
+    }
+}
+
+impl AnthropicBackend {
+    /// Sends `messages` and reads the event stream as it arrives, handing each piece of text
+    /// to `on_text`.
+    async fn messages(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        messages: &[ChatMessage],
+        on_text: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<Generation, Box<dyn Error>> {
+        let request = MessagesRequest {
+            model,
+            max_tokens: self.max_tokens,
+            system,
+            messages: messages
+                .iter()
+                .map(|message| Message { role: message.role.as_str(), content: &message.content })
+                .collect(),
+            stream: true,
+        };
+
+        let started = Instant::now();
+        let mut response = self.client
+            .post(format!("{}/v1/messages", self.url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status();
+        // Failures before the stream starts come back as a plain JSON error body
+        if !status.is_success() {
+            let text = response.text().await?;
+            if debug_enabled() {
+                eprintln!("Response status: {}", status);
+                eprintln!("Raw response: {}", text);
+            }
+            return Err(match serde_json::from_str::<ErrorResponse>(&text) {
+                Ok(body) => format!("Anthropic API error ({}): {}", body.error.kind, body.error.message),
+                Err(_) => format!("Anthropic API returned {}: {}", status, text),
+            }
+            .into());
+        }
+
+        let mut generation = Generation::default();
+        let mut pending: Vec<u8> = Vec::new();
+        let mut finished = false;
+        while !finished {
+            // Chunks can end anywhere, so only complete lines are read; the last may lack a newline
+            let end = match response.chunk().await? {
+                Some(chunk) => {
+                    pending.extend_from_slice(&chunk);
+                    match pending.iter().rposition(|byte| *byte == b'\n') {
+                        Some(newline) => newline + 1,
+                        None => continue,
+                    }
+                }
+                None => {
+                    finished = true;
+                    pending.len()
+                }
+            };
+            let lines: Vec<u8> = pending.drain(..end).collect();
+            let lines = String::from_utf8_lossy(&lines);
+            if debug_enabled() {
+                eprint!("{}", lines);
+            }
+            for data in lines.lines().filter_map(|line| line.strip_prefix("data:")) {
+                match serde_json::from_str::<StreamEvent>(data.trim()) {
+                    Ok(StreamEvent::MessageStart { message }) => {
+                        generation.prompt_tokens += message.usage.input_tokens;
+                    }
+                    Ok(StreamEvent::ContentBlockDelta { delta: Delta::TextDelta { text } }) => {
+                        on_text(&text);
+                        generation.text.push_str(&text);
+                    }
+                    Ok(StreamEvent::MessageDelta { usage }) => {
+                        generation.completion_tokens += usage.output_tokens;
+                    }
+                    // Errors can also arrive mid-stream, e.g. when the API is overloaded
+                    Ok(StreamEvent::Error { error }) => {
+                        return Err(format!("Anthropic API error ({}): {}", error.kind, error.message).into());
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // The API doesn't report server-side timings, so the whole stream counts as generation
+        let elapsed = started.elapsed().as_nanos() as u64;
+        generation.eval_duration_ns = elapsed;
+        generation.total_duration_ns = elapsed;
+
+        Ok(generation)
+    }
+}
+
+#[async_trait]
+impl Backend for AnthropicBackend {
+    async fn generate(&self, model: &str, system: Option<&str>, prompt: &str) -> Result<Generation, Box<dyn Error>> {
+        self.chat(model, system, &[ChatMessage::user(prompt)]).await
+    }
+
+    async fn chat(&self, model: &str, system: Option<&str>, messages: &[ChatMessage]) -> Result<Generation, Box<dyn Error>> {
+        self.messages(model, system, messages, &mut |_| {}).await
+    }
+
+    async fn chat_streaming(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        messages: &[ChatMessage],
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<Generation, Box<dyn Error>> {
+        self.messages(model, system, messages, on_text).await
+    }
+}
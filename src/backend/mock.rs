@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use async_trait::async_trait;
+use super::{Backend, Generation};
+
+/// What the mock backend answers when no responses are given.
+pub const DEFAULT_RESPONSE: &str = "## Summary\n\nThis review comes from the mock backend; no model was asked.\n";
+
+/// Answers with canned responses instead of asking a model, for tests and for smoke
+/// tests where no model server is available. Responses are returned in order, and the
+/// last one again once they run out.
+pub struct MockBackend {
+    responses: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl MockBackend {
+    pub fn new(responses: Vec<String>) -> Self {
+        MockBackend {
+            responses,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Answers every prompt with `response`.
+    pub fn with_response(response: impl Into<String>) -> Self {
+        Self::new(vec![response.into()])
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::with_response(DEFAULT_RESPONSE)
+    }
+}
+
+#[async_trait]
+impl Backend for MockBackend {
+    async fn generate(&self, _model: &str, _system: Option<&str>, _prompt: &str) -> Result<Generation, Box<dyn Error>> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed).min(self.responses.len().saturating_sub(1));
+        let text = self.responses.get(index).ok_or("the mock backend has no responses")?;
+        Ok(Generation {
+            text: text.clone(),
+            ..Generation::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn repeats_the_last_response() {
+        let backend = MockBackend::new(vec!["first".to_string(), "second".to_string()]);
+        let mut texts = Vec::new();
+        for _ in 0..3 {
+            texts.push(backend.generate("model", None, "prompt").await.unwrap().text);
+        }
+        assert_eq!(texts, ["first", "second", "second"]);
+    }
+
+    #[tokio::test]
+    async fn no_responses_is_an_error() {
+        let backend = MockBackend::new(Vec::new());
+        assert!(backend.generate("model", None, "prompt").await.is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::error::Error;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub mod anthropic;
+pub mod mock;
+pub mod ollama;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// A local or remote Ollama server
+    #[default]
+    Ollama,
+    /// The Anthropic Messages API
+    Anthropic,
+    /// Canned responses instead of a model, for tests and smoke tests
+    Mock,
+}
+
+/// The text a model produced for one prompt, plus whatever usage numbers the API reported.
+#[derive(Debug, Default)]
+pub struct Generation {
+    pub text: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub eval_duration_ns: u64,
+    pub total_duration_ns: u64,
+}
+
+/// Who said something in a conversation with the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+/// One turn of a conversation.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::User, content: content.into() }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        ChatMessage { role: Role::Assistant, content: content.into() }
+    }
+}
+
+/// A model API that turns a prompt into a review.
+#[async_trait]
+pub trait Backend: Send + Sync {
+    /// `system` sets the model's persona and is sent separately from the prompt.
+    async fn generate(&self, model: &str, system: Option<&str>, prompt: &str) -> Result<Generation, Box<dyn Error>>;
+
+    /// Like `generate`, but the answer should be JSON matching `schema`. APIs that can't
+    /// enforce a schema rely on the prompt asking for it.
+    async fn generate_structured(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        prompt: &str,
+        _schema: &Value,
+    ) -> Result<Generation, Box<dyn Error>> {
+        self.generate(model, system, prompt).await
+    }
+
+    /// Checks that the API can be reached, for health checks. APIs that can't be checked
+    /// without spending tokens are taken to be up.
+    async fn health(&self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Answers the last message of a conversation that alternates between the user and the
+    /// model, starting and ending with the user. APIs without a chat endpoint get the whole
+    /// conversation as a single prompt.
+    async fn chat(&self, model: &str, system: Option<&str>, messages: &[ChatMessage]) -> Result<Generation, Box<dyn Error>> {
+        let mut prompt = String::new();
+        for message in messages {
+            let speaker = match message.role {
+                Role::User => "User",
+                Role::Assistant => "You",
+            };
+            prompt.push_str(&format!("{}:\n{}\n\n", speaker, message.content.trim_end()));
+        }
+        prompt.push_str("You:\n");
+        self.generate(model, system, &prompt).await
+    }
+
+    /// Like `chat`, but hands the answer to `on_text` piece by piece as it arrives. APIs that
+    /// can't stream hand it over whole once it is done.
+    async fn chat_streaming(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        messages: &[ChatMessage],
+        // The lifetime is spelled out, or async_trait would tie the text to the method's
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<Generation, Box<dyn Error>> {
+        let generation = self.chat(model, system, messages).await?;
+        on_text(&generation.text);
+        Ok(generation)
+    }
+}
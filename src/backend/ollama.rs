@@ -0,0 +1,368 @@
+use std::error::Error;
+use async_trait::async_trait;
+use std::time::Duration;
+use clap::ValueEnum;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::debug_enabled;
+use crate::proxy::Proxies;
+use crate::tls::TlsFiles;
+use super::{Backend, ChatMessage, Generation};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Which Ollama endpoint single prompts, like reviews, are sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OllamaApi {
+    /// `/api/chat`, with the system prompt and the prompt as separate messages
+    #[default]
+    Chat,
+    /// `/api/generate`, for servers or proxies that only offer it
+    Generate,
+}
+
+/// A request to `/api/generate`, with a prompt, or to `/api/chat`, with messages.
+#[derive(Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    messages: Option<Vec<OllamaMessage<'a>>>,
+    /// JSON schema the answer is constrained to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<&'a Value>,
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct OllamaMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    /// The text from `/api/generate`
+    #[serde(default)]
+    response: String,
+    /// The text from `/api/chat`
+    #[serde(default)]
+    message: Option<OllamaReply>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+    #[serde(default)]
+    eval_duration: u64,
+    #[serde(default)]
+    total_duration: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaReply {
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaError {
+    error: String,
+}
+
+/// Why a request failed, which decides whether it is worth repeating. Messages are kept
+/// as strings so the future stays `Send`.
+enum Failure {
+    /// Server errors and connection problems, which may go away on their own
+    Retryable(String),
+    /// The model isn't pulled on the server
+    ModelNotFound(String),
+    Fatal(String),
+}
+
+/// Talks to an Ollama server through its `/api/chat` endpoint, or `/api/generate` for single
+/// prompts when configured to.
+pub struct OllamaBackend {
+    url: String,
+    client: Client,
+    api: OllamaApi,
+    retries: u32,
+    retry_delay: Duration,
+    fallback_model: Option<String>,
+}
+
+impl OllamaBackend {
+    /// A backend for the Ollama server at `url`, e.g. `http://localhost:11434`.
+    pub fn new(url: String) -> Self {
+        OllamaBackend {
+            url,
+            client: Self::client_builder().build().unwrap_or_default(),
+            api: OllamaApi::default(),
+            retries: 2,
+            retry_delay: Duration::from_secs(1),
+            fallback_model: None,
+        }
+    }
+
+    fn client_builder() -> reqwest::ClientBuilder {
+        // Bounds the wait for an unreachable server; generating can still take as long as it takes
+        Client::builder().connect_timeout(CONNECT_TIMEOUT)
+    }
+
+    /// Connects to the server through `proxies`, authenticating and verifying it with `tls`.
+    pub fn with_connection(mut self, proxies: &Proxies, tls: &TlsFiles) -> Result<Self, Box<dyn Error>> {
+        self.client = tls.apply(proxies.apply(Self::client_builder())?)?.build()?;
+        Ok(self)
+    }
+
+    /// Sends single prompts to `/api/generate` instead of `/api/chat`. Conversations always
+    /// go to `/api/chat`.
+    pub fn with_api(mut self, api: OllamaApi) -> Self {
+        self.api = api;
+        self
+    }
+
+    /// Repeats a request that failed with a server or connection error up to `retries`
+    /// times, waiting `retry_delay` longer before each attempt.
+    pub fn with_retries(mut self, retries: u32, retry_delay: Duration) -> Self {
+        self.retries = retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Model to use instead when the requested one isn't available on the server.
+    pub fn with_fallback_model(mut self, fallback_model: Option<String>) -> Self {
+        self.fallback_model = fallback_model;
+        self
+    }
+
+    /// Sends `request`, retrying server errors and switching to the fallback model when the
+    /// requested one isn't there.
+    async fn send(&self, mut request: OllamaRequest<'_>) -> Result<Generation, Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            match self.request(&request).await {
+                Ok(generation) => return Ok(generation),
+                Err(Failure::ModelNotFound(message)) => match self.fallback_model.as_deref() {
+                    Some(fallback) if fallback != request.model => {
+                        warning!("{}, falling back to {}", message, fallback);
+                        request.model = fallback;
+                    }
+                    _ => return Err(message.into()),
+                },
+                Err(Failure::Retryable(message)) if attempt < self.retries => {
+                    attempt += 1;
+                    warning!("{}, retrying ({}/{})", message, attempt, self.retries);
+                    tokio::time::sleep(self.retry_delay * attempt).await;
+                }
+                Err(Failure::Retryable(message) | Failure::Fatal(message)) => return Err(message.into()),
+            }
+        }
+    }
+
+    async fn request(&self, request: &OllamaRequest<'_>) -> Result<Generation, Failure> {
+        let endpoint = if request.messages.is_some() { "chat" } else { "generate" };
+        let response = self.client
+            .post(format!("{}/api/{}", self.url, endpoint))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| Failure::Retryable(format!("could not reach Ollama at {}: {}", self.url, e)))?;
+            
+        // Get status before consuming response with text()
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| Failure::Retryable(format!("could not read the response from Ollama: {}", e)))?;
+        
+        // Debug logging when DEBUG=TRUE
+        if debug_enabled() {
+            eprintln!("Response status: {}", status);
+            eprintln!("Raw response: {}", text);
+        }
+
+        if !status.is_success() {
+            let message = serde_json::from_str::<OllamaError>(&text).map_or(text, |error| error.error);
+            let message = format!("Ollama returned {}: {}", status, message.trim());
+            return Err(if status == StatusCode::NOT_FOUND && message.contains("not found") {
+                Failure::ModelNotFound(message)
+            } else if status.is_server_error() {
+                Failure::Retryable(message)
+            } else {
+                Failure::Fatal(message)
+            });
+        }
+
+        // Parse line by line as each line is a separate JSON object
+        let mut generation = Generation::default();
+        for line in text.lines() {
+            if let Some((_, true)) = add_line(&mut generation, line) {
+                break;
+            }
+        }
+
+        Ok(generation)
+    }
+
+    /// Sends a single prompt to the configured API.
+    async fn prompt(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        prompt: &str,
+        format: Option<&Value>,
+    ) -> Result<Generation, Box<dyn Error>> {
+        let messages = [ChatMessage::user(prompt)];
+        let request = match self.api {
+            OllamaApi::Chat => self.chat_request(model, system, &messages, format),
+            OllamaApi::Generate => OllamaRequest {
+                model,
+                system,
+                prompt: Some(prompt),
+                messages: None,
+                format,
+                stream: false,
+            },
+        };
+        self.send(request).await
+    }
+
+    /// Posts a streamed chat request and hands each piece of the answer to `on_text` as its
+    /// line arrives. Nothing is retried, as part of the answer may already be shown.
+    async fn stream(&self, request: &OllamaRequest<'_>, on_text: &mut (dyn FnMut(&str) + Send)) -> Result<Generation, Box<dyn Error>> {
+        let mut response = self.client
+            .post(format!("{}/api/chat", self.url))
+            .json(request)
+            .send()
+            .await
+            .map_err(|e| format!("could not reach Ollama at {}: {}", self.url, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await?;
+            let message = serde_json::from_str::<OllamaError>(&text).map_or(text, |error| error.error);
+            return Err(format!("Ollama returned {}: {}", status, message.trim()).into());
+        }
+
+        let mut generation = Generation::default();
+        let mut pending: Vec<u8> = Vec::new();
+        // Chunks can end anywhere, so only complete lines are read; the last may lack a newline
+        let mut read = |pending: &mut Vec<u8>, generation: &mut Generation, end: usize| {
+            let line: Vec<u8> = pending.drain(..end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if debug_enabled() {
+                eprintln!("Streamed line: {}", line.trim_end());
+            }
+            match add_line(generation, &line) {
+                Some((text, done)) => {
+                    if !text.is_empty() {
+                        on_text(&text);
+                    }
+                    done
+                }
+                None => false,
+            }
+        };
+        while let Some(chunk) = response.chunk().await? {
+            pending.extend_from_slice(&chunk);
+            while let Some(newline) = pending.iter().position(|byte| *byte == b'\n') {
+                if read(&mut pending, &mut generation, newline + 1) {
+                    return Ok(generation);
+                }
+            }
+        }
+        let end = pending.len();
+        read(&mut pending, &mut generation, end);
+        Ok(generation)
+    }
+
+    /// The system prompt goes first in the conversation, as `/api/chat` has no separate field.
+    fn chat_request<'a>(
+        &self,
+        model: &'a str,
+        system: Option<&'a str>,
+        messages: &'a [ChatMessage],
+        format: Option<&'a Value>,
+    ) -> OllamaRequest<'a> {
+        let system = system.map(|content| OllamaMessage { role: "system", content });
+        let messages = messages
+            .iter()
+            .map(|message| OllamaMessage { role: message.role.as_str(), content: &message.content });
+        OllamaRequest {
+            model,
+            system: None,
+            prompt: None,
+            messages: Some(system.into_iter().chain(messages).collect()),
+            format,
+            stream: false,
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for OllamaBackend {
+    async fn generate(&self, model: &str, system: Option<&str>, prompt: &str) -> Result<Generation, Box<dyn Error>> {
+        self.prompt(model, system, prompt, None).await
+    }
+
+    /// Passes `schema` as the request's `format`, so the server constrains the answer to it.
+    async fn generate_structured(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        prompt: &str,
+        schema: &Value,
+    ) -> Result<Generation, Box<dyn Error>> {
+        self.prompt(model, system, prompt, Some(schema)).await
+    }
+
+    async fn health(&self) -> Result<(), Box<dyn Error>> {
+        let response = self.client
+            .get(format!("{}/api/version", self.url))
+            .timeout(CONNECT_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("could not reach Ollama at {}: {}", self.url, e))?;
+        if !response.status().is_success() {
+            return Err(format!("Ollama at {} returned {}", self.url, response.status()).into());
+        }
+        Ok(())
+    }
+
+    async fn chat(&self, model: &str, system: Option<&str>, messages: &[ChatMessage]) -> Result<Generation, Box<dyn Error>> {
+        self.send(self.chat_request(model, system, messages, None)).await
+    }
+
+    async fn chat_streaming(
+        &self,
+        model: &str,
+        system: Option<&str>,
+        messages: &[ChatMessage],
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<Generation, Box<dyn Error>> {
+        let request = OllamaRequest { stream: true, ..self.chat_request(model, system, messages, None) };
+        self.stream(&request, on_text).await
+    }
+}
+
+/// Adds one line of a reply to `generation`. Returns the text the line carried and whether
+/// it was the last, or `None` for a line that isn't part of the reply.
+fn add_line(generation: &mut Generation, line: &str) -> Option<(String, bool)> {
+    let response = serde_json::from_str::<OllamaResponse>(line.trim()).ok()?;
+    let mut text = response.response;
+    if let Some(message) = response.message {
+        text.push_str(&message.content);
+    }
+    generation.text.push_str(&text);
+    generation.prompt_tokens += response.prompt_eval_count;
+    generation.completion_tokens += response.eval_count;
+    generation.eval_duration_ns += response.eval_duration;
+    generation.total_duration_ns += response.total_duration;
+    Some((text, response.done))
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::findings::{normalize_message, Finding, Severity};
+
+/// Name of the baseline file, kept at the root of the working tree.
+pub const BASELINE_FILE: &str = ".code_reviewer_baseline.json";
+
+// `:42`, `:42-50`, "line 42" or "lines 42 to 50" in a message, which move as the code around them changes
+static LINE_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i):\d+(?:-\d+)?\b|\blines? \d+(?:\s*(?:-|to)\s*\d+)?").unwrap()
+});
+
+/// Identifies a finding by its file and normalized message, leaving out line numbers and
+/// punctuation, so it still matches after code above it has moved.
+pub fn fingerprint(finding: &Finding) -> String {
+    let message: String = LINE_REFERENCE
+        .replace_all(&finding.message, "")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    let message = normalize_message(&message);
+    let digest = Sha256::digest(format!("{}:{}", finding.file.as_deref().unwrap_or_default(), message));
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// A finding that has been acknowledged and is no longer reported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub fingerprint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The rest is for whoever reads the file; only the fingerprint is matched
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Findings known from earlier reviews, which later reviews leave out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub findings: Vec<Entry>,
+}
+
+impl Baseline {
+    /// A baseline of `findings`, one entry per fingerprint, ordered by file and fingerprint so
+    /// rewriting it for the same findings gives the same file.
+    pub fn from_findings(findings: &[Finding]) -> Self {
+        let entries: BTreeMap<_, _> = findings
+            .iter()
+            .map(|finding| {
+                let entry = Entry {
+                    fingerprint: fingerprint(finding),
+                    file: finding.file.clone(),
+                    severity: finding.severity,
+                    message: finding.message.clone(),
+                };
+                ((entry.file.clone(), entry.fingerprint.clone()), entry)
+            })
+            .collect();
+        Baseline { findings: entries.into_values().collect() }
+    }
+
+    /// Adds `finding` unless the baseline already has it, keeping the order of
+    /// [`Baseline::from_findings`]. Returns whether it was added.
+    pub fn add(&mut self, finding: &Finding) -> bool {
+        let fingerprint = fingerprint(finding);
+        if self.findings.iter().any(|entry| entry.fingerprint == fingerprint) {
+            return false;
+        }
+        self.findings.push(Entry {
+            fingerprint,
+            file: finding.file.clone(),
+            severity: finding.severity,
+            message: finding.message.clone(),
+        });
+        self.findings.sort_by(|a, b| (&a.file, &a.fingerprint).cmp(&(&b.file, &b.fingerprint)));
+        true
+    }
+
+    /// The baseline at `path`, or `None` when there is no file.
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("could not read the baseline {}: {}", path.display(), e).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("could not read the baseline {}: {}", path.display(), e).into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Drops the findings in the baseline, returning how many were dropped.
+    pub fn filter(&self, findings: &mut Vec<Finding>) -> usize {
+        let before = findings.len();
+        findings.retain(|finding| {
+            let fingerprint = fingerprint(finding);
+            !self.findings.iter().any(|entry| entry.fingerprint == fingerprint)
+        });
+        before - findings.len()
+    }
+
+    /// Drops the entries for files that no longer exist under `root`, returning how many were
+    /// dropped. Entries without a file are kept.
+    pub fn prune(&mut self, root: &Path) -> usize {
+        let before = self.findings.len();
+        self.findings.retain(|entry| entry.file.as_ref().is_none_or(|file| root.join(file).exists()));
+        before - self.findings.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use super::*;
+    use crate::findings::parse_findings;
+
+    #[test]
+    fn fingerprint_ignores_line_numbers_and_formatting() {
+        let before = parse_findings("- `src/lib.rs:12` unwrap panics on **bad input** (error)");
+        let after = parse_findings("- `src/lib.rs` line 40:  Unwrap panics on bad input (error)");
+        assert_eq!(after[0].line, Some(40));
+        assert_eq!(fingerprint(&before[0]), fingerprint(&after[0]));
+
+        let other_file = parse_findings("- `src/main.rs:12` unwrap panics on bad input (error)");
+        assert_ne!(fingerprint(&before[0]), fingerprint(&other_file[0]));
+    }
+
+    #[test]
+    fn filters_baselined_findings() {
+        let baseline = Baseline::from_findings(&parse_findings("- `src/lib.rs:2` unwrap panics (error)"));
+        let mut findings = parse_findings(
+            "- `src/lib.rs:9` unwrap panics (error)\n\
+            - `src/lib.rs:5` the new loop is quadratic (warning)\n",
+        );
+        assert_eq!(baseline.filter(&mut findings), 1);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("quadratic"));
+    }
+
+    #[test]
+    fn added_findings_are_kept_once_in_order() {
+        let findings = parse_findings("- `src/lib.rs:2` unwrap panics (error)\n- `src/a.rs:1` odd name (info)\n");
+        let mut baseline = Baseline::from_findings(&findings[..1]);
+        assert!(baseline.add(&findings[1]));
+        assert!(!baseline.add(&findings[0]));
+        assert_eq!(baseline, Baseline::from_findings(&findings));
+    }
+
+    #[test]
+    fn prune_drops_entries_for_deleted_files() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("kept.rs"), "").unwrap();
+        let mut baseline = Baseline::from_findings(&parse_findings(
+            "- `kept.rs:1` kept (info)\n- `gone.rs:1` gone (info)\n- The commit message is vague (info)\n",
+        ));
+        assert_eq!(baseline.prune(dir.path()), 1);
+        assert_eq!(baseline.findings.len(), 2);
+        assert!(baseline.findings.iter().all(|entry| entry.file.as_deref() != Some("gone.rs")));
+    }
+
+    #[test]
+    fn saved_baseline_loads_back() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(BASELINE_FILE);
+        assert_eq!(Baseline::load(&path).unwrap(), None);
+
+        let baseline = Baseline::from_findings(&parse_findings("- `src/lib.rs:2` unwrap panics (error)"));
+        baseline.save(&path).unwrap();
+        assert_eq!(Baseline::load(&path).unwrap(), Some(baseline));
+    }
+}
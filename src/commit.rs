@@ -0,0 +1,191 @@
+use std::path::Path;
+use serde::Deserialize;
+
+/// Longest subject line a generated commit message should have.
+pub const MAX_SUBJECT_LEN: usize = 72;
+
+/// Column the body of a generated commit message is wrapped at.
+const BODY_WIDTH: usize = 72;
+
+/// Line git puts above the diff in the message file of `git commit --verbose`.
+const SCISSORS: &str = "------------------------ >8 ------------------------";
+
+/// What `check-message` does when the model can't be asked, e.g. because Ollama isn't running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Warn and let the commit through, so nobody is locked out of committing offline
+    #[default]
+    Allow,
+    /// Fail, blocking the commit
+    Block,
+}
+
+/// How well a commit message describes its change, in the model's judgement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Good,
+    /// Accurate but too vague to be useful, like "wip" or "fix"
+    Insufficient,
+    /// Claims something the change doesn't do, or leaves out what it mainly does
+    Misleading,
+}
+
+/// The model's verdict on a commit message and its suggestions for improving it.
+#[derive(Debug, Clone)]
+pub struct MessageCheck {
+    pub verdict: Verdict,
+    pub feedback: String,
+}
+
+impl MessageCheck {
+    /// Parses a response that starts with a `VERDICT: GOOD|INSUFFICIENT|MISLEADING` line. A
+    /// response without one counts as good, so a confused model never blocks a commit.
+    pub fn parse(response: &str) -> MessageCheck {
+        let response = response.trim();
+        let (first, rest) = response.split_once('\n').unwrap_or((response, ""));
+        let verdict = first
+            .trim()
+            .trim_matches('*')
+            .strip_prefix("VERDICT:")
+            .map(|verdict| verdict.trim().trim_matches('*').trim().to_uppercase());
+        let verdict = match verdict.as_deref() {
+            Some("INSUFFICIENT") => Verdict::Insufficient,
+            Some("MISLEADING") => Verdict::Misleading,
+            Some(_) => Verdict::Good,
+            None => return MessageCheck { verdict: Verdict::Good, feedback: response.to_string() },
+        };
+        MessageCheck { verdict, feedback: rest.trim().to_string() }
+    }
+}
+
+/// The message git will commit from the contents of a commit message file: without comment
+/// lines and without the diff `git commit --verbose` adds below the scissors line.
+pub fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .take_while(|line| !line.contains(SCISSORS))
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// The conventional-commit scope shared by every path in `files`: the first directory below
+/// `src`, or the file name for files directly in it, e.g. `backend` for
+/// `src/backend/ollama.rs`. `None` when the files don't agree on one.
+pub fn infer_scope(files: &[String]) -> Option<String> {
+    let mut scopes = files.iter().map(|file| {
+        let mut components: Vec<&str> = Path::new(file)
+            .components()
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+        if components.len() > 1 && matches!(components[0], "src" | "lib") {
+            components.remove(0);
+        }
+        match components.as_slice() {
+            [] => None,
+            [name] => Path::new(name).file_stem()?.to_str().map(str::to_lowercase),
+            [directory, ..] => Some(directory.to_lowercase()),
+        }
+    });
+
+    let scope = scopes.next()??;
+    scopes.all(|other| other.as_ref() == Some(&scope)).then_some(scope)
+}
+
+/// Cleans up a commit message written by a model: drops code fences around it and wraps body
+/// lines longer than the body width, keeping bullet points indented.
+pub fn tidy_message(message: &str) -> String {
+    let lines: Vec<&str> = message
+        .trim()
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect();
+    let mut lines = lines.into_iter().skip_while(|line| line.trim().is_empty());
+
+    let mut tidy = match lines.next() {
+        Some(subject) => subject.trim().to_string(),
+        None => return String::new(),
+    };
+    for line in lines {
+        tidy.push('\n');
+        tidy.push_str(&wrap(line.trim_end(), BODY_WIDTH));
+    }
+    tidy.trim_end().to_string() + "\n"
+}
+
+/// Wraps `line` at word boundaries, indenting continuation lines to match a leading `- ` or `* `.
+fn wrap(line: &str, width: usize) -> String {
+    if line.chars().count() <= width {
+        return line.to_string();
+    }
+    let content = line.trim_start();
+    let indent = line.len() - content.len();
+    let hanging = indent + if content.starts_with("- ") || content.starts_with("* ") { 2 } else { 0 };
+
+    let mut wrapped = String::new();
+    let mut current = line[..indent].to_string();
+    for word in content.split_whitespace() {
+        let at_start = current.trim().is_empty();
+        if !at_start && current.chars().count() + 1 + word.chars().count() > width {
+            wrapped.push_str(&current);
+            wrapped.push('\n');
+            current = " ".repeat(hanging);
+        } else if !at_start {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    wrapped.push_str(&current);
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn scope_is_the_shared_module() {
+        assert_eq!(infer_scope(&files(&["src/backend/ollama.rs", "src/backend/mod.rs"])).as_deref(), Some("backend"));
+        assert_eq!(infer_scope(&files(&["src/git.rs"])).as_deref(), Some("git"));
+        assert_eq!(infer_scope(&files(&["docs/usage.md", "docs/config.md"])).as_deref(), Some("docs"));
+    }
+
+    #[test]
+    fn no_scope_when_files_disagree() {
+        assert_eq!(infer_scope(&files(&["src/git.rs", "src/backend/mod.rs"])), None);
+        assert_eq!(infer_scope(&[]), None);
+    }
+
+    #[test]
+    fn verdict_is_read_from_the_first_line() {
+        let check = MessageCheck::parse("VERDICT: MISLEADING\n- The change also renames `run`.");
+        assert_eq!(check.verdict, Verdict::Misleading);
+        assert_eq!(check.feedback, "- The change also renames `run`.");
+        assert_eq!(MessageCheck::parse("**VERDICT:** insufficient\nSay what is fixed.").verdict, Verdict::Insufficient);
+        assert_eq!(MessageCheck::parse("Looks accurate.").verdict, Verdict::Good);
+    }
+
+    #[test]
+    fn comments_and_verbose_diff_are_stripped() {
+        let file = "fix parser\n\n# Please enter the commit message\n# ------------------------ >8 ------------------------\ndiff --git a/x b/x\n";
+        assert_eq!(strip_comments(file), "fix parser");
+    }
+
+    #[test]
+    fn tidy_message_strips_fences_and_wraps_the_body() {
+        let message = "```\nfeat(git): add notes\n\n- Store every review as a git note on the reviewed commit so it can be read back later\n```";
+        assert_eq!(
+            tidy_message(message),
+            "feat(git): add notes\n\n\
+            - Store every review as a git note on the reviewed commit so it can be\n  \
+            read back later\n"
+        );
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use serde::{Deserialize, Serialize};
+
+/// What happens to a diff larger than `max_diff_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LargeDiffPolicy {
+    /// Refuse to review it unless `--force` is given
+    #[default]
+    Abort,
+    /// Review it one file at a time, as with `--per-file`
+    PerFile,
+}
+
+/// Paths of the files that can be loaded as context after the change, in the order they
+/// appear in the diff. Renamed files are listed under their new name. Deleted files have
+/// nothing left to load and binary files nothing to read, so both are left out.
+pub fn changed_files(diff: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+
+    for file in parse(diff) {
+        if file.binary {
+            continue;
+        }
+        if let Some(path) = file.new_path {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files
+}
+
+/// Size of a diff, as reported by `git diff --stat`.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct DiffStats {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+pub fn diff_stats(diff: &str) -> DiffStats {
+    let mut stats = DiffStats::default();
+
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            stats.files_changed += 1;
+        } else if line.starts_with('+') && !line.starts_with("+++") {
+            stats.insertions += 1;
+        } else if line.starts_with('-') && !line.starts_with("---") {
+            stats.deletions += 1;
+        }
+    }
+
+    stats
+}
+
+/// The part of a diff that belongs to one file.
+#[derive(Debug, Clone, Default)]
+pub struct FileDiff {
+    /// The file's name after the change, or before it for deletions
+    pub path: String,
+    /// Name before the change, `None` for added files
+    pub old_path: Option<String>,
+    /// Name after the change, `None` for deleted files
+    pub new_path: Option<String>,
+    /// File mode before the change, when it changed or the file was deleted
+    pub old_mode: Option<String>,
+    /// File mode after the change, when it changed or the file was added
+    pub new_mode: Option<String>,
+    /// Binary files have no hunks, just a note that they differ
+    pub binary: bool,
+    pub hunks: Vec<Hunk>,
+    /// The file's part of the diff as text, headers included
+    pub diff: String,
+}
+
+impl FileDiff {
+    pub fn is_added(&self) -> bool {
+        self.old_path.is_none() && self.new_path.is_some()
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.new_path.is_none() && self.old_path.is_some()
+    }
+
+    pub fn is_renamed(&self) -> bool {
+        matches!((&self.old_path, &self.new_path), (Some(old), Some(new)) if old != new)
+    }
+}
+
+/// One `@@` section of a file's diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_count: u32,
+    pub new_start: u32,
+    pub new_count: u32,
+    /// The text after the closing `@@`, usually the enclosing function
+    pub section: String,
+    pub lines: Vec<Line>,
+}
+
+impl Hunk {
+    pub fn added(&self) -> impl Iterator<Item = &Line> {
+        self.lines.iter().filter(|line| line.kind == LineKind::Added)
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &Line> {
+        self.lines.iter().filter(|line| line.kind == LineKind::Removed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// A line of a hunk, without its `+`, `-` or ` ` prefix, numbered on the sides it is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub kind: LineKind,
+    pub content: String,
+    pub old_number: Option<u32>,
+    pub new_number: Option<u32>,
+    /// Followed by `\ No newline at end of file`
+    pub no_newline: bool,
+}
+
+/// Parses a unified diff, from `git diff` or plain `diff -u`, into its files. Hunk lines
+/// are read as far as the hunk header's line counts go, so a removed line that looks like a
+/// `---` header is still a removed line. Anything before the first file is ignored.
+pub fn parse(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut lines = diff.lines().peekable();
+    // Lines the current hunk still has on each side
+    let (mut old_left, mut new_left) = (0, 0);
+
+    while let Some(line) = lines.next() {
+        // A line that can't be part of a hunk ends it, even if the counts say otherwise
+        let hunk_line = matches!(line.chars().next(), None | Some('+' | '-' | ' ' | '\\'));
+        if !hunk_line {
+            (old_left, new_left) = (0, 0);
+        }
+        let in_hunk = old_left > 0 || new_left > 0;
+        let plain_header = !in_hunk
+            && line.starts_with("--- ")
+            && lines.peek().is_some_and(|next| next.starts_with("+++ "))
+            && files.last().is_none_or(|file| !file.hunks.is_empty() || file.binary);
+
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            let (old, new) = git_header_paths(header);
+            files.push(FileDiff { old_path: old, new_path: new, ..FileDiff::default() });
+        } else if plain_header {
+            files.push(FileDiff::default());
+        }
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+        file.diff.push_str(line);
+        file.diff.push('\n');
+
+        if in_hunk {
+            let Some(hunk) = file.hunks.last_mut() else {
+                continue;
+            };
+            let (kind, content) = match line.chars().next() {
+                Some('+') => (LineKind::Added, &line[1..]),
+                Some('-') => (LineKind::Removed, &line[1..]),
+                Some(' ') => (LineKind::Context, &line[1..]),
+                Some('\\') => {
+                    if let Some(last) = hunk.lines.last_mut() {
+                        last.no_newline = true;
+                    }
+                    continue;
+                }
+                // A blank context line whose leading space was trimmed
+                _ => (LineKind::Context, ""),
+            };
+            let old_number = (kind != LineKind::Added).then(|| hunk.old_start + hunk.old_count - old_left);
+            let new_number = (kind != LineKind::Removed).then(|| hunk.new_start + hunk.new_count - new_left);
+            if old_number.is_some() {
+                old_left = old_left.saturating_sub(1);
+            }
+            if new_number.is_some() {
+                new_left = new_left.saturating_sub(1);
+            }
+            hunk.lines.push(Line { kind, content: content.to_string(), old_number, new_number, no_newline: false });
+        } else if let Some(hunk) = parse_hunk(line) {
+            (old_left, new_left) = (hunk.old_count, hunk.new_count);
+            file.hunks.push(hunk);
+        } else if line.starts_with("\\ ") {
+            // "\ No newline at end of file" after the last line of the hunk
+            if let Some(last) = file.hunks.last_mut().and_then(|hunk| hunk.lines.last_mut()) {
+                last.no_newline = true;
+            }
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            file.old_path = header_path(path, "a/");
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            file.new_path = header_path(path, "b/");
+        } else if let Some(path) = line.strip_prefix("rename from ").or_else(|| line.strip_prefix("copy from ")) {
+            file.old_path = Some(unquote(path).to_string());
+        } else if let Some(path) = line.strip_prefix("rename to ").or_else(|| line.strip_prefix("copy to ")) {
+            file.new_path = Some(unquote(path).to_string());
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            file.old_path = None;
+            file.new_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+            file.new_path = None;
+            file.old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            file.old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            file.new_mode = Some(mode.to_string());
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            file.binary = true;
+        }
+    }
+
+    for file in &mut files {
+        file.path = file.new_path.clone().or_else(|| file.old_path.clone()).unwrap_or_default();
+    }
+    files
+}
+
+/// The old and new paths of a `diff --git a/old b/new` header. Ambiguous with spaces in the
+/// names, so the `---`, `+++` and rename lines that follow take precedence.
+fn git_header_paths(header: &str) -> (Option<String>, Option<String>) {
+    let (old, new) = match header.rsplit_once(" b/") {
+        Some((old, new)) => (old, format!("b/{}", new)),
+        None => (header, header.to_string()),
+    };
+    (header_path(old, "a/"), header_path(&new, "b/"))
+}
+
+/// A path from a `---` or `+++` line: without its `a/` or `b/` prefix and any timestamp
+/// `diff -u` appends, and `None` for `/dev/null`.
+fn header_path(path: &str, prefix: &str) -> Option<String> {
+    let path = unquote(path.split('\t').next().unwrap_or(path).trim_end());
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix(prefix).unwrap_or(path).to_string())
+}
+
+/// Strips the quotes git puts around paths with unusual characters.
+fn unquote(path: &str) -> &str {
+    path.strip_prefix('"').and_then(|path| path.strip_suffix('"')).unwrap_or(path)
+}
+
+/// A hunk with no lines yet from its `@@ -a,b +c,d @@ section` header. A missing count is 1.
+fn parse_hunk(header: &str) -> Option<Hunk> {
+    let (old_start, new_start, section) = parse_hunk_header(header)?;
+    let ranges = header.strip_prefix("@@ ")?.split_once(" @@")?.0;
+    let (old, new) = ranges.split_once(' ')?;
+    let count = |range: &str| range.split_once(',').map_or(Some(1), |(_, count)| count.parse().ok());
+    Some(Hunk {
+        old_start,
+        old_count: count(old)?,
+        new_start,
+        new_count: count(new)?,
+        section: section.trim().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+/// Splits a diff into pieces of at most `max_bytes`, each a valid diff on its own, for
+/// changes too large to review in one request. Pieces break between hunks and repeat the
+/// header of every file they have hunks of. A hunk too large for a piece by itself is split
+/// between lines, with a new `@@` header for each part. Only a single line longer than
+/// `max_bytes` makes a piece go over.
+pub fn chunk_diff(diff: &str, max_bytes: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for file in parse(diff) {
+        let (header, hunks) = split_hunks(&file.diff);
+        let mut parts: Vec<String> = hunks
+            .iter()
+            .flat_map(|hunk| split_hunk(hunk, max_bytes.saturating_sub(header.len())))
+            .collect();
+        // A file without hunks, like a binary file or a pure rename, is just its header
+        if parts.is_empty() {
+            parts.push(String::new());
+        }
+
+        let mut has_header = false;
+        for part in parts {
+            let needed = part.len() + if has_header { 0 } else { header.len() };
+            if !current.is_empty() && current.len() + needed > max_bytes {
+                chunks.push(std::mem::take(&mut current));
+                has_header = false;
+            }
+            if !has_header {
+                current.push_str(&header);
+                has_header = true;
+            }
+            current.push_str(&part);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Separates a file's diff into the header before its first `@@` and its hunks.
+fn split_hunks(file_diff: &str) -> (String, Vec<String>) {
+    let mut header = String::new();
+    let mut hunks: Vec<String> = Vec::new();
+
+    for line in file_diff.lines() {
+        if line.starts_with("@@ ") {
+            hunks.push(String::new());
+        }
+        let target = hunks.last_mut().unwrap_or(&mut header);
+        target.push_str(line);
+        target.push('\n');
+    }
+
+    (header, hunks)
+}
+
+/// Splits a hunk longer than `max_bytes` between lines, renumbering each part.
+fn split_hunk(hunk: &str, max_bytes: usize) -> Vec<String> {
+    let mut lines = hunk.lines();
+    let header = lines.next().unwrap_or_default();
+    let Some((old_start, new_start, section)) = parse_hunk_header(header) else {
+        return vec![hunk.to_string()];
+    };
+    if hunk.len() <= max_bytes {
+        return vec![hunk.to_string()];
+    }
+    // The renumbered headers are at most a digit per number longer
+    let body_limit = max_bytes.saturating_sub(header.len() + 5);
+
+    let mut parts = Vec::new();
+    let mut part = HunkPart::new(old_start, new_start);
+    for line in lines {
+        // "\ No newline at end of file" belongs to the line before it
+        let continues = line.starts_with('\\');
+        if !continues && !part.body.is_empty() && part.body.len() + line.len() + 1 > body_limit {
+            let next = HunkPart::new(part.old_start + part.old_count, part.new_start + part.new_count);
+            parts.push(std::mem::replace(&mut part, next).finish(section));
+        }
+        part.push(line);
+    }
+    if !part.body.is_empty() {
+        parts.push(part.finish(section));
+    }
+    parts
+}
+
+/// Reads the start lines and the trailing section heading from `@@ -a,b +c,d @@ section`.
+fn parse_hunk_header(header: &str) -> Option<(u32, u32, &str)> {
+    let ranges = header.strip_prefix("@@ ")?;
+    let (ranges, section) = ranges.split_once(" @@")?;
+    let (old, new) = ranges.split_once(' ')?;
+    let start = |range: &str| range.split(',').next()?.parse::<u32>().ok();
+    Some((start(old.strip_prefix('-')?)?, start(new.strip_prefix('+')?)?, section))
+}
+
+struct HunkPart {
+    old_start: u32,
+    new_start: u32,
+    old_count: u32,
+    new_count: u32,
+    body: String,
+}
+
+impl HunkPart {
+    fn new(old_start: u32, new_start: u32) -> Self {
+        HunkPart {
+            old_start,
+            new_start,
+            old_count: 0,
+            new_count: 0,
+            body: String::new(),
+        }
+    }
+
+    fn push(&mut self, line: &str) {
+        match line.chars().next() {
+            Some('+') => self.new_count += 1,
+            Some('-') => self.old_count += 1,
+            Some('\\') => {}
+            // Context lines, including blank ones whose leading space was trimmed
+            _ => {
+                self.old_count += 1;
+                self.new_count += 1;
+            }
+        }
+        self.body.push_str(line);
+        self.body.push('\n');
+    }
+
+    fn finish(self, section: &str) -> String {
+        // An empty range is numbered by the line before it
+        let start = |start: u32, count: u32| if count == 0 { start.saturating_sub(1) } else { start };
+        format!(
+            "@@ -{},{} +{},{} @@{}\n{}",
+            start(self.old_start, self.old_count),
+            self.old_count,
+            start(self.new_start, self.new_count),
+            self.new_count,
+            section,
+            self.body
+        )
+    }
+}
+
+/// Lines on the new side of each file that a review comment can be attached to: the added
+/// and context lines inside the diff's hunks, keyed by path. Files without any, like deleted
+/// and binary files, are left out.
+pub fn commentable_lines(diff: &str) -> HashMap<String, BTreeSet<u32>> {
+    commentable_hunks(diff)
+        .into_iter()
+        .map(|(path, hunks)| (path, hunks.into_iter().flatten().collect()))
+        .collect()
+}
+
+/// The commentable lines of each hunk, in order and keyed by path, for comments on a range of
+/// lines, which can't span hunks. Hunks that only remove lines have none and are left out.
+pub fn commentable_hunks(diff: &str) -> HashMap<String, Vec<RangeInclusive<u32>>> {
+    let mut hunks: HashMap<String, Vec<RangeInclusive<u32>>> = HashMap::new();
+
+    for file in parse(diff) {
+        let Some(path) = file.new_path else {
+            continue;
+        };
+        for hunk in &file.hunks {
+            let mut numbers = hunk.lines.iter().filter_map(|line| line.new_number);
+            if let Some(first) = numbers.next() {
+                let last = numbers.next_back().unwrap_or(first);
+                hunks.entry(path.clone()).or_default().push(first..=last);
+            }
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    const MULTI_HUNK: &str = include_str!("../tests/fixtures/multi_hunk.diff");
+    const TRICKY: &str = include_str!("../tests/fixtures/tricky.diff");
+
+    fn file<'a>(files: &'a [FileDiff], path: &str) -> &'a FileDiff {
+        files.iter().find(|file| file.path == path).unwrap()
+    }
+
+    /// The lines a diff changes or shows as context, without file and hunk headers.
+    fn content_lines(diff: &str) -> Vec<&str> {
+        let mut in_hunk = false;
+        diff.lines()
+            .filter(|line| {
+                if line.starts_with("diff --git ") {
+                    in_hunk = false;
+                } else if line.starts_with("@@ ") {
+                    in_hunk = true;
+                    return false;
+                }
+                in_hunk
+            })
+            .collect()
+    }
+
+    /// Checks that the counts in every `@@` header match the lines of its hunk.
+    fn assert_hunk_counts(diff: &str) {
+        for hunk in diff.split("\n@@ ").skip(1) {
+            let (header, body) = hunk.split_once('\n').unwrap();
+            let ranges = header.split(" @@").next().unwrap();
+            let (old, new) = ranges.split_once(' ').unwrap();
+            let count = |range: &str| range.split(',').nth(1).unwrap().parse::<usize>().unwrap();
+            let body: Vec<&str> = body.lines().take_while(|line| !line.starts_with("diff --git ")).collect();
+            let old_lines = body.iter().filter(|line| !line.starts_with('+')).count();
+            let new_lines = body.iter().filter(|line| !line.starts_with('-')).count();
+            assert_eq!(count(&old[1..]), old_lines, "old count of @@ {}", header);
+            assert_eq!(count(&new[1..]), new_lines, "new count of @@ {}", header);
+        }
+    }
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::blank_lines("\n\n")]
+    fn empty_diff_has_no_chunks(#[case] diff: &str) {
+        assert!(chunk_diff(diff, 1000).is_empty());
+    }
+
+    #[rstest]
+    #[case::exactly_at_the_limit(0)]
+    #[case::well_under_the_limit(1000)]
+    fn single_hunk_under_the_limit_is_one_chunk(#[case] headroom: usize) {
+        let diff = MULTI_HUNK.split("@@ -24,").next().unwrap();
+        let chunks = chunk_diff(diff, diff.len() + headroom);
+        assert_eq!(chunks, [diff]);
+    }
+
+    #[rstest]
+    #[case(160)]
+    #[case(200)]
+    #[case(260)]
+    fn single_hunk_over_the_limit_is_split_between_lines(#[case] max_bytes: usize) {
+        let diff = MULTI_HUNK.split("@@ -24,").next().unwrap();
+        let chunks = chunk_diff(diff, max_bytes);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= max_bytes, "{} bytes:\n{}", chunk.len(), chunk);
+            assert!(chunk.starts_with("diff --git a/src/server.rs b/src/server.rs\n"));
+            assert_hunk_counts(chunk);
+        }
+        assert!(chunks[0].contains("\n@@ -1,"));
+        let reassembled: Vec<&str> = chunks.iter().flat_map(|chunk| content_lines(chunk)).collect();
+        assert_eq!(reassembled, content_lines(diff));
+    }
+
+    #[test]
+    fn multiple_hunks_are_split_between_hunks() {
+        let hunk_size = MULTI_HUNK.split("\n@@ ").map(str::len).max().unwrap();
+        let chunks = chunk_diff(MULTI_HUNK, hunk_size + 150);
+
+        assert!(chunks.len() >= 3);
+        for chunk in &chunks {
+            assert!(chunk.starts_with("diff --git "));
+            assert!(chunk.contains("\n@@ "));
+        }
+        // No hunk was cut, so every header is one of the original ones
+        let headers: Vec<&str> = MULTI_HUNK.lines().filter(|line| line.starts_with("@@ ")).collect();
+        for chunk in &chunks {
+            for header in chunk.lines().filter(|line| line.starts_with("@@ ")) {
+                assert!(headers.contains(&header), "{}", header);
+            }
+        }
+        assert!(chunks.last().unwrap().starts_with("diff --git a/src/config.rs b/src/config.rs\n"));
+    }
+
+    #[rstest]
+    #[case(120)]
+    #[case(300)]
+    #[case(600)]
+    #[case(1000)]
+    #[case(100_000)]
+    fn chunks_contain_every_line_in_order(#[case] max_bytes: usize) {
+        let chunks = chunk_diff(MULTI_HUNK, max_bytes);
+
+        let reassembled: Vec<&str> = chunks.iter().flat_map(|chunk| content_lines(chunk)).collect();
+        assert_eq!(reassembled, content_lines(MULTI_HUNK));
+        for chunk in &chunks {
+            assert_hunk_counts(chunk);
+            assert_eq!(changed_files(chunk).len(), chunk.matches("diff --git ").count());
+        }
+    }
+
+    #[test]
+    fn changed_files_follow_renames_and_skip_deletions() {
+        assert_eq!(changed_files(TRICKY), ["src/new_name.rs", "docs/manual.md", "scripts/release.sh", "src/version.rs"]);
+    }
+
+    #[test]
+    fn parses_every_file_of_the_diff() {
+        let files = parse(TRICKY);
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["src/new_name.rs", "docs/manual.md", "assets/logo.png", "scripts/release.sh", "queries/cleanup.sql", "src/version.rs"]
+        );
+        let text: String = files.iter().map(|file| file.diff.as_str()).collect();
+        assert_eq!(text, TRICKY);
+    }
+
+    #[rstest]
+    #[case::rename_with_changes("src/new_name.rs", "src/old_name.rs", 1)]
+    #[case::pure_rename("docs/manual.md", "docs/guide.md", 0)]
+    fn renames_keep_both_paths(#[case] path: &str, #[case] old_path: &str, #[case] hunks: usize) {
+        let files = parse(TRICKY);
+        let file = file(&files, path);
+        assert!(file.is_renamed());
+        assert_eq!(file.old_path.as_deref(), Some(old_path));
+        assert_eq!(file.new_path.as_deref(), Some(path));
+        assert_eq!(file.hunks.len(), hunks);
+    }
+
+    #[test]
+    fn hunk_lines_are_numbered_on_their_sides() {
+        let files = parse(TRICKY);
+        let hunk = &file(&files, "src/new_name.rs").hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count), (1, 3, 1, 3));
+        assert_eq!(hunk.section, "");
+
+        let removed: Vec<_> = hunk.removed().map(|line| (line.old_number, line.new_number, line.content.as_str())).collect();
+        assert_eq!(removed, [(Some(2), None, "    \"old\"")]);
+        let added: Vec<_> = hunk.added().map(|line| (line.old_number, line.new_number, line.content.as_str())).collect();
+        assert_eq!(added, [(None, Some(2), "    \"new\"")]);
+        assert_eq!((hunk.lines[3].old_number, hunk.lines[3].new_number), (Some(3), Some(3)));
+    }
+
+    #[test]
+    fn binary_and_mode_changes_have_no_hunks() {
+        let files = parse(TRICKY);
+        let binary = file(&files, "assets/logo.png");
+        assert!(binary.binary);
+        assert!(binary.hunks.is_empty());
+
+        let script = file(&files, "scripts/release.sh");
+        assert!(!script.binary && script.hunks.is_empty() && !script.is_renamed());
+        assert_eq!(script.old_mode.as_deref(), Some("100644"));
+        assert_eq!(script.new_mode.as_deref(), Some("100755"));
+    }
+
+    #[test]
+    fn removed_line_that_looks_like_a_header_stays_in_the_hunk() {
+        let files = parse(TRICKY);
+        let deleted = file(&files, "queries/cleanup.sql");
+        assert!(deleted.is_deleted());
+        assert_eq!(deleted.old_mode.as_deref(), Some("100644"));
+        let removed: Vec<&str> = deleted.hunks[0].removed().map(|line| line.content.as_str()).collect();
+        assert_eq!(removed, ["-- remove stale sessions", "DELETE FROM sessions WHERE expired;"]);
+    }
+
+    #[test]
+    fn new_file_without_newline_at_the_end() {
+        let files = parse(TRICKY);
+        let added = file(&files, "src/version.rs");
+        assert!(added.is_added());
+        assert_eq!(added.new_mode.as_deref(), Some("100644"));
+        let hunk = &added.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_count, hunk.new_start, hunk.new_count), (0, 0, 1, 1));
+        assert!(hunk.lines[0].no_newline);
+    }
+
+    #[test]
+    fn plain_unified_diffs_are_parsed_too() {
+        let diff = "--- a.txt\t2024-01-01 10:00:00\n+++ b.txt\t2024-01-01 10:05:00\n@@ -1 +1 @@\n-one\n+two\n\
+            --- c.txt\n+++ c.txt\n@@ -1,2 +1,2 @@ intro\n same\n-old\n+new\n";
+        let files = parse(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].old_path.as_deref(), Some("a.txt"));
+        assert_eq!(files[0].path, "b.txt");
+        assert_eq!(files[1].path, "c.txt");
+        assert_eq!(files[1].hunks[0].section, "intro");
+        assert_eq!(files[1].hunks[0].lines.len(), 3);
+    }
+
+    #[test]
+    fn commentable_lines_are_the_new_side_of_each_hunk() {
+        let lines = commentable_lines(TRICKY);
+        let mut paths: Vec<&str> = lines.keys().map(String::as_str).collect();
+        paths.sort();
+        assert_eq!(paths, ["src/new_name.rs", "src/version.rs"]);
+        assert_eq!(lines["src/new_name.rs"], BTreeSet::from([1, 2, 3]));
+        assert_eq!(lines["src/version.rs"], BTreeSet::from([1]));
+    }
+
+    #[test]
+    fn commentable_lines_count_trimmed_blank_lines_and_plain_diffs() {
+        let trimmed = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n\
+            @@ -10,4 +10,5 @@\n fn a() {\n\n+    b();\n     c();\n }\n";
+        assert_eq!(commentable_lines(trimmed)["src/a.rs"], BTreeSet::from([10, 11, 12, 13, 14]));
+
+        let plain = "--- src/a.rs.orig\n+++ src/a.rs\n@@ -1 +1,2 @@\n x\n+y\n";
+        assert_eq!(commentable_lines(plain)["src/a.rs"], BTreeSet::from([1, 2]));
+    }
+
+    #[test]
+    fn commentable_hunks_keep_hunks_apart() {
+        let hunks = commentable_hunks(MULTI_HUNK);
+        let server = &hunks["src/server.rs"];
+        assert!(server.len() > 1);
+        assert!(server.windows(2).all(|pair| pair[0].end() < pair[1].start()));
+        assert_eq!(commentable_hunks(TRICKY)["src/new_name.rs"], [1..=3]);
+    }
+}
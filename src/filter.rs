@@ -0,0 +1,65 @@
+use std::path::Path;
+use glob::{MatchOptions, Pattern};
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Decides which files of the codebase are read for context, by extension and by path.
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    include_extensions: Vec<String>,
+    exclude_extensions: Vec<String>,
+    exclude_paths: Vec<Pattern>,
+}
+
+impl FileFilter {
+    /// Extensions are given without the leading dot and may span several dots, like
+    /// `min.js`. An empty `include_extensions` allows every extension. Invalid path
+    /// patterns are skipped with a warning.
+    pub fn new(include_extensions: &[String], exclude_extensions: &[String], exclude_paths: &[String]) -> Self {
+        let normalize = |extensions: &[String]| {
+            extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect()
+        };
+
+        FileFilter {
+            include_extensions: normalize(include_extensions),
+            exclude_extensions: normalize(exclude_extensions),
+            exclude_paths: exclude_paths
+                .iter()
+                .filter_map(|pattern| match Pattern::new(pattern) {
+                    Ok(pattern) => Some(pattern),
+                    Err(e) => {
+                        warning!("Ignoring invalid path pattern {:?}: {}", pattern, e);
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the file at `path`, relative to the codebase root, should be read.
+    pub fn allows(&self, path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let has_extension = |ext: &String| name.ends_with(&format!(".{}", ext));
+
+        if !self.include_extensions.is_empty() && !self.include_extensions.iter().any(has_extension) {
+            return false;
+        }
+        if self.exclude_extensions.iter().any(has_extension) {
+            return false;
+        }
+        !self
+            .exclude_paths
+            .iter()
+            .any(|pattern| pattern.matches_path_with(path, MATCH_OPTIONS))
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::LazyLock;
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// `src/foo.rs:42` or `src/foo.rs:42-50`
+static LOCATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?P<file>(?:[\w.-]+/)*[\w.-]+\.(?:rs|py|js|jsx|ts|tsx|go|java|kt|c|h|cc|cpp|hpp|cs|rb|php|swift|scala|sh|toml|ya?ml|json|md|html|css|sql))(?::(?P<line>\d+)(?:-(?P<end>\d+))?)?\b",
+    )
+    .unwrap()
+});
+
+// "line 42" or "lines 42-50" mentioned alongside a bare file name
+static LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\blines? (?P<line>\d+)(?:\s*(?:-|to)\s*(?P<end>\d+))?").unwrap()
+});
+
+static SEVERITY_KEYWORDS: LazyLock<[(Severity, Regex); 3]> = LazyLock::new(|| {
+    [
+        (Severity::Error, Regex::new(r"(?i)\b(?:critical|error|bug|vulnerab\w*|high|panics?|crash\w*)\b").unwrap()),
+        (Severity::Warning, Regex::new(r"(?i)\b(?:warning|medium|potential|risk\w*)\b").unwrap()),
+        (Severity::Suggestion, Regex::new(r"(?i)\b(?:suggest\w*|consider|nit|low|could|optional\w*)\b").unwrap()),
+    ]
+});
+
+// Keywords for each category, checked in order. Used both for section headers and,
+// when a finding sits outside any recognisable section, for the finding text itself.
+static CATEGORY_KEYWORDS: LazyLock<[(Category, Regex); 5]> = LazyLock::new(|| {
+    [
+        (Category::Security, Regex::new(r"(?i)\b(?:secur\w*|vulnerab\w*|inject\w*|xss|csrf|ssrf|sanitiz\w*|secrets?|passwords?|credentials?|auth\w*|unsafe)\b").unwrap()),
+        (Category::Bugs, Regex::new(r"(?i)\b(?:bugs?|issues?|panics?|crash\w*|incorrect\w*|overflow\w*|race|deadlock\w*|null|unwrap|off-by-one|correctness)\b").unwrap()),
+        (Category::Performance, Regex::new(r"(?i)\b(?:perform\w*|slow\w*|allocat\w*|clones?|complexity|cach\w*|memory|latency|efficien\w*)\b").unwrap()),
+        (Category::Style, Regex::new(r"(?i)\b(?:style|naming|formatting|readab\w*|conventions?|idiomatic|best practices?|documentation|comments?)\b").unwrap()),
+        (Category::Suggestions, Regex::new(r"(?i)\b(?:suggest\w*|improve\w*|consider)\b").unwrap()),
+    ]
+});
+
+// An explicit label at the start of a finding, e.g. `[security]` or `**Performance**:`
+static CATEGORY_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^[\[*_]*(bugs?|style|performance|security|suggestions?)[\]*_]*\s*[:\]-]").unwrap()
+});
+
+// Items that only say there is nothing to report, e.g. "None." or "No security issues found."
+static NOTHING_TO_REPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:none|n/a|nothing(?: to report)?|no (?:[\w-]+ )*(?:issues?|concerns?|problems?|changes? needed)(?: [\w-]+)*)\.?$").unwrap()
+});
+
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(?:[-*+]|\d+[.)])\s+").unwrap());
+
+/// How serious a finding is, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Suggestion,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// All severities, most severe first.
+    pub const ALL: [Severity; 4] = [Severity::Error, Severity::Warning, Severity::Info, Severity::Suggestion];
+
+    /// Infers a severity from the wording of a finding, defaulting to `Info`.
+    pub(crate) fn infer(text: &str) -> Self {
+        SEVERITY_KEYWORDS
+            .iter()
+            .find(|(_, pattern)| pattern.is_match(text))
+            .map(|(severity, _)| *severity)
+            .unwrap_or(Severity::Info)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Suggestion => "suggestion",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// The review areas the prompt asks the model to cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Bugs,
+    Style,
+    Performance,
+    Security,
+    Suggestions,
+}
+
+impl Category {
+    /// Matches category keywords in a section header or finding text.
+    pub(crate) fn from_keywords(text: &str) -> Option<Self> {
+        CATEGORY_KEYWORDS
+            .iter()
+            .find(|(_, pattern)| pattern.is_match(text))
+            .map(|(category, _)| *category)
+    }
+
+    fn from_label(text: &str) -> Option<Self> {
+        let caps = CATEGORY_LABEL.captures(text)?;
+        match caps[1].to_ascii_lowercase().trim_end_matches('s') {
+            "bug" => Some(Category::Bugs),
+            "style" => Some(Category::Style),
+            "performance" => Some(Category::Performance),
+            "security" => Some(Category::Security),
+            "suggestion" => Some(Category::Suggestions),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Category::Bugs => "bugs",
+            Category::Style => "style",
+            Category::Performance => "performance",
+            Category::Security => "security",
+            Category::Suggestions => "suggestions",
+        })
+    }
+}
+
+/// A single review comment extracted from the model's output.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub end_line: Option<u32>,
+    pub severity: Severity,
+    pub category: Category,
+    pub message: String,
+    /// How to fix it, when the model said so separately from the message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    /// `section` is the category of the review section the finding appeared under, if any.
+    /// An explicit label in the finding itself takes precedence over it.
+    fn from_text(text: &str, section: Option<Category>) -> Self {
+        let mut finding = Finding {
+            file: None,
+            line: None,
+            end_line: None,
+            severity: Severity::infer(text),
+            category: Category::from_label(text.trim())
+                .or(section)
+                .or_else(|| Category::from_keywords(text))
+                .unwrap_or(Category::Suggestions),
+            message: text.trim().to_string(),
+            suggestion: None,
+        };
+
+        if let Some(caps) = LOCATION.captures(text) {
+            finding.file = Some(caps["file"].to_string());
+            finding.line = caps.name("line").and_then(|m| m.as_str().parse().ok());
+            finding.end_line = caps.name("end").and_then(|m| m.as_str().parse().ok());
+
+            if finding.line.is_none() {
+                if let Some(caps) = LINE.captures(text) {
+                    finding.line = caps["line"].parse().ok();
+                    finding.end_line = caps.name("end").and_then(|m| m.as_str().parse().ok());
+                }
+            }
+        }
+
+        finding
+    }
+}
+
+/// Splits a free-text review into findings, one per list item. Section headers such as
+/// `1. Potential bugs:` are skipped but set the category of the items below them, and
+/// indented continuation lines and fenced code blocks are kept with the item they follow.
+pub fn parse_findings(review: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut current: Option<String> = None;
+    let mut section: Option<Category> = None;
+    let mut in_fence = false;
+
+    let flush = |current: &mut Option<String>, section: Option<Category>, findings: &mut Vec<Finding>| {
+        if let Some(text) = current.take() {
+            findings.push(Finding::from_text(&text, section));
+        }
+    };
+
+    for line in review.lines() {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || in_fence {
+            if trimmed.starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if let Some(text) = current.as_mut() {
+                text.push('\n');
+                text.push_str(line);
+            }
+            continue;
+        }
+
+        if let Some(item) = LIST_ITEM.find(trimmed) {
+            flush(&mut current, section, &mut findings);
+            let text = &trimmed[item.end()..];
+            if is_header(text) {
+                section = Category::from_keywords(text);
+            } else if !NOTHING_TO_REPORT.is_match(text.trim()) {
+                current = Some(text.to_string());
+            }
+        } else if !trimmed.is_empty() && line.starts_with(char::is_whitespace) {
+            if let Some(text) = current.as_mut() {
+                text.push('\n');
+                text.push_str(trimmed);
+            }
+        } else {
+            flush(&mut current, section, &mut findings);
+            if trimmed.starts_with('#') || is_header(trimmed) {
+                section = Category::from_keywords(trimmed);
+            }
+        }
+    }
+    flush(&mut current, section, &mut findings);
+
+    findings
+}
+
+/// A line like `Security considerations:`, `**Security:**` or `**Security**`.
+fn is_header(text: &str) -> bool {
+    let text = text.trim();
+    let unwrapped = text.trim_matches(|c| c == '*' || c == '_').trim_end();
+    unwrapped.ends_with(':') || (text.len() > 4 && text.starts_with("**") && text.ends_with("**") && !unwrapped.contains("**"))
+}
+
+/// Lowercases the message and strips markdown and extra whitespace, so cosmetic
+/// differences in the model's wording don't change what a finding is matched by.
+pub fn normalize_message(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| !matches!(c, '*' | '`' | '_'))
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Drops findings less severe than `min`, returning how many were dropped per severity.
+pub fn filter_by_severity(findings: &mut Vec<Finding>, min: Severity) -> BTreeMap<Severity, usize> {
+    let mut suppressed = BTreeMap::new();
+
+    findings.retain(|finding| {
+        let keep = finding.severity >= min;
+        if !keep {
+            *suppressed.entry(finding.severity).or_insert(0) += 1;
+        }
+        keep
+    });
+
+    suppressed
+}
+
+/// Keeps only findings in one of `categories`, returning how many were dropped.
+pub fn filter_by_category(findings: &mut Vec<Finding>, categories: &[Category]) -> usize {
+    let before = findings.len();
+    findings.retain(|finding| categories.contains(&finding.category));
+    before - findings.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    const ONE_OF_EACH: &str = "\
+- `src/a.rs:1` first (error)
+- `src/a.rs:2` second (warning)
+- `src/a.rs:3` third (warning)
+- `src/a.rs:4` fourth (info)
+- `src/a.rs:5` fifth (suggestion)
+- `src/a.rs:6` sixth (suggestion)
+";
+
+    #[test]
+    fn severities_order_from_suggestion_to_error() {
+        assert!(Severity::Suggestion < Severity::Info);
+        assert!(Severity::Info < Severity::Warning);
+        assert!(Severity::Warning < Severity::Error);
+        assert!(Severity::ALL.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[rstest]
+    #[case::suggestion(Severity::Suggestion, 6, &[])]
+    #[case::info(Severity::Info, 4, &[(Severity::Suggestion, 2)])]
+    #[case::warning(Severity::Warning, 3, &[(Severity::Suggestion, 2), (Severity::Info, 1)])]
+    #[case::error(Severity::Error, 1, &[(Severity::Suggestion, 2), (Severity::Info, 1), (Severity::Warning, 2)])]
+    fn severity_filter_counts_what_it_drops(
+        #[case] min: Severity,
+        #[case] kept: usize,
+        #[case] suppressed: &[(Severity, usize)],
+    ) {
+        let mut findings = parse_findings(ONE_OF_EACH);
+        let dropped = filter_by_severity(&mut findings, min);
+        assert_eq!(findings.len(), kept);
+        assert!(findings.iter().all(|finding| finding.severity >= min));
+        assert_eq!(dropped, suppressed.iter().copied().collect::<BTreeMap<_, _>>());
+    }
+
+    #[test]
+    fn explicit_labels_beat_the_section() {
+        let findings = parse_findings("## Security\n\n- **Performance**: `src/a.rs:3` the unwrap here panics (error)\n- [style] odd name\n");
+        let categories: Vec<Category> = findings.iter().map(|finding| finding.category).collect();
+        assert_eq!(categories, [Category::Performance, Category::Style]);
+    }
+
+    #[test]
+    fn section_headings_beat_keywords() {
+        let findings = parse_findings(
+            "1. Potential bugs:\n   - `src/a.rs:1` this clones the vector on every call\n\
+            **Style**\n- `src/a.rs:2` the unwrap here panics\n",
+        );
+        let categories: Vec<Category> = findings.iter().map(|finding| finding.category).collect();
+        assert_eq!(categories, [Category::Bugs, Category::Style]);
+    }
+
+    #[rstest]
+    #[case::security("`src/a.rs:1` the query is open to SQL injection", Category::Security)]
+    #[case::bugs("`src/a.rs:2` the unwrap here panics on bad input", Category::Bugs)]
+    #[case::performance("`src/a.rs:3` this clones the vector on every call", Category::Performance)]
+    #[case::style("`src/a.rs:4` the naming doesn't follow the conventions", Category::Style)]
+    #[case::nothing_matches("`src/a.rs:5` rename it", Category::Suggestions)]
+    fn keywords_give_the_category_outside_sections(#[case] text: &str, #[case] category: Category) {
+        let findings = parse_findings(&format!("- {}\n", text));
+        assert_eq!(findings[0].category, category);
+    }
+
+    #[test]
+    fn category_filter_keeps_only_the_requested_categories() {
+        let mut findings = parse_findings(
+            "- [security] secret in the log\n- [bugs] off by one\n- [style] odd name\n- [performance] clones\n- [bugs] overflow\n",
+        );
+        assert_eq!(filter_by_category(&mut findings, &[Category::Bugs, Category::Security]), 2);
+        let categories: Vec<Category> = findings.iter().map(|finding| finding.category).collect();
+        assert_eq!(categories, [Category::Security, Category::Bugs, Category::Bugs]);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::diff;
+use crate::findings::Finding;
+use crate::ReviewResult;
+
+const PREFIX: &str = "# REVIEW: ";
+
+/// One finding as comment lines, continuation lines indented under the first.
+fn annotation(finding: &Finding) -> String {
+    let mut lines = finding.message.lines();
+    let mut out = format!("{}[{}] {}\n", PREFIX, finding.severity, lines.next().unwrap_or_default());
+    for line in lines {
+        out.push_str(&format!("#{}{}\n", " ".repeat(PREFIX.len() - 1), line));
+    }
+    out
+}
+
+/// The reviewed diff with every finding inserted as `# REVIEW:` comment lines after the line
+/// it refers to. Findings on a file but not on a line of its hunks go after the file's first
+/// hunk header, and findings without a file before the whole diff.
+pub fn render(result: &ReviewResult) -> String {
+    let mut out = String::new();
+    let mut placed = vec![false; result.findings.len()];
+    let mut annotate = |out: &mut String, matches: &dyn Fn(&Finding) -> bool| {
+        for (finding, placed) in result.findings.iter().zip(placed.iter_mut()) {
+            if !*placed && matches(finding) {
+                out.push_str(&annotation(finding));
+                *placed = true;
+            }
+        }
+    };
+
+    annotate(&mut out, &|finding| finding.file.is_none());
+
+    for file in diff::parse(&result.patch) {
+        let in_file = |finding: &Finding| finding.file.as_deref() == Some(file.path.as_str());
+        let in_hunks = |line: u32| {
+            file.hunks.iter().flat_map(|hunk| &hunk.lines).any(|hunk_line| hunk_line.new_number == Some(line))
+        };
+        // Walks the file's text alongside its parsed hunks, to keep every line as it was
+        let mut hunks = file.hunks.iter();
+        let mut hunk: Option<&diff::Hunk> = None;
+        let mut next_line = 0;
+        let mut first_hunk = true;
+
+        for line in file.diff.lines() {
+            out.push_str(line);
+            out.push('\n');
+
+            let hunk_line = hunk.and_then(|hunk| hunk.lines.get(next_line));
+            if let Some(hunk_line) = hunk_line.filter(|_| !line.starts_with('\\')) {
+                next_line += 1;
+                if let Some(number) = hunk_line.new_number {
+                    annotate(&mut out, &|finding| in_file(finding) && finding.line == Some(number));
+                }
+            } else if line.starts_with("@@ ") {
+                hunk = hunks.next();
+                next_line = 0;
+                if first_hunk {
+                    first_hunk = false;
+                    annotate(&mut out, &|finding| in_file(finding) && !finding.line.is_some_and(in_hunks));
+                }
+            }
+        }
+        // A file without hunks, like a binary file, gets its findings after its header
+        annotate(&mut out, &in_file);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::parse_findings;
+
+    const DIFF: &str = include_str!("../../tests/fixtures/unwrap.diff");
+
+    #[test]
+    fn findings_follow_their_lines() {
+        let review = "- `src/lib.rs:2` unwrap panics on bad input (error)\n\
+            - `src/lib.rs` has no tests (suggestion)\n\
+            - The commit message is vague (info)\n";
+        let result = ReviewResult {
+            patch: DIFF.to_string(),
+            findings: parse_findings(review),
+            ..ReviewResult::default()
+        };
+
+        let out = render(&result);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("# REVIEW: [info] The commit message is vague"));
+        let at = |prefix: &str| lines.iter().position(|line| line.starts_with(prefix)).unwrap();
+        assert_eq!(at("# REVIEW: [suggestion]"), at("@@ ") + 1);
+        assert_eq!(at("# REVIEW: [error]"), at("+    input.parse().unwrap()") + 1);
+        let stripped: String = out.lines().filter(|line| !line.starts_with('#')).map(|line| format!("{}\n", line)).collect();
+        assert_eq!(stripped, DIFF);
+    }
+}
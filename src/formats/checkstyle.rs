@@ -0,0 +1,141 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io;
+use quick_xml::escape::escape;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesDecl, Event};
+use quick_xml::name::QName;
+use quick_xml::Writer;
+use crate::findings::{Finding, Severity};
+use crate::ReviewResult;
+
+/// Renders findings as checkstyle XML, one `<file>` element per file. As with rdjson,
+/// findings without a file are left out and findings without a line go on line 1
+/// unless `drop_unlocated` is set.
+pub fn render(result: &ReviewResult, drop_unlocated: bool) -> io::Result<String> {
+    let mut files: BTreeMap<&str, Vec<&Finding>> = BTreeMap::new();
+    for finding in &result.findings {
+        if drop_unlocated && finding.line.is_none() {
+            continue;
+        }
+        if let Some(file) = &finding.file {
+            files.entry(file).or_default().push(finding);
+        }
+    }
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer
+        .create_element("checkstyle")
+        .with_attribute(("version", "4.3"))
+        .write_inner_content(|writer| {
+            for (file, findings) in &files {
+                writer
+                    .create_element("file")
+                    .with_attribute(attribute("name", file))
+                    .write_inner_content(|writer| {
+                        for finding in findings {
+                            let line = finding.line.unwrap_or(1).to_string();
+                            writer
+                                .create_element("error")
+                                .with_attribute(("line", line.as_str()))
+                                .with_attribute(("severity", severity(finding.severity)))
+                                .with_attribute(attribute("message", &finding.message))
+                                .with_attribute(("source", "code_reviewer"))
+                                .write_empty()?;
+                        }
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+
+    String::from_utf8(writer.into_inner()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Suggestion => "info",
+    }
+}
+
+/// XML parsers turn literal line breaks and tabs in attribute values into spaces, so
+/// multi-line messages and odd file names keep theirs as character references. Control
+/// characters aren't allowed in XML 1.0 at all and are dropped.
+fn attribute(key: &'static str, text: &str) -> Attribute<'static> {
+    let text: String = text.chars().filter(|c| !c.is_control() || matches!(c, '\n' | '\t')).collect();
+    let value = escape(text.as_str()).replace('\n', "&#10;").replace('\t', "&#9;");
+    Attribute { key: QName(key), value: Cow::Owned(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use quick_xml::events::Event;
+    use quick_xml::{Reader, XmlVersion};
+    use super::*;
+    use crate::findings::Category;
+    use crate::formats::{OutputFormat, RenderOptions};
+
+    const GOLDEN: &str = include_str!("../../tests/fixtures/checkstyle.xml");
+
+    /// Files and messages with everything XML attributes have to escape.
+    const TRICKY: [(&str, Option<u32>, Severity, &str); 4] = [
+        ("src/\"quoted\" & 'apostrophes'.rs", Some(3), Severity::Error, "Don't write `a < b && b > c` as \"a<b>c\""),
+        ("src/ünïcödé/naïve.rs", None, Severity::Suggestion, "Naïve Unicode: 日本語, emoji 🦀, and ẞ"),
+        ("src/tab\tand\nnewline.rs", Some(7), Severity::Warning, "First line\n\tindented second line\nthird line"),
+        ("src/control\u{1}\u{1b}[31m.rs", Some(9), Severity::Info, "Bell\u{7}, escape \u{1b}[0m, null\u{0} and form feed\u{c} are dropped"),
+    ];
+
+    fn tricky_result() -> ReviewResult {
+        let findings = TRICKY
+            .iter()
+            .map(|(file, line, severity, message)| Finding {
+                file: Some(file.to_string()),
+                line: *line,
+                end_line: None,
+                severity: *severity,
+                category: Category::Bugs,
+                message: message.to_string(),
+                suggestion: None,
+            })
+            .collect();
+        ReviewResult { findings, ..ReviewResult::default() }
+    }
+
+    #[test]
+    fn tricky_characters_match_the_golden_file() {
+        let out = crate::formats::render(OutputFormat::Checkstyle, &tricky_result(), &RenderOptions::default()).unwrap();
+        assert_eq!(out, GOLDEN);
+    }
+
+    #[test]
+    fn escaped_attributes_read_back_without_control_characters() {
+        let mut reader = Reader::from_str(GOLDEN);
+        let mut read = Vec::new();
+        let mut file = String::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(element) | Event::Empty(element) => {
+                    let value = |key: &str| {
+                        element.try_get_attribute(key).unwrap().unwrap().normalized_value(XmlVersion::Explicit1_0).unwrap().into_owned()
+                    };
+                    match element.name().as_ref() {
+                        "file" => file = value("name"),
+                        "error" => read.push((file.clone(), value("message"))),
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        let without_controls = |text: &str| -> String { text.chars().filter(|c| !c.is_control() || matches!(c, '\n' | '\t')).collect() };
+        let mut expected: Vec<(String, String)> =
+            TRICKY.iter().map(|(file, _, _, message)| (without_controls(file), without_controls(message))).collect();
+        expected.sort();
+        assert_eq!(read, expected);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFiles;
+use codespan_reporting::term::termcolor::{Ansi, NoColor, WriteColor};
+use codespan_reporting::term::{self, Config};
+use crate::findings::{Finding, Severity};
+use crate::git;
+use crate::ReviewResult;
+use super::RenderOptions;
+
+/// Where the source lines shown with each finding are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnippetSource {
+    /// The files as they are on disk
+    #[default]
+    WorkingTree,
+    /// The staged version of the files, for reviews of the staged changes
+    Index,
+}
+
+impl SnippetSource {
+    fn read(self, path: &str) -> Option<String> {
+        match self {
+            SnippetSource::WorkingTree => fs::read_to_string(path).ok(),
+            SnippetSource::Index => git::run(&["show", &format!(":{}", path)]).ok(),
+        }
+    }
+}
+
+/// Byte range of lines `start` to `end` of `source`, counted from 1 and without the last
+/// line's newline, or `None` when `start` is past the end of the file.
+fn line_range(source: &str, start: u32, end: u32) -> Option<Range<usize>> {
+    let mut offsets = std::iter::once(0).chain(source.match_indices('\n').map(|(index, _)| index + 1));
+    let begin = offsets.nth(start.checked_sub(1)? as usize).filter(|&begin| begin < source.len())?;
+    let last = source[begin..]
+        .split_inclusive('\n')
+        .take(end.saturating_sub(start) as usize + 1)
+        .map(str::len)
+        .sum::<usize>();
+    Some(begin..(begin + source[begin..begin + last].trim_end_matches(['\n', '\r']).len()))
+}
+
+fn diagnostic(finding: &Finding) -> Diagnostic<usize> {
+    let diagnostic = match finding.severity {
+        Severity::Error => Diagnostic::error(),
+        Severity::Warning => Diagnostic::warning(),
+        Severity::Info => Diagnostic::note(),
+        Severity::Suggestion => Diagnostic::help(),
+    };
+    let mut lines = finding.message.lines();
+    let mut notes: Vec<String> = lines.next().into_iter().skip(1).chain(lines).map(str::to_string).collect();
+    notes.retain(|note| !note.trim().is_empty());
+    if let Some(suggestion) = &finding.suggestion {
+        notes.push(format!("suggestion: {}", suggestion));
+    }
+    diagnostic
+        .with_code(finding.category.to_string())
+        .with_message(finding.message.lines().next().unwrap_or_default())
+        .with_notes(notes)
+}
+
+/// Every finding as a compiler-style diagnostic: a severity header, the flagged lines of the
+/// file with a few lines around them, then the rest of the message and the suggested fix.
+/// When the file can't be read or the model named a line past its end, the location is only
+/// mentioned.
+pub fn render(result: &ReviewResult, options: &RenderOptions) -> Result<String, Box<dyn Error>> {
+    render_with(result, options.color, |path| options.snippets.read(path))
+}
+
+fn render_with(result: &ReviewResult, color: bool, read: impl Fn(&str) -> Option<String>) -> Result<String, Box<dyn Error>> {
+    let mut files = SimpleFiles::new();
+    let mut file_ids: HashMap<&str, Option<usize>> = HashMap::new();
+    let mut diagnostics = Vec::new();
+
+    for finding in &result.findings {
+        let mut diagnostic = diagnostic(finding);
+
+        if let Some(path) = finding.file.as_deref() {
+            let file_id = *file_ids
+                .entry(path)
+                .or_insert_with(|| read(path).map(|source| files.add(path.to_string(), source)));
+            let range = finding.line.zip(file_id).and_then(|(line, file_id)| {
+                let source = files.get(file_id).ok()?.source();
+                line_range(source, line, finding.end_line.unwrap_or(line)).map(|range| (file_id, range))
+            });
+            let location = match (range, finding.line) {
+                (Some((file_id, range)), _) => {
+                    diagnostic = diagnostic.with_labels(vec![Label::primary(file_id, range)]);
+                    None
+                }
+                // The model named a line the file doesn't have
+                (None, Some(line)) if file_id.is_some() => Some(format!("at {}:{}, past the end of the file", path, line)),
+                (None, Some(line)) => Some(format!("at {}:{}", path, line)),
+                (None, None) => Some(format!("in {}", path)),
+            };
+            if let Some(location) = location {
+                diagnostic.notes.insert(0, location);
+            }
+        }
+        diagnostics.push(diagnostic);
+    }
+
+    let mut out = Vec::new();
+    if color {
+        emit(&mut Ansi::new(&mut out), &files, &diagnostics)?;
+    } else {
+        emit(&mut NoColor::new(&mut out), &files, &diagnostics)?;
+    }
+    Ok(String::from_utf8(out)?)
+}
+
+fn emit(writer: &mut impl WriteColor, files: &SimpleFiles<String, String>, diagnostics: &[Diagnostic<usize>]) -> Result<(), Box<dyn Error>> {
+    // A few lines around each flagged line, like rustc
+    let config = Config { before_label_lines: 2, after_label_lines: 2, ..Config::default() };
+    for diagnostic in diagnostics {
+        term::emit_to_write_style(writer, &config, files, diagnostic)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::parse_findings;
+
+    const SOURCE: &str = "fn parse(input: &str) -> u32 {\n    input.parse().unwrap()\n}\n";
+
+    fn render(review: &str) -> String {
+        let result = ReviewResult { findings: parse_findings(review), ..ReviewResult::default() };
+        render_with(&result, false, |path| (path == "src/lib.rs").then(|| SOURCE.to_string())).unwrap()
+    }
+
+    #[test]
+    fn shows_the_flagged_line() {
+        let out = render("- `src/lib.rs:2` unwrap panics on bad input (error)");
+        assert!(out.starts_with("error[bugs]: `src/lib.rs:2` unwrap panics on bad input (error)"), "{}", out);
+        assert!(out.contains("┌─ src/lib.rs:2:1"), "{}", out);
+        assert!(out.contains("2 │     input.parse().unwrap()"), "{}", out);
+    }
+
+    #[test]
+    fn line_past_the_end_is_only_mentioned() {
+        let out = render("- `src/lib.rs:40` unwrap panics (warning)");
+        assert!(out.contains("= at src/lib.rs:40, past the end of the file"), "{}", out);
+        assert!(!out.contains("┌─"), "{}", out);
+    }
+
+    #[test]
+    fn unreadable_file_and_no_file() {
+        let out = render("- `src/gone.rs:3` odd naming (suggestion)\n- The commit message is vague (info)\n");
+        assert!(out.contains("help[style]: `src/gone.rs:3` odd naming (suggestion)\n = at src/gone.rs:3"), "{}", out);
+        assert!(out.contains("note[suggestions]: The commit message is vague (info)"), "{}", out);
+    }
+
+    #[test]
+    fn line_ranges() {
+        assert_eq!(line_range(SOURCE, 2, 2), Some(31..57));
+        assert_eq!(line_range(SOURCE, 1, 3).map(|range| &SOURCE[range]), Some(SOURCE.trim_end()));
+        assert_eq!(line_range(SOURCE, 4, 4), None);
+        assert_eq!(line_range(SOURCE, 0, 1), None);
+    }
+}
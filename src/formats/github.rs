@@ -0,0 +1,48 @@
+use crate::findings::{Finding, Severity};
+use crate::ReviewResult;
+
+/// Escapes the message part of a workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escapes a `key=value` property of a workflow command.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn command(finding: &Finding) -> String {
+    let level = match finding.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info | Severity::Suggestion => "notice",
+    };
+
+    let mut properties = Vec::new();
+    if let Some(file) = &finding.file {
+        properties.push(format!("file={}", escape_property(file)));
+        if let Some(line) = finding.line {
+            properties.push(format!("line={}", line));
+            if let Some(end) = finding.end_line.filter(|end| *end > line) {
+                properties.push(format!("endLine={}", end));
+            }
+        }
+    }
+
+    if properties.is_empty() {
+        format!("::{}::{}", level, escape_data(&finding.message))
+    } else {
+        format!("::{} {}::{}", level, properties.join(","), escape_data(&finding.message))
+    }
+}
+
+/// Renders one GitHub Actions workflow command per finding, which Actions turns into
+/// annotations on the run and the pull request.
+pub fn render(result: &ReviewResult) -> String {
+    result
+        .findings
+        .iter()
+        .map(command)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,61 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::findings::{normalize_message, Finding, Severity};
+use crate::ReviewResult;
+
+/// One entry of a GitLab Code Quality (CodeClimate-style) report.
+#[derive(Serialize)]
+struct Issue<'a> {
+    description: &'a str,
+    check_name: String,
+    fingerprint: String,
+    severity: &'static str,
+    location: Location<'a>,
+}
+
+#[derive(Serialize)]
+struct Location<'a> {
+    path: &'a str,
+    lines: Lines,
+}
+
+#[derive(Serialize)]
+struct Lines {
+    begin: u32,
+}
+
+/// A stable identifier for a finding, so GitLab can tell which issues are new, fixed or
+/// unchanged between pipelines.
+fn fingerprint(path: &str, line: u32, message: &str) -> String {
+    let digest = Sha256::digest(format!("{}:{}:{}", path, line, normalize_message(message)));
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl<'a> Issue<'a> {
+    /// Code Quality issues need a path, so findings without a file are left out.
+    fn new(finding: &'a Finding) -> Option<Self> {
+        let path = finding.file.as_deref()?;
+        let line = finding.line.unwrap_or(1);
+
+        Some(Issue {
+            description: &finding.message,
+            check_name: format!("code_reviewer/{}", finding.category),
+            fingerprint: fingerprint(path, line, &finding.message),
+            severity: match finding.severity {
+                Severity::Error => "critical",
+                Severity::Warning => "major",
+                Severity::Info => "minor",
+                Severity::Suggestion => "info",
+            },
+            location: Location {
+                path,
+                lines: Lines { begin: line },
+            },
+        })
+    }
+}
+
+pub fn render(result: &ReviewResult) -> Result<String, serde_json::Error> {
+    let issues: Vec<Issue> = result.findings.iter().filter_map(Issue::new).collect();
+    serde_json::to_string_pretty(&issues)
+}
@@ -0,0 +1,109 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use quick_xml::escape::escape;
+use crate::ReviewResult;
+
+const STYLE: &str = "\
+body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Helvetica, Arial, sans-serif; max-width: 960px; margin: 2em auto; padding: 0 1em; color: #1f2328; line-height: 1.5; }
+header { border-bottom: 1px solid #d0d7de; margin-bottom: 1.5em; }
+dl { display: grid; grid-template-columns: max-content auto; gap: 0.2em 1em; }
+dt { font-weight: 600; }
+dd { margin: 0; }
+pre { background: #f6f8fa; padding: 1em; overflow-x: auto; border-radius: 6px; }
+code { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; font-size: 0.9em; }
+.diff span { display: block; }
+.diff .add { background: #dafbe1; }
+.diff .del { background: #ffebe9; }
+.diff .hunk { color: #8250df; }
+.diff .meta { color: #57606a; font-weight: 600; }
+";
+
+/// Renders a standalone HTML page with the review, the diff and the context files.
+/// Raw HTML in the model's markdown is shown as text rather than passed through, so
+/// markup quoted from the code under review can't break the page.
+pub fn render(result: &ReviewResult) -> String {
+    let title = match (&result.repository, &result.branch) {
+        (Some(repository), Some(branch)) => format!("Code review: {}/{}", repository, branch),
+        (Some(repository), None) => format!("Code review: {}", repository),
+        _ => "Code review".to_string(),
+    };
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n", escape(&title), STYLE));
+
+    out.push_str(&format!("<header>\n<h1>{}</h1>\n<dl>\n", escape(&title)));
+    let mut metadata = vec![("Model", result.model.clone()), ("Timestamp", result.timestamp.clone())];
+    if let Some(repository) = &result.repository {
+        metadata.push(("Repository", repository.clone()));
+    }
+    if let Some(branch) = &result.branch {
+        metadata.push(("Branch", branch.clone()));
+    }
+    if let Some(commit) = &result.commit {
+        metadata.push(("Commit", commit.clone()));
+    }
+    metadata.push((
+        "Changes",
+        format!(
+            "{} files, +{} -{}",
+            result.diff.files_changed, result.diff.insertions, result.diff.deletions
+        ),
+    ));
+    if let Some(score) = &result.score {
+        metadata.push(("Score", format!("{:.1}/10", score.overall)));
+    }
+    for (name, value) in metadata {
+        out.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", name, escape(&value)));
+    }
+    out.push_str("</dl>\n</header>\n");
+
+    out.push_str("<main>\n<section>\n<h2>Review</h2>\n");
+    out.push_str(&markdown(&result.review));
+    out.push_str("</section>\n");
+
+    if !result.context_files.is_empty() {
+        out.push_str("<section>\n<h2>Context files</h2>\n<ul>\n");
+        for file in &result.context_files {
+            out.push_str(&format!("<li><code>{}</code></li>\n", escape(file)));
+        }
+        out.push_str("</ul>\n</section>\n");
+    }
+
+    out.push_str("<section>\n<h2>Diff</h2>\n<pre class=\"diff\"><code>");
+    for line in result.patch.lines() {
+        let class = if line.starts_with("+++") || line.starts_with("---") || line.starts_with("diff --git") {
+            "meta"
+        } else if line.starts_with('+') {
+            "add"
+        } else if line.starts_with('-') {
+            "del"
+        } else if line.starts_with("@@") {
+            "hunk"
+        } else {
+            "context"
+        };
+        out.push_str(&format!("<span class=\"{}\">{}</span>", class, escape(line)));
+    }
+    out.push_str("</code></pre>\n</section>\n</main>\n</body>\n</html>\n");
+
+    out
+}
+
+fn markdown(text: &str) -> String {
+    let events = Parser::new_ext(text, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        Event::Start(Tag::Link { link_type, dest_url, title, id }) if is_script_url(&dest_url) => {
+            Event::Start(Tag::Link { link_type, dest_url: CowStr::Borrowed("#"), title, id })
+        }
+        event => event,
+    });
+
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+fn is_script_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    url.starts_with("javascript:") || url.starts_with("vbscript:") || url.starts_with("data:")
+}
@@ -0,0 +1,20 @@
+use serde::Serialize;
+use crate::ReviewResult;
+
+/// Bumped whenever a field is removed or changes meaning, so consumers can gate on it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The document written by `--format json`.
+#[derive(Debug, Serialize)]
+pub struct JsonReport<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub result: &'a ReviewResult,
+}
+
+pub fn render(result: &ReviewResult) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&JsonReport {
+        version: SCHEMA_VERSION,
+        result,
+    })
+}
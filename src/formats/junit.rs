@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::io;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use quick_xml::Writer;
+use crate::findings::{Finding, Severity};
+use crate::report;
+use crate::ReviewResult;
+
+/// Findings at or above this severity fail their file's testcase unless `--fail-on` sets
+/// another threshold. Less severe findings are listed in the testcase's output without
+/// failing it.
+const DEFAULT_FAILURE_SEVERITY: Severity = Severity::Warning;
+
+/// Testcase name for findings that don't name a file.
+const UNLOCATED: &str = "(no file)";
+
+/// Renders the review as a JUnit test suite with one testcase per reviewed file. The suite
+/// is named after the repository and branch, and its time is the review's wall time. Files
+/// with findings at or above `fail_on` fail, so the suite agrees with the exit status.
+pub fn render(result: &ReviewResult, fail_on: Option<Severity>) -> io::Result<String> {
+    let threshold = fail_on.unwrap_or(DEFAULT_FAILURE_SEVERITY);
+    let mut files: BTreeMap<&str, Vec<&Finding>> =
+        result.files.iter().map(|file| (file.as_str(), Vec::new())).collect();
+    for finding in &result.findings {
+        files.entry(finding.file.as_deref().unwrap_or(UNLOCATED)).or_default().push(finding);
+    }
+
+    let failures = files
+        .values()
+        .filter(|findings| findings.iter().any(|f| f.severity >= threshold))
+        .count();
+    let tests = files.len().to_string();
+    let failures = failures.to_string();
+    let time = format!("{:.3}", result.usage.wall_time.as_secs_f64());
+    let name = suite_name(result);
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer
+        .create_element("testsuites")
+        .with_attributes([("name", "code_reviewer"), ("tests", &tests), ("failures", &failures), ("time", &time)])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("testsuite")
+                .with_attributes([
+                    ("name", name.as_str()),
+                    ("tests", &tests),
+                    ("failures", &failures),
+                    ("errors", "0"),
+                    ("skipped", "0"),
+                    ("time", &time),
+                    ("timestamp", &result.timestamp),
+                ])
+                .write_inner_content(|writer| {
+                    for (file, findings) in &files {
+                        write_testcase(writer, file, findings, threshold)?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+
+    String::from_utf8(writer.into_inner()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_testcase(writer: &mut Writer<Vec<u8>>, file: &str, findings: &[&Finding], threshold: Severity) -> io::Result<()> {
+    let testcase = writer
+        .create_element("testcase")
+        .with_attributes([("name", file), ("classname", "code_reviewer")]);
+    if findings.is_empty() {
+        testcase.write_empty()?;
+        return Ok(());
+    }
+
+    let mut findings: Vec<Finding> = findings.iter().map(|f| (*f).clone()).collect();
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    let (failing, passing): (Vec<Finding>, Vec<Finding>) =
+        findings.into_iter().partition(|f| f.severity >= threshold);
+    testcase.write_inner_content(|writer| {
+        if !failing.is_empty() {
+            let plural = if failing.len() == 1 { "finding" } else { "findings" };
+            let message = format!("{} {}", failing.len(), plural);
+            writer
+                .create_element("failure")
+                .with_attributes([("message", message.as_str()), ("type", &failing[0].severity.to_string())])
+                .write_text_content(BytesText::new(&report::findings_text(&failing)))?;
+        }
+        if !passing.is_empty() {
+            writer
+                .create_element("system-out")
+                .write_text_content(BytesText::new(&report::findings_text(&passing)))?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+fn suite_name(result: &ReviewResult) -> String {
+    match (&result.repository, &result.branch) {
+        (Some(repository), Some(branch)) => format!("{}/{}", repository, branch),
+        (Some(repository), None) => repository.clone(),
+        _ => "code_reviewer".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use rstest::rstest;
+    use super::*;
+    use crate::findings::parse_findings;
+
+    fn result() -> ReviewResult {
+        let mut result = ReviewResult {
+            files: vec!["src/clean.rs".to_string(), "src/lib.rs".to_string(), "src/style.rs".to_string()],
+            findings: parse_findings(
+                "- `src/lib.rs:2` unwrap panics (error)\n\
+                - `src/lib.rs:5` odd name (suggestion)\n\
+                - `src/style.rs:1` odd name (info)\n\
+                - `src/style.rs:4` odd spacing (suggestion)\n\
+                - The commit message is vague (warning)\n",
+            ),
+            ..ReviewResult::default()
+        };
+        result.usage.wall_time = Duration::from_millis(2500);
+        result
+    }
+
+    /// The XML of the testcase named `name`.
+    fn testcase<'a>(out: &'a str, name: &str) -> &'a str {
+        let start = out.find(&format!("<testcase name=\"{}\"", name)).unwrap();
+        let rest = &out[start..];
+        let end = match (rest.find("/>"), rest.find('>')) {
+            (Some(empty), Some(open)) if empty < open => empty + "/>".len(),
+            _ => rest.find("</testcase>").unwrap() + "</testcase>".len(),
+        };
+        &rest[..end]
+    }
+
+    #[test]
+    fn files_without_findings_pass() {
+        let out = render(&result(), None).unwrap();
+        assert_eq!(testcase(&out, "src/clean.rs"), r#"<testcase name="src/clean.rs" classname="code_reviewer"/>"#);
+    }
+
+    #[test]
+    fn warnings_and_errors_fail_their_file() {
+        let out = render(&result(), None).unwrap();
+        let lib = testcase(&out, "src/lib.rs");
+        assert!(lib.contains(r#"<failure message="1 finding" type="error">- error: src/lib.rs:2: `src/lib.rs:2` unwrap panics (error)"#), "{}", lib);
+        assert!(lib.contains("unwrap panics (error)\n</failure>"), "{}", lib);
+        assert!(lib.contains("<system-out>- suggestion: src/lib.rs:5: `src/lib.rs:5` odd name (suggestion)"), "{}", lib);
+    }
+
+    #[test]
+    fn info_and_suggestions_are_output_without_failing() {
+        let out = render(&result(), None).unwrap();
+        let style = testcase(&out, "src/style.rs");
+        assert!(!style.contains("<failure"), "{}", style);
+        assert!(style.contains("<system-out>- info: src/style.rs:1: `src/style.rs:1` odd name (info)\n- suggestion: src/style.rs:4:"), "{}", style);
+    }
+
+    #[test]
+    fn unlocated_findings_go_to_their_own_testcase() {
+        let out = render(&result(), None).unwrap();
+        let unlocated = testcase(&out, UNLOCATED);
+        assert!(unlocated.contains(r#"<failure message="1 finding" type="warning">- warning: The commit message is vague (warning)"#), "{}", unlocated);
+    }
+
+    #[test]
+    fn counts_and_time_come_from_the_review() {
+        let out = render(&result(), None).unwrap();
+        assert!(out.contains(r#"<testsuites name="code_reviewer" tests="4" failures="2" time="2.500">"#), "{}", out);
+        assert!(out.contains(r#"tests="4" failures="2" errors="0" skipped="0" time="2.500""#), "{}", out);
+    }
+
+    #[rstest]
+    #[case::error(Severity::Error, &["src/lib.rs"])]
+    #[case::info(Severity::Info, &["(no file)", "src/lib.rs", "src/style.rs"])]
+    fn fail_on_sets_the_failure_threshold(#[case] fail_on: Severity, #[case] failing: &[&str]) {
+        let out = render(&result(), Some(fail_on)).unwrap();
+        let failed: Vec<&str> = [UNLOCATED, "src/clean.rs", "src/lib.rs", "src/style.rs"]
+            .into_iter()
+            .filter(|name| testcase(&out, name).contains("<failure"))
+            .collect();
+        assert_eq!(failed, failing);
+        assert!(out.contains(&format!(r#"tests="4" failures="{}""#, failing.len())), "{}", out);
+    }
+}
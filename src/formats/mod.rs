@@ -0,0 +1,84 @@
+use std::error::Error;
+use clap::ValueEnum;
+use crate::findings::Severity;
+use crate::report;
+use crate::ReviewResult;
+use diagnostics::SnippetSource;
+
+pub mod annotated;
+pub mod checkstyle;
+pub mod diagnostics;
+pub mod github;
+pub mod gitlab;
+pub mod html;
+pub mod json;
+pub mod junit;
+pub mod rdjson;
+pub mod sarif;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable review, rendered as formatted markdown on a terminal
+    Text,
+    /// The review exactly as the model wrote it, without a banner or styling
+    Plain,
+    /// A single JSON document with the review and its metadata
+    Json,
+    /// A SARIF 2.1.0 log for code-scanning tools
+    Sarif,
+    /// Reviewdog Diagnostic Format (rdjson)
+    Rdjson,
+    /// GitHub Actions workflow commands (::error, ::warning, ::notice)
+    Github,
+    /// GitLab Code Quality report
+    Gitlab,
+    /// Checkstyle XML, for Jenkins warnings-ng and editors
+    Checkstyle,
+    /// JUnit XML with one testcase per reviewed file
+    Junit,
+    /// A self-contained HTML page with the review, the diff and metadata
+    Html,
+    /// The diff with the findings as `# REVIEW:` comment lines after the lines they are about
+    AnnotatedDiff,
+    /// Compiler-style diagnostics, each finding with the source lines it is about
+    Diagnostics,
+}
+
+/// Presentation settings shared by the output formats.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Render the text format as styled markdown
+    pub color: bool,
+    /// Leave out findings without a line number instead of placing them on line 1
+    pub drop_unlocated: bool,
+    /// Show the extracted findings instead of the full review text, because some were filtered out
+    pub findings_only: bool,
+    /// Where the diagnostics format reads the source lines it shows
+    pub snippets: SnippetSource,
+    /// The `--fail-on` threshold, at or above which findings fail their JUnit testcase;
+    /// `warning` when unset
+    pub fail_on: Option<Severity>,
+}
+
+/// Renders a review in the given format. The result always ends with a newline.
+pub fn render(format: OutputFormat, result: &ReviewResult, options: &RenderOptions) -> Result<String, Box<dyn Error>> {
+    let mut out = match format {
+        OutputFormat::Text | OutputFormat::Plain if options.findings_only => report::findings_text(&result.findings),
+        OutputFormat::Text | OutputFormat::Plain => result.review.clone(),
+        OutputFormat::Json => json::render(result)?,
+        OutputFormat::Sarif => sarif::render(result)?,
+        OutputFormat::Rdjson => rdjson::render(result, options.drop_unlocated)?,
+        OutputFormat::Github => github::render(result),
+        OutputFormat::Gitlab => gitlab::render(result)?,
+        OutputFormat::Checkstyle => checkstyle::render(result, options.drop_unlocated)?,
+        OutputFormat::Junit => junit::render(result, options.fail_on)?,
+        OutputFormat::Html => html::render(result),
+        OutputFormat::AnnotatedDiff => annotated::render(result),
+        OutputFormat::Diagnostics => diagnostics::render(result, options)?,
+    };
+
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
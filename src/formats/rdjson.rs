@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use serde::Serialize;
+use crate::findings::{Finding, Severity};
+use crate::ReviewResult;
+
+/// Reviewdog Diagnostic Format, see
+/// <https://github.com/reviewdog/reviewdog/tree/master/proto/rdf>
+#[derive(Serialize)]
+pub struct DiagnosticResult<'a> {
+    source: Source,
+    diagnostics: Vec<Diagnostic<'a>>,
+}
+
+#[derive(Serialize)]
+struct Source {
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+struct Diagnostic<'a> {
+    message: Cow<'a, str>,
+    location: Location<'a>,
+    severity: &'static str,
+}
+
+#[derive(Serialize)]
+struct Location<'a> {
+    path: &'a str,
+    range: Range,
+}
+
+#[derive(Serialize)]
+struct Range {
+    start: Position,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<Position>,
+}
+
+#[derive(Serialize)]
+struct Position {
+    line: u32,
+}
+
+impl<'a> DiagnosticResult<'a> {
+    /// Findings without a file can't be placed on a PR and are always left out. Findings
+    /// with a file but no line go on line 1 unless `drop_unlocated` is set.
+    pub fn new(result: &'a ReviewResult, drop_unlocated: bool) -> Self {
+        DiagnosticResult {
+            source: Source { name: "code_reviewer" },
+            diagnostics: result
+                .findings
+                .iter()
+                .filter(|finding| !(drop_unlocated && finding.line.is_none()))
+                .filter_map(Diagnostic::new)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> Diagnostic<'a> {
+    fn new(finding: &'a Finding) -> Option<Self> {
+        let path = finding.file.as_deref()?;
+        let line = finding.line.unwrap_or(1);
+
+        // The suggestion says how to fix it in words, so it can't be a replacement for the
+        // range in rdjson `suggestions`
+        let message = match &finding.suggestion {
+            Some(suggestion) => Cow::Owned(format!("{}\n\nSuggestion: {}", finding.message, suggestion)),
+            None => Cow::Borrowed(finding.message.as_str()),
+        };
+
+        Some(Diagnostic {
+            message,
+            location: Location {
+                path,
+                range: Range {
+                    start: Position { line },
+                    end: finding.end_line.filter(|end| *end > line).map(|line| Position { line }),
+                },
+            },
+            severity: match finding.severity {
+                Severity::Error => "ERROR",
+                Severity::Warning => "WARNING",
+                Severity::Info | Severity::Suggestion => "INFO",
+            },
+        })
+    }
+}
+
+pub fn render(result: &ReviewResult, drop_unlocated: bool) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&DiagnosticResult::new(result, drop_unlocated))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use super::*;
+    use crate::findings::parse_findings;
+
+    fn rdjson(review: &str, drop_unlocated: bool) -> Value {
+        let result = ReviewResult { findings: parse_findings(review), ..ReviewResult::default() };
+        serde_json::from_str(&render(&result, drop_unlocated).unwrap()).unwrap()
+    }
+
+    const REVIEW: &str = "\
+- `src/lib.rs:2-4` unwrap panics (error)
+- `src/lib.rs:7-7` the loop is quadratic (warning)
+- `src/lib.rs:9` odd name (info)
+- `src/lib.rs` has no tests (suggestion)
+- The commit message is vague (warning)
+";
+
+    #[test]
+    fn maps_severities_and_ranges() {
+        let diagnostics = rdjson(REVIEW, false)["diagnostics"].clone();
+        let located: Vec<(&str, &Value)> = diagnostics
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|diagnostic| (diagnostic["severity"].as_str().unwrap(), &diagnostic["location"]["range"]))
+            .collect();
+        assert_eq!(
+            located,
+            [
+                ("ERROR", &json!({ "start": { "line": 2 }, "end": { "line": 4 } })),
+                ("WARNING", &json!({ "start": { "line": 7 } })),
+                ("INFO", &json!({ "start": { "line": 9 } })),
+                ("INFO", &json!({ "start": { "line": 1 } })),
+            ]
+        );
+        assert_eq!(diagnostics[0]["location"]["path"], "src/lib.rs");
+    }
+
+    #[test]
+    fn drop_unlocated_leaves_out_findings_without_a_line() {
+        let diagnostics = rdjson(REVIEW, true)["diagnostics"].clone();
+        let lines: Vec<u64> =
+            diagnostics.as_array().unwrap().iter().map(|diagnostic| diagnostic["location"]["range"]["start"]["line"].as_u64().unwrap()).collect();
+        assert_eq!(lines, [2, 7, 9]);
+    }
+
+    #[test]
+    fn suggestions_follow_the_message() {
+        let mut finding = parse_findings("- `src/lib.rs:2` unwrap panics (error)").remove(0);
+        finding.suggestion = Some("Return a Result".to_string());
+        let result = ReviewResult { findings: vec![finding], ..ReviewResult::default() };
+        let rdjson: Value = serde_json::from_str(&render(&result, false).unwrap()).unwrap();
+        assert_eq!(rdjson["diagnostics"][0]["message"], "`src/lib.rs:2` unwrap panics (error)\n\nSuggestion: Return a Result");
+    }
+}
@@ -0,0 +1,249 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use crate::findings::{Category, Finding, Severity};
+use crate::ReviewResult;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Serialize)]
+pub struct SarifLog<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run<'a>>,
+}
+
+#[derive(Serialize)]
+struct Run<'a> {
+    tool: Tool<'a>,
+    results: Vec<SarifResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct Tool<'a> {
+    driver: Driver<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver<'a> {
+    name: &'static str,
+    version: &'static str,
+    properties: DriverProperties<'a>,
+    rules: Vec<Rule>,
+}
+
+/// One rule per finding category, so code-scanning UIs can group and filter by it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: String,
+    short_description: Message<'static>,
+}
+
+#[derive(Serialize)]
+struct DriverProperties<'a> {
+    model: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult<'a> {
+    rule_id: String,
+    rule_index: usize,
+    level: &'static str,
+    message: Message<'a>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    locations: Vec<Location<'a>>,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location<'a> {
+    physical_location: PhysicalLocation<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation<'a> {
+    artifact_location: ArtifactLocation<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<Region>,
+}
+
+#[derive(Serialize)]
+struct ArtifactLocation<'a> {
+    uri: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_line: Option<u32>,
+}
+
+impl Rule {
+    fn new(category: Category) -> Self {
+        Rule {
+            id: category.to_string(),
+            short_description: Message {
+                text: match category {
+                    Category::Bugs => "Potential bugs or issues",
+                    Category::Style => "Code style and best practices",
+                    Category::Performance => "Performance implications",
+                    Category::Security => "Security considerations",
+                    Category::Suggestions => "Suggestions for improvement",
+                },
+            },
+        }
+    }
+}
+
+impl<'a> SarifLog<'a> {
+    pub fn new(result: &'a ReviewResult) -> Self {
+        let categories = Category::value_variants();
+        SarifLog {
+            schema: SCHEMA,
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "code_reviewer",
+                        version: env!("CARGO_PKG_VERSION"),
+                        properties: DriverProperties { model: &result.model },
+                        rules: categories.iter().copied().map(Rule::new).collect(),
+                    },
+                },
+                results: result
+                    .findings
+                    .iter()
+                    .map(|finding| SarifResult::new(finding, categories))
+                    .collect(),
+            }],
+        }
+    }
+}
+
+impl<'a> SarifResult<'a> {
+    /// Findings without a resolvable file are still reported, with only a message.
+    /// `rules` are the categories in the order they appear in the driver's rules.
+    fn new(finding: &'a Finding, rules: &[Category]) -> Self {
+        let locations = finding.file.as_deref().map(|uri| Location {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri },
+                region: finding.line.map(|start_line| Region {
+                    start_line,
+                    end_line: finding.end_line.filter(|end| *end > start_line),
+                }),
+            },
+        });
+
+        SarifResult {
+            rule_id: finding.category.to_string(),
+            rule_index: rules.iter().position(|rule| *rule == finding.category).unwrap_or_default(),
+            level: match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info | Severity::Suggestion => "note",
+            },
+            message: Message { text: &finding.message },
+            locations: locations.into_iter().collect(),
+        }
+    }
+}
+
+pub fn render(result: &ReviewResult) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&SarifLog::new(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use super::*;
+    use crate::findings::parse_findings;
+
+    const SARIF_SCHEMA: &str = include_str!("../../tests/fixtures/sarif-schema-2.1.0.json");
+
+    fn sarif(review: &str) -> Value {
+        let result = ReviewResult {
+            model: "codellama".to_string(),
+            findings: parse_findings(review),
+            ..ReviewResult::default()
+        };
+        serde_json::from_str(&render(&result).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn output_matches_the_sarif_schema() {
+        let log = sarif(
+            "- `src/lib.rs:2-4` unwrap panics on bad input (error)\n\
+            - `src/lib.rs:9` the loop clones every item (warning)\n\
+            - `src/a.rs` has no tests (suggestion)\n\
+            - The commit message is vague (info)\n",
+        );
+        let mut schema: Value = serde_json::from_str(SARIF_SCHEMA).unwrap();
+        // The published pattern for `language` has a stray `]` that isn't a valid regex; the
+        // logs never set a language
+        for definition in ["run", "toolComponent"] {
+            schema["definitions"][definition]["properties"]["language"].as_object_mut().unwrap().remove("pattern");
+        }
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let errors: Vec<String> = validator.iter_errors(&log).map(|e| format!("{} at {}", e, e.instance_path())).collect();
+        assert!(errors.is_empty(), "{:#?}", errors);
+        assert_eq!(log["runs"][0]["results"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn findings_without_a_file_have_only_a_message() {
+        let log = sarif("- The commit message is vague (info)\n- `src/a.rs` has no tests (suggestion)\n");
+        let results = &log["runs"][0]["results"];
+        assert_eq!(results[0]["message"]["text"], "The commit message is vague (info)");
+        assert!(results[0].get("locations").is_none());
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/a.rs");
+        assert!(location.get("region").is_none());
+    }
+
+    #[test]
+    fn end_line_is_only_set_for_ranges() {
+        let log = sarif("- `src/lib.rs:2-4` unwrap panics (error)\n- `src/lib.rs:7-7` odd name (info)\n- `src/lib.rs:9` odd name (info)\n");
+        let regions: Vec<&Value> = log["runs"][0]["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| &result["locations"][0]["physicalLocation"]["region"])
+            .collect();
+        assert_eq!(*regions[0], serde_json::json!({ "startLine": 2, "endLine": 4 }));
+        assert_eq!(*regions[1], serde_json::json!({ "startLine": 7 }));
+        assert_eq!(*regions[2], serde_json::json!({ "startLine": 9 }));
+    }
+
+    #[test]
+    fn results_point_at_their_category_rule() {
+        let log = sarif(
+            "- [security] secret in the log (error)\n\
+            - [style] `src/a.rs:1` odd name (info)\n\
+            - [bugs] `src/a.rs:2` unwrap panics (error)\n\
+            - [suggestions] add a test (suggestion)\n\
+            - [performance] `src/b.rs` clones every item (warning)\n",
+        );
+        let rules = log["runs"][0]["tool"]["driver"]["rules"].as_array().unwrap();
+        let ids: Vec<&str> = rules.iter().map(|rule| rule["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, ["bugs", "style", "performance", "security", "suggestions"]);
+
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        let rule_ids: Vec<&str> = results.iter().map(|result| result["ruleId"].as_str().unwrap()).collect();
+        assert_eq!(rule_ids, ["security", "style", "bugs", "suggestions", "performance"]);
+        for result in results {
+            let index = result["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(rules[index]["id"], result["ruleId"]);
+        }
+    }
+}
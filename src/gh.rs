@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::process::Command;
+use serde::Deserialize;
+
+/// A pull request as described by its author.
+#[derive(Debug, Deserialize)]
+pub struct PullRequest {
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub commits: Vec<Commit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commit {
+    pub message_headline: String,
+}
+
+impl PullRequest {
+    /// The title, description and commits, to check the change against its stated intent.
+    pub fn description(&self) -> String {
+        let mut description = format!("Title: {}", self.title.trim());
+        if !self.body.trim().is_empty() {
+            description.push_str(&format!("\n\nDescription:\n{}", self.body.trim()));
+        }
+        if !self.commits.is_empty() {
+            description.push_str("\n\nCommits:");
+            for commit in &self.commits {
+                description.push_str(&format!("\n- {}", commit.message_headline));
+            }
+        }
+        description
+    }
+}
+
+/// Runs the GitHub CLI with `args` and returns its stdout. A missing or unauthenticated
+/// `gh` becomes an error that says how to fix it.
+pub fn run(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("gh").args(args).output().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => "the GitHub CLI (gh) is not installed; get it from https://cli.github.com".to_string(),
+        _ => format!("could not run gh: {}", e),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("gh auth login") {
+            return Err("the GitHub CLI (gh) is not logged in; run `gh auth login` first".into());
+        }
+        let command = args.iter().take(2).copied().collect::<Vec<_>>().join(" ");
+        return Err(format!("gh {} failed ({}):\n{}", command, output.status, stderr.trim_end()).into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// The diff of pull request `number`, which may also be its URL or branch.
+pub fn pull_request_diff(number: &str) -> Result<String, Box<dyn Error>> {
+    run(&["pr", "diff", number])
+}
+
+pub fn pull_request(number: &str) -> Result<PullRequest, Box<dyn Error>> {
+    let json = run(&["pr", "view", number, "--json", "title,body,commits"])?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Switches the working tree to the pull request's head branch.
+pub fn checkout(number: &str) -> Result<(), Box<dyn Error>> {
+    run(&["pr", "checkout", number])?;
+    Ok(())
+}
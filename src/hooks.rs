@@ -0,0 +1,226 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use clap::ValueEnum;
+use code_reviewer::{git, warning};
+
+/// Marks whole hook scripts written by earlier versions, which replaced the hook instead of
+/// adding to it.
+const MARKER: &str = "# Installed by code_reviewer install-hook";
+
+/// Delimit the lines this tool adds to a hook, so they can be updated or removed without
+/// touching the rest of someone else's hook.
+const BEGIN: &str = "# >>> code_reviewer >>>";
+const END: &str = "# <<< code_reviewer <<<";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookKind {
+    /// Reviews the staged changes before each commit
+    PreCommit,
+    /// Reviews the commits not yet on the upstream branch before each push
+    PrePush,
+    /// Checks the commit message against the staged changes
+    CommitMsg,
+}
+
+impl HookKind {
+    fn file_name(self) -> &'static str {
+        match self {
+            HookKind::PreCommit => "pre-commit",
+            HookKind::PrePush => "pre-push",
+            HookKind::CommitMsg => "commit-msg",
+        }
+    }
+}
+
+/// Location of the hook script, honouring worktrees and `core.hooksPath`.
+fn hook_path(kind: HookKind) -> Result<PathBuf, Box<dyn Error>> {
+    let hooks_dir = PathBuf::from(git::run(&["rev-parse", "--git-path", "hooks"])?.trim());
+    Ok(hooks_dir.join(kind.file_name()))
+}
+
+/// The lines that run the tool from the hook, skipped when CODE_REVIEWER_SKIP=1.
+fn block(kind: HookKind) -> String {
+    let program = std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "code_reviewer".to_string());
+
+    let command = match kind {
+        HookKind::PreCommit => format!("\"{}\" --staged || exit $?", program),
+        // A branch without an upstream has nothing to compare against yet
+        HookKind::PrePush => format!(
+            "if git rev-parse --verify --quiet '@{{upstream}}' >/dev/null; then\n    \
+            \"{}\" --base '@{{upstream}}' || exit $?\n  fi",
+            program
+        ),
+        // git passes the commit-msg hook the file holding the message
+        HookKind::CommitMsg => format!("\"{}\" check-message \"$1\" || exit $?", program),
+    };
+    format!("{}\nif [ \"$CODE_REVIEWER_SKIP\" != 1 ]; then\n  {}\nfi\n{}\n", BEGIN, command, END)
+}
+
+/// The byte range of the lines between `BEGIN` and `END` in `content`, `END`'s newline included.
+fn block_range(content: &str) -> Option<std::ops::Range<usize>> {
+    let begin = content.find(BEGIN)?;
+    let end = begin + content[begin..].find(END)?;
+    let end = content[end..].find('\n').map_or(content.len(), |newline| end + newline + 1);
+    Some(begin..end)
+}
+
+/// `content` without the lines between `BEGIN` and `END`, and whether they were there.
+fn remove_block(content: &str) -> (String, bool) {
+    let Some(range) = block_range(content) else {
+        return (content.to_string(), false);
+    };
+    let before = content[..range.start].trim_end_matches('\n');
+    let rest = format!("{}\n{}", before, &content[range.end..]);
+    (rest.trim_end().to_string() + "\n", true)
+}
+
+/// `existing` with `block` in place of the one it already has, or appended to it.
+fn add_block(existing: &str, block: &str) -> String {
+    match block_range(existing) {
+        Some(range) => format!("{}{}{}", &existing[..range.start], block, &existing[range.end..]),
+        None => format!("{}\n{}", existing.trim_end(), block),
+    }
+}
+
+/// The first unindented `exit`, or `exec` of a command, in `script`. Lines appended after it
+/// don't run. `exec` with only redirections, like `exec 1>&2`, carries on.
+fn early_exit(script: &str) -> Option<&str> {
+    script.lines().filter(|line| !line.starts_with(char::is_whitespace)).find(|line| {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("exit") => true,
+            Some("exec") => words.next().is_some_and(|word| !word.contains(['<', '>'])),
+            _ => false,
+        }
+    })
+}
+
+/// Whether `script` has nothing left to run, only blank lines and a shebang.
+fn is_empty(script: &str) -> bool {
+    script.lines().all(|line| line.trim().is_empty() || line.starts_with("#!"))
+}
+
+/// Adds the review to the hook, creating the hook if there is none. An existing hook is kept
+/// and the review appended to it, unless `force` replaces it. Installing again updates the
+/// review's lines in place.
+pub fn install(kind: HookKind, force: bool) -> Result<(), Box<dyn Error>> {
+    let path = hook_path(kind)?;
+    let existing = fs::read_to_string(&path).ok().filter(|content| !force && !content.contains(MARKER));
+
+    let content = match existing {
+        Some(existing) => {
+            if block_range(&existing).is_none() {
+                if let Some(line) = early_exit(&existing) {
+                    warning!(
+                        "{} runs `{}` before the end, so the review appended to it may never run",
+                        path.display(),
+                        line.trim_end()
+                    );
+                }
+            }
+            add_block(&existing, &block(kind))
+        }
+        None => format!("#!/bin/sh\n{}", block(kind)),
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    eprintln!("Installed {} hook at {}", kind.file_name(), path.display());
+    Ok(())
+}
+
+/// Takes the review out of the hook, and removes the hook when nothing else is left in it.
+pub fn uninstall(kind: HookKind) -> Result<(), Box<dyn Error>> {
+    let path = hook_path(kind)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => {
+            eprintln!("No {} hook installed", kind.file_name());
+            return Ok(());
+        }
+    };
+
+    if content.contains(MARKER) {
+        fs::remove_file(&path)?;
+        eprintln!("Removed {} hook at {}", kind.file_name(), path.display());
+        return Ok(());
+    }
+    let (rest, found) = remove_block(&content);
+    if !found {
+        eprintln!("The {} hook at {} doesn't run code_reviewer, leaving it in place", kind.file_name(), path.display());
+        return Ok(());
+    }
+
+    if is_empty(&rest) {
+        fs::remove_file(&path)?;
+        eprintln!("Removed {} hook at {}", kind.file_name(), path.display());
+    } else {
+        fs::write(&path, rest)?;
+        eprintln!("Removed code_reviewer from the {} hook at {}", kind.file_name(), path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    const HOOK: &str = "#!/bin/sh\nnpm run lint\n";
+
+    #[test]
+    fn removing_the_block_restores_the_hook() {
+        let installed = add_block(HOOK, &block(HookKind::PreCommit));
+        assert!(installed.starts_with(HOOK));
+        assert_eq!(remove_block(&installed), (HOOK.to_string(), true));
+        assert_eq!(remove_block(HOOK), (HOOK.to_string(), false));
+    }
+
+    #[test]
+    fn installing_again_updates_the_block_in_place() {
+        let installed = add_block(HOOK, &block(HookKind::PreCommit));
+        assert_eq!(add_block(&installed, &block(HookKind::PreCommit)), installed);
+
+        let edited = format!("{}npm test\n", installed);
+        let updated = add_block(&edited, &block(HookKind::PrePush));
+        assert_eq!(updated, format!("{}{}npm test\n", HOOK, block(HookKind::PrePush)));
+    }
+
+    #[test]
+    fn lines_after_the_block_are_kept() {
+        let content = format!("#!/bin/sh\n{}npm test\n", block(HookKind::PreCommit));
+        assert_eq!(remove_block(&content), ("#!/bin/sh\nnpm test\n".to_string(), true));
+    }
+
+    #[test]
+    fn removing_the_block_from_a_hook_of_our_own_leaves_nothing_to_run() {
+        let (rest, found) = remove_block(&format!("#!/bin/sh\n{}", block(HookKind::CommitMsg)));
+        assert!(found);
+        assert_eq!(rest, "#!/bin/sh\n");
+        assert!(is_empty(&rest));
+        assert!(!is_empty(HOOK));
+    }
+
+    #[rstest]
+    #[case::exit_status("#!/bin/sh\nnpm test\nexit $?\n", Some("exit $?"))]
+    #[case::exit("#!/bin/sh\nnpm test\nexit\n", Some("exit"))]
+    #[case::exec("#!/bin/sh\nexec npm test\n", Some("exec npm test"))]
+    #[case::redirect_only("#!/bin/sh\nexec 1>&2\nnpm test\n", None)]
+    #[case::indented("#!/bin/sh\nif [ -z \"$CI\" ]; then\n  exit 0\nfi\nnpm test\n", None)]
+    #[case::exit_in_a_word("#!/bin/sh\nexiting=1\n", None)]
+    fn finds_lines_that_end_the_hook(#[case] script: &str, #[case] expected: Option<&str>) {
+        assert_eq!(early_exit(script), expected);
+    }
+}
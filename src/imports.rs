@@ -0,0 +1,310 @@
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::diff::{self, LineKind};
+
+static RUST_USE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+((?:crate|super|self)(?:::[^;]*)?);").unwrap()
+});
+static RUST_MOD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;").unwrap());
+static PYTHON_FROM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*from\s+(\.*)([\w.]*)\s+import\s+(.+)").unwrap());
+static PYTHON_IMPORT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*import\s+([\w.]+(?:\s*,\s*[\w.]+)*)").unwrap());
+// `import x from './x'`, `export * from './x'`, `import './x'`, `require('./x')` and `import('./x')`
+static JS_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:\bfrom\s+|^\s*import\s+|\brequire\s*\(\s*|\bimport\s*\(\s*)['"](\.{1,2}/[^'"]+)['"]"#).unwrap()
+});
+static C_INCLUDE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"^\s*#\s*include\s+"([^"]+)""#).unwrap());
+
+const JS_EXTENSIONS: [&str; 6] = ["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+/// The files in `known` that lines added by `diff` import: Rust `use crate::`, `super::` and
+/// `self::` paths and `mod` declarations, Python imports, relative JavaScript and TypeScript
+/// imports and requires, and C `#include "..."`. Imports of other packages, and paths that
+/// don't resolve to a known file, are left out. Files are listed once, in the order they are
+/// first imported.
+pub fn imported_files(diff: &str, known: &BTreeSet<&str>) -> Vec<String> {
+    let mut files = Vec::new();
+
+    for file in diff::parse(diff) {
+        let Some(path) = file.new_path.as_deref() else {
+            continue;
+        };
+        let added = file.hunks.iter().flat_map(|hunk| &hunk.lines).filter(|line| line.kind == LineKind::Added);
+        for line in added {
+            for candidate in candidates(path, &line.content) {
+                let candidate = candidate.to_string_lossy().into_owned();
+                if candidate != path && known.contains(candidate.as_str()) && !files.contains(&candidate) {
+                    files.push(candidate);
+                }
+            }
+        }
+    }
+
+    files
+}
+
+/// Paths `line` of the file at `path` may import, most specific first.
+fn candidates(path: &str, line: &str) -> Vec<PathBuf> {
+    let path = Path::new(path);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+
+    match extension {
+        "rs" => {
+            if let Some(caps) = RUST_USE.captures(line) {
+                return expand_use(&caps[1]).iter().flat_map(|segments| rust_module(path, segments)).collect();
+            }
+            if let Some(caps) = RUST_MOD.captures(line) {
+                let dir = rust_module_dir(path);
+                return vec![dir.join(format!("{}.rs", &caps[1])), dir.join(&caps[1]).join("mod.rs")];
+            }
+            Vec::new()
+        }
+        "py" => python_candidates(dir, line),
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => JS_IMPORT
+            .captures_iter(line)
+            .flat_map(|caps| {
+                let target = normalize(&dir.join(&caps[1]));
+                let mut candidates = vec![target.clone()];
+                for extension in JS_EXTENSIONS {
+                    candidates.push(PathBuf::from(format!("{}.{}", target.display(), extension)));
+                }
+                for extension in JS_EXTENSIONS {
+                    candidates.push(target.join(format!("index.{}", extension)));
+                }
+                candidates
+            })
+            .collect(),
+        "c" | "h" | "cc" | "cpp" | "hpp" | "cxx" | "hh" => C_INCLUDE
+            .captures(line)
+            .map(|caps| vec![normalize(&dir.join(&caps[1])), normalize(Path::new(&caps[1]))])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// Every path a `use` tree names, as segments, e.g. `crate::a::{b, c::D}` gives `crate a b`
+/// and `crate a c D`.
+fn expand_use(tree: &str) -> Vec<Vec<String>> {
+    let tree: String = tree.chars().filter(|c| !c.is_whitespace()).collect();
+    let Some(open) = tree.find('{') else {
+        return vec![tree.split("::").map(str::to_string).collect()];
+    };
+    let prefix = tree[..open].trim_end_matches("::");
+    let inner = tree[open + 1..].strip_suffix('}').unwrap_or(&tree[open + 1..]);
+
+    // Splits at the commas that aren't inside nested braces
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    items
+        .into_iter()
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| match item {
+            "self" => vec![prefix.split("::").map(str::to_string).collect()],
+            _ => expand_use(&format!("{}::{}", prefix, item)),
+        })
+        .collect()
+}
+
+/// Directory a Rust file's child modules live in: its own for `lib.rs`, `main.rs` and
+/// `mod.rs`, a directory named after it for any other file.
+fn rust_module_dir(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    match path.file_stem().and_then(|stem| stem.to_str()) {
+        Some("lib" | "main" | "mod") | None => dir.to_path_buf(),
+        Some(stem) => dir.join(stem),
+    }
+}
+
+/// Root of the crate `path` is in: the nearest `src` directory above it.
+fn rust_crate_root(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|ancestor| ancestor.file_name().is_some_and(|name| name == "src"))
+        .unwrap_or(Path::new(""))
+        .to_path_buf()
+}
+
+/// The files a `use` path may be defined in, longest module path first, since its last
+/// segments may name an item rather than a module.
+fn rust_module(path: &Path, segments: &[String]) -> Vec<PathBuf> {
+    let (base, rest) = match segments.first().map(String::as_str) {
+        Some("crate") => (rust_crate_root(path), &segments[1..]),
+        Some("self") => (rust_module_dir(path), &segments[1..]),
+        Some("super") => {
+            let mut base = rust_module_dir(path);
+            let supers = segments.iter().take_while(|segment| *segment == "super").count();
+            for _ in 0..supers {
+                base = base.parent().unwrap_or(Path::new("")).to_path_buf();
+            }
+            (base, &segments[supers..])
+        }
+        _ => return Vec::new(),
+    };
+
+    let rest: Vec<&String> = rest.iter().filter(|segment| *segment != "*").collect();
+    let mut candidates = Vec::new();
+    for len in (1..=rest.len()).rev() {
+        let module: PathBuf = rest[..len].iter().map(|segment| segment.as_str()).collect();
+        candidates.push(base.join(module.with_extension("rs")));
+        candidates.push(base.join(&module).join("mod.rs"));
+    }
+    // Items of the module `super` or `self` names are defined in its own file
+    if rest.len() <= 1 && segments.first().is_some_and(|segment| segment != "crate") {
+        candidates.push(base.with_extension("rs"));
+        candidates.push(base.join("mod.rs"));
+    }
+    candidates
+}
+
+fn python_candidates(dir: &Path, line: &str) -> Vec<PathBuf> {
+    let module = |base: &Path, dotted: &str| -> Vec<PathBuf> {
+        let module: PathBuf = dotted.split('.').filter(|part| !part.is_empty()).collect();
+        vec![base.join(module.with_extension("py")), base.join(&module).join("__init__.py")]
+    };
+
+    if let Some(caps) = PYTHON_FROM.captures(line) {
+        let names: Vec<&str> = caps[3]
+            .trim_matches(|c: char| c == '(' || c == ')' || c.is_whitespace())
+            .split(',')
+            .filter_map(|name| name.split_whitespace().next())
+            .collect();
+        let dots = caps[1].len();
+        let bases: Vec<PathBuf> = if dots > 0 {
+            let mut base = dir.to_path_buf();
+            for _ in 1..dots {
+                base = base.parent().unwrap_or(Path::new("")).to_path_buf();
+            }
+            vec![base]
+        } else {
+            vec![PathBuf::new(), dir.to_path_buf()]
+        };
+
+        let mut candidates = Vec::new();
+        for base in &bases {
+            let package = &caps[2];
+            // `from pkg import module` imports a module as often as a name
+            for name in &names {
+                candidates.extend(module(base, &format!("{}.{}", package, name)));
+            }
+            if !package.is_empty() {
+                candidates.extend(module(base, package));
+            }
+        }
+        return candidates.iter().map(|candidate| normalize(candidate)).collect();
+    }
+    if let Some(caps) = PYTHON_IMPORT.captures(line) {
+        return caps[1]
+            .split(',')
+            .flat_map(|dotted| [module(Path::new(""), dotted.trim()), module(dir, dotted.trim())].concat())
+            .map(|candidate| normalize(&candidate))
+            .collect();
+    }
+    Vec::new()
+}
+
+/// `path` with `.` and `..` components resolved, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    const KNOWN: &[&str] = &[
+        "src/lib.rs",
+        "src/diff.rs",
+        "src/backend/mod.rs",
+        "src/backend/ollama.rs",
+        "src/formats/mod.rs",
+        "src/formats/json.rs",
+        "app/__init__.py",
+        "app/models.py",
+        "app/api/views.py",
+        "app/api/serializers.py",
+        "web/src/api/client.ts",
+        "web/src/components/Button.tsx",
+        "web/src/utils/index.ts",
+        "native/parser.h",
+    ];
+
+    /// A diff adding `line` to the file at `path`.
+    fn diff(path: &str, line: &str) -> String {
+        format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1,1 +1,2 @@\n context\n+{1}\n", path, line)
+    }
+
+    fn imports(path: &str, line: &str) -> Vec<String> {
+        let known: BTreeSet<&str> = KNOWN.iter().copied().collect();
+        imported_files(&diff(path, line), &known)
+    }
+
+    #[rstest]
+    #[case::crate_item("src/backend/ollama.rs", "use crate::diff::parse;", &["src/diff.rs"])]
+    #[case::crate_module("src/lib.rs", "use crate::backend::ollama::OllamaBackend;", &["src/backend/ollama.rs", "src/backend/mod.rs"])]
+    #[case::use_tree("src/lib.rs", "use crate::{diff, formats::json::render};", &["src/diff.rs", "src/formats/json.rs", "src/formats/mod.rs"])]
+    #[case::super_from_mod_rs("src/formats/mod.rs", "use super::diff::Hunk;", &["src/diff.rs"])]
+    #[case::super_from_a_file("src/formats/json.rs", "use super::RenderOptions;", &["src/formats/mod.rs"])]
+    #[case::self_path("src/backend/mod.rs", "pub use self::ollama::OllamaBackend;", &["src/backend/ollama.rs"])]
+    #[case::mod_declaration("src/lib.rs", "pub mod diff;", &["src/diff.rs"])]
+    #[case::other_crate("src/lib.rs", "use serde::Serialize;", &[])]
+    fn rust(#[case] path: &str, #[case] line: &str, #[case] expected: &[&str]) {
+        assert_eq!(imports(path, line), expected);
+    }
+
+    #[rstest]
+    #[case::relative_module("app/api/views.py", "from .serializers import UserSerializer", &["app/api/serializers.py"])]
+    #[case::parent_package("app/api/views.py", "from ..models import User", &["app/models.py"])]
+    #[case::module_from_a_package("app/api/views.py", "from app import models", &["app/models.py", "app/__init__.py"])]
+    #[case::absolute_import("app/api/views.py", "import app.models", &["app/models.py"])]
+    #[case::third_party("app/api/views.py", "import requests", &[])]
+    fn python(#[case] path: &str, #[case] line: &str, #[case] expected: &[&str]) {
+        assert_eq!(imports(path, line), expected);
+    }
+
+    #[rstest]
+    #[case::sibling_directory("web/src/components/Button.tsx", "import { get } from '../api/client';", &["web/src/api/client.ts"])]
+    #[case::index_file("web/src/components/Button.tsx", "const utils = require(\"../utils\");", &["web/src/utils/index.ts"])]
+    #[case::dynamic_import("web/src/components/Button.tsx", "const api = await import('../api/client.ts');", &["web/src/api/client.ts"])]
+    #[case::package("web/src/components/Button.tsx", "import React from 'react';", &[])]
+    fn javascript(#[case] path: &str, #[case] line: &str, #[case] expected: &[&str]) {
+        assert_eq!(imports(path, line), expected);
+    }
+
+    #[test]
+    fn c_includes_resolve_from_the_file_or_the_root() {
+        assert_eq!(imports("native/parser.c", "#include \"parser.h\""), ["native/parser.h"]);
+        assert_eq!(imports("native/lexer.c", "#include <stdio.h>"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn only_added_lines_count() {
+        let known: BTreeSet<&str> = KNOWN.iter().copied().collect();
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,1 @@\n use crate::formats::json;\n-use crate::diff;\n";
+        assert!(imported_files(diff, &known).is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use code_reviewer::backend::ChatMessage;
+use code_reviewer::baseline::Baseline;
+use code_reviewer::findings::Finding;
+use code_reviewer::formats::{diagnostics, RenderOptions};
+use code_reviewer::language::fence_language;
+use code_reviewer::patches::{self, Patch, Status};
+use code_reviewer::{git, CodeReviewTool, ReviewResult};
+
+const HELP: &str = "Ask a follow-up question about the review, or:
+  /include <file>  send a file of the codebase with the next question
+  /reset           forget the follow-up questions and included files so far
+  /quit            stop asking (or Ctrl-D)
+  /help            show this help";
+
+/// Answers follow-up questions about a review until `/quit` or the end of input, printing
+/// the answers as they are written. Each question is sent with the review prompt, the review
+/// and the questions and answers before it, and the files `/include`d since the last one.
+pub async fn follow_up(reviewer: &CodeReviewTool, prompt: String, review: String) -> Result<(), Box<dyn Error>> {
+    let mut messages = vec![ChatMessage::user(prompt), ChatMessage::assistant(review)];
+    let conversation_start = messages.len();
+    // Read on the first /include, most sessions never need it
+    let mut codebase: Option<BTreeMap<String, String>> = None;
+    let mut included = String::new();
+    eprintln!("\n{}", HELP);
+
+    let mut stdin = io::stdin().lock();
+    loop {
+        eprint!("\n> ");
+        io::stderr().flush()?;
+        let mut line = String::new();
+        if stdin.read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(());
+        }
+
+        let question = line.trim();
+        match question {
+            "" => continue,
+            "/quit" | "/exit" => return Ok(()),
+            "/reset" => {
+                messages.truncate(conversation_start);
+                included.clear();
+                eprintln!("Back to the review, follow-up questions forgotten.");
+                continue;
+            }
+            "/help" => {
+                eprintln!("{}", HELP);
+                continue;
+            }
+            _ if question.starts_with("/include") => {
+                let path = question["/include".len()..].trim();
+                let path = path.strip_prefix("./").unwrap_or(path);
+                if path.is_empty() {
+                    eprintln!("Usage: /include <file>");
+                    continue;
+                }
+                let codebase = match &mut codebase {
+                    Some(codebase) => codebase,
+                    None => match reviewer.tokenize_codebase_async(Path::new("./")).await {
+                        Ok(read) => codebase.insert(read),
+                        Err(e) => {
+                            eprintln!("Error reading the codebase: {}", e);
+                            continue;
+                        }
+                    },
+                };
+                match codebase.get(path) {
+                    Some(content) => {
+                        included.push_str(&format!(
+                            "Here is {} for reference:\n```{}\n{}\n```\n\n",
+                            path,
+                            fence_language(path),
+                            content.trim_end()
+                        ));
+                        eprintln!("{} will be sent with the next question.", path);
+                    }
+                    None => eprintln!("{} is not in the codebase, or is ignored or too large", path),
+                }
+                continue;
+            }
+            _ if question.starts_with('/') => {
+                eprintln!("Unknown command {}, see /help", question);
+                continue;
+            }
+            _ => {}
+        }
+
+        messages.push(ChatMessage::user(format!("{}{}", included, question)));
+        println!();
+        let mut stdout = io::stdout();
+        let answered = reviewer
+            .chat_streaming(&messages, &mut |text| {
+                print!("{}", text);
+                let _ = stdout.flush();
+            })
+            .await;
+        match answered {
+            Ok(answer) => {
+                if !answer.ends_with('\n') {
+                    println!();
+                }
+                messages.push(ChatMessage::assistant(answer));
+                included.clear();
+            }
+            Err(e) => {
+                // An unanswered question would leave two user turns in a row; the files are
+                // kept for the next try
+                messages.pop();
+                eprintln!("\nError: {}", e);
+            }
+        }
+    }
+}
+
+const TRIAGE_HELP: &str = "a: apply the suggested patch, s: skip, b: add to the baseline, q: quit";
+
+/// The patches proposed for each finding: those in its message or suggestion, else those in
+/// the review between it and the next finding that change its file.
+fn suggested_patches(review: &str, findings: &[Finding]) -> Vec<Vec<Patch>> {
+    let mut starts = Vec::new();
+    let mut from = 0;
+    for finding in findings {
+        let first_line = finding.message.lines().next().unwrap_or_default();
+        let start = review[from..].find(first_line).map(|start| from + start);
+        from = start.map_or(from, |start| start + first_line.len());
+        starts.push(start);
+    }
+
+    findings
+        .iter()
+        .enumerate()
+        .map(|(index, finding)| {
+            let own = patches::extract(&format!("{}\n{}", finding.message, finding.suggestion.as_deref().unwrap_or_default()));
+            if !own.is_empty() {
+                return own;
+            }
+            let Some(start) = starts[index] else {
+                return Vec::new();
+            };
+            let end = starts[index + 1..].iter().flatten().next().copied().unwrap_or(review.len());
+            patches::extract(&review[start..end])
+                .into_iter()
+                .filter(|patch| finding.file.as_ref().is_none_or(|file| patch.files.contains(file)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Goes through `findings` one at a time, showing each with its source lines and suggested
+/// fix, and applies its patch, skips it or adds it to the baseline at `baseline_path` as the
+/// user answers. Patches are applied as with --apply.
+pub fn triage(review: &str, findings: &[Finding], options: &RenderOptions, baseline_path: &Path) -> Result<(), Box<dyn Error>> {
+    let root = git::toplevel().unwrap_or_else(|| PathBuf::from("."));
+    let mut baseline = None;
+    let (mut applied, mut skipped, mut baselined) = (0, 0, 0);
+
+    let suggested = suggested_patches(review, findings);
+    let mut stdin = io::stdin().lock();
+    'findings: for (index, (finding, candidates)) in findings.iter().zip(&suggested).enumerate() {
+        let result = ReviewResult { findings: vec![finding.clone()], ..ReviewResult::default() };
+        eprint!("\n[{}/{}] {}", index + 1, findings.len(), diagnostics::render(&result, options)?);
+
+        loop {
+            eprint!("{} > ", TRIAGE_HELP);
+            io::stderr().flush()?;
+            let mut line = String::new();
+            if stdin.read_line(&mut line)? == 0 {
+                eprintln!();
+                break 'findings;
+            }
+            match line.trim() {
+                "a" => {
+                    let Some(patch) = candidates.first() else {
+                        eprintln!("This finding has no patch to apply.");
+                        continue;
+                    };
+                    match patch.check(&root) {
+                        Status::Applies => {
+                            patch.apply(&root)?;
+                            eprintln!("Applied to {}.", patch.files.join(", "));
+                            applied += 1;
+                        }
+                        status => {
+                            eprintln!("The patch {}", status);
+                            continue;
+                        }
+                    }
+                }
+                "s" => skipped += 1,
+                "b" => {
+                    // Loaded on first use, so the file is only written when something is added
+                    let baseline = match &mut baseline {
+                        Some(baseline) => baseline,
+                        None => baseline.insert(Baseline::load(baseline_path)?.unwrap_or_default()),
+                    };
+                    baseline.add(finding);
+                    baseline.save(baseline_path)?;
+                    baselined += 1;
+                }
+                "q" => break 'findings,
+                _ => continue,
+            }
+            break;
+        }
+    }
+
+    let left = findings.len() - applied - skipped - baselined;
+    let left = if left > 0 { format!(", {} left", left) } else { String::new() };
+    eprintln!("\n{} applied, {} skipped, {} baselined{}", applied, skipped, baselined, left);
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use clap::ValueEnum;
+use crate::diff;
+
+/// Languages the review prompt has extra checks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum LanguageProfile {
+    Rust,
+    Python,
+    #[value(name = "typescript", alias = "javascript")]
+    TypeScript,
+    Go,
+    Java,
+    #[value(name = "cpp", alias = "c++", alias = "c")]
+    Cpp,
+}
+
+impl LanguageProfile {
+    /// The language of files with `extension`, without the dot.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "rs" => Some(LanguageProfile::Rust),
+            "py" | "pyi" => Some(LanguageProfile::Python),
+            "ts" | "tsx" | "mts" | "cts" | "js" | "jsx" | "mjs" | "cjs" => Some(LanguageProfile::TypeScript),
+            "go" => Some(LanguageProfile::Go),
+            "java" => Some(LanguageProfile::Java),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => Some(LanguageProfile::Cpp),
+            _ => None,
+        }
+    }
+
+    /// What the review prompt additionally asks the model to check in this language.
+    pub fn extra_instructions(&self) -> &str {
+        match self {
+            LanguageProfile::Rust => {
+                "Check for unnecessary clones, unwrap() calls without justification, missing error propagation, and improper use of unsafe."
+            }
+            LanguageProfile::Python => {
+                "Check for mutable default arguments, bare or overly broad except clauses, resources opened without a with block, and missing or wrong type hints."
+            }
+            LanguageProfile::TypeScript => {
+                "Check for uses of any that lose type safety, unhandled promise rejections and missing await, == where === is meant, and non-null assertions that hide possible undefined values."
+            }
+            LanguageProfile::Go => {
+                "Check for ignored or unwrapped errors, goroutines that can leak or race, missing defer for closing resources, and nil pointer or nil map dereferences."
+            }
+            LanguageProfile::Java => {
+                "Check for possible NullPointerExceptions, resources not closed with try-with-resources, swallowed exceptions, and equals without hashCode."
+            }
+            LanguageProfile::Cpp => {
+                "Check for memory leaks and raw new/delete where RAII or smart pointers fit, buffer overflows, undefined behavior, and dangling references or iterators."
+            }
+        }
+    }
+}
+
+/// Rust anti-patterns `--rust-mode` asks the model to look for, one check each.
+pub const RUST_ANTI_PATTERNS: [&str; 6] = [
+    "`.unwrap()` or `.expect()` in non-test code",
+    "`clone()` calls that borrowing or moving would avoid",
+    "`Box<dyn Error>` where a `thiserror` error type or `anyhow` would serve callers better",
+    "public functions returning `Result` without `#[must_use]`",
+    "`unsafe` blocks without a `// SAFETY:` comment explaining why they are sound",
+    "integer arithmetic that can overflow, where `checked_`, `saturating_` or `wrapping_` operations belong",
+];
+
+/// Extension of the file at `path`, without the dot. Dotfiles like `.eslintrc` and names
+/// without a dot like `Makefile` have none.
+fn extension(path: &str) -> Option<&str> {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    // A leading dot starts a hidden file's name, not its extension
+    name.trim_start_matches('.').rsplit_once('.').map(|(_, extension)| extension)
+}
+
+/// Every language the changed files in `diff` are written in, the one with the most files
+/// first. Ties go to the language changed first, and files in other languages are left out.
+pub fn detect_languages(diff: &str) -> Vec<LanguageProfile> {
+    let mut counts: Vec<(LanguageProfile, usize)> = Vec::new();
+    for path in diff::changed_files(diff) {
+        let Some(language) = extension(&path).and_then(LanguageProfile::from_extension) else {
+            continue;
+        };
+        match counts.iter_mut().find(|(counted, _)| *counted == language) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((language, 1)),
+        }
+    }
+    // Stable, so equal counts keep the order the languages were first seen in
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    counts.into_iter().map(|(language, _)| language).collect()
+}
+
+/// The language most of the changed files in `diff` are written in, see [`detect_languages`].
+pub fn detect_language(diff: &str) -> Option<LanguageProfile> {
+    detect_languages(diff).into_iter().next()
+}
+
+/// Info string for a markdown code fence around the file at `path`, e.g. `rust` for
+/// `src/lib.rs`, or an empty one when the extension isn't known.
+pub fn fence_language(path: &str) -> &'static str {
+    match path.rsplit(['/', '\\']).next().unwrap_or(path) {
+        "Dockerfile" => return "dockerfile",
+        "Makefile" | "GNUmakefile" => return "makefile",
+        _ => {}
+    }
+    let Some(extension) = extension(path) else {
+        return "";
+    };
+    match extension.to_ascii_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        "scala" => "scala",
+        "sh" | "bash" | "zsh" => "bash",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        _ => "",
+    }
+}
+
+impl fmt::Display for LanguageProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LanguageProfile::Rust => "Rust",
+            LanguageProfile::Python => "Python",
+            LanguageProfile::TypeScript => "TypeScript",
+            LanguageProfile::Go => "Go",
+            LanguageProfile::Java => "Java",
+            LanguageProfile::Cpp => "C++",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    fn diff(paths: &[&str]) -> String {
+        paths
+            .iter()
+            .map(|path| format!("diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1 +1 @@\n-a\n+b\n", path))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::rust("src/lib.rs", "rust")]
+    #[case::upper_case_extension("lib/Util.PY", "python")]
+    #[case::file_name("docker/Dockerfile", "dockerfile")]
+    #[case::dotfile_without_extension(".gitignore", "")]
+    #[case::dotfile_with_extension(".eslintrc.json", "json")]
+    #[case::unknown("data.bin", "")]
+    fn fence_languages(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(fence_language(path), expected);
+    }
+
+    #[rstest]
+    #[case::single(&["src/lib.rs"], &[LanguageProfile::Rust])]
+    #[case::most_files_first(&["app.py", "src/a.ts", "src/b.tsx"], &[LanguageProfile::TypeScript, LanguageProfile::Python])]
+    #[case::tie_goes_to_the_first(&["main.go", "Main.java"], &[LanguageProfile::Go, LanguageProfile::Java])]
+    #[case::unknown_files_are_left_out(&["README.md", "Makefile", "include/parser.hpp"], &[LanguageProfile::Cpp])]
+    #[case::dotfiles(&[".eslintrc", ".eslintrc.js", ".github/workflows/ci.yml"], &[LanguageProfile::TypeScript])]
+    #[case::dotted_directory(&["config.d/run"], &[])]
+    #[case::no_known_language(&["README.md", ".gitignore"], &[])]
+    fn detects_languages(#[case] paths: &[&str], #[case] expected: &[LanguageProfile]) {
+        assert_eq!(detect_languages(&diff(paths)), expected);
+        assert_eq!(detect_language(&diff(paths)), expected.first().copied());
+    }
+}
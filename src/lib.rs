@@ -0,0 +1,1437 @@
+//! Reviews code changes with a language model.
+//!
+//! [`CodeReviewTool`] gets a diff, gathers context from the codebase and asks a
+//! [`Backend`] for a review; [`Settings`] holds the configuration read from
+//! `config.toml`, and a [`ReviewResult`] is what the [`reporters`] deliver.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ignore::WalkBuilder;
+use config::{Config, Environment, File, FileFormat};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use tokio::sync::Semaphore;
+
+/// Prints `Warning: ` and the formatted message on stderr, unless [`set_quiet`] silenced it.
+/// Defined ahead of the modules, which all use it.
+#[macro_export]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if !$crate::quiet() {
+            eprintln!("Warning: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+pub mod audit;
+pub mod backend;
+pub mod baseline;
+pub mod commit;
+pub mod diff;
+pub mod filter;
+pub mod findings;
+pub mod formats;
+pub mod gh;
+pub mod git;
+pub mod imports;
+pub mod language;
+pub mod mcp;
+mod pager;
+pub mod patches;
+mod progress;
+pub mod proxy;
+pub mod report;
+pub mod reporters;
+pub mod score;
+pub mod server;
+pub mod stats;
+pub mod structured;
+pub mod symbols;
+pub mod tls;
+
+use backend::{Backend, BackendKind, ChatMessage, Generation};
+use backend::ollama::OllamaApi;
+use commit::{FailurePolicy, MessageCheck};
+use diff::{DiffStats, LargeDiffPolicy};
+use filter::FileFilter;
+use findings::{Finding, Severity};
+use audit::SecurityAuditReport;
+use language::LanguageProfile;
+use score::ReviewScore;
+use stats::{CodebaseStats, ReviewStats, Usage};
+
+/// Where `get_diff` takes the changes under review from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSource {
+    /// Uncommitted changes in the working tree
+    WorkingTree,
+    /// Changes added to the index
+    Staged,
+    /// A stash entry, `stash@{0}` when none is given
+    Stash(Option<String>),
+    /// Commits on HEAD since it branched off a revision, e.g. `@{upstream}`
+    Base(String),
+    /// The changes one commit made to its first parent
+    Commit(String),
+    /// Staged changes followed by the unstaged ones, each under a label
+    All,
+}
+
+/// Heads the staged part of a [`DiffSource::All`] diff.
+pub const STAGED_LABEL: &str = "# Staged changes";
+/// Heads the unstaged part of a [`DiffSource::All`] diff, relative to the staged files.
+pub const UNSTAGED_LABEL: &str = "# Unstaged changes";
+
+/// Runs `git diff` with `args`, limited to `files` when there are any.
+fn git_diff(repository: Option<&Path>, args: &[&str], files: &[String]) -> Result<String, Box<dyn Error>> {
+    let mut all = vec!["diff"];
+    all.extend_from_slice(args);
+    all.push("--");
+    if files.is_empty() {
+        all.push(".");
+    } else {
+        all.extend(files.iter().map(String::as_str));
+    }
+    git::run_in(repository, &all)
+}
+
+#[derive(Debug, Deserialize)]
+/// Configuration read from `config.toml`; every field has a default.
+pub struct Settings {
+    /// Model API reviews are sent to
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// Base URL of the Ollama server
+    #[serde(default = "default_ollama_url")]
+    pub ollama_url: String,
+    /// Ollama endpoint reviews are sent to; `generate` for servers without `/api/chat`
+    #[serde(default)]
+    pub ollama_api: OllamaApi,
+    /// Times a request to Ollama is repeated after a server or connection error
+    #[serde(default = "default_ollama_retries")]
+    pub ollama_retries: u32,
+    /// Ollama model to use when `model` isn't pulled on the server
+    #[serde(default)]
+    pub fallback_model: Option<String>,
+    /// Base URL of the Anthropic API
+    #[serde(default = "default_anthropic_url")]
+    pub anthropic_url: String,
+    /// API key for the anthropic backend, else `ANTHROPIC_API_KEY`
+    #[serde(default)]
+    pub anthropic_api_key: Option<String>,
+    /// Completion limit for the anthropic backend
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    /// Model that writes the review
+    #[serde(default = "default_model")]
+    pub model: String,
+    /// Number of codebase files sent as context; 0 disables context
+    #[serde(default = "default_max_files_context")]
+    pub max_files_context: usize,
+    /// Diffs larger than this many bytes aren't reviewed in one request; 0 turns the check off
+    #[serde(default = "default_max_diff_bytes")]
+    pub max_diff_bytes: usize,
+    /// Whether a diff over `max_diff_bytes` is refused or reviewed file by file
+    #[serde(default)]
+    pub large_diff: LargeDiffPolicy,
+    /// Larger files are left out of the context
+    #[serde(default = "default_max_file_size_kb")]
+    pub max_file_size_kb: u64,
+    /// Context files are truncated after this many characters
+    #[serde(default = "default_max_file_content_chars")]
+    pub max_file_content_chars: usize,
+    /// Context files are truncated after this many lines
+    #[serde(default = "default_max_file_content_lines")]
+    pub max_file_content_lines: usize,
+    /// Only files with these extensions are read for context; empty means all
+    #[serde(default)]
+    pub include_extensions: Vec<String>,
+    /// Files with these extensions are never read for context
+    #[serde(default = "default_exclude_extensions")]
+    pub exclude_extensions: Vec<String>,
+    /// Glob patterns, relative to the repository root, left out of the context
+    #[serde(default)]
+    pub exclude_paths: Vec<String>,
+    /// Leave the files matching `test_patterns` out of the context
+    #[serde(default)]
+    pub exclude_tests: bool,
+    /// Glob patterns for test files
+    #[serde(default = "default_test_patterns")]
+    pub test_patterns: Vec<String>,
+    /// Run a self-critique pass over every review
+    #[serde(default)]
+    pub verify: bool,
+    /// Model for the self-critique pass, else `model`
+    #[serde(default)]
+    pub verifier_model: Option<String>,
+    /// Rate the change 1-10 per review dimension
+    #[serde(default)]
+    pub score: bool,
+    /// Scoring prompt with `{diff}` and `{review}` placeholders
+    #[serde(default)]
+    pub score_prompt: Option<String>,
+    /// Persona sent as the system prompt; empty sends none
+    #[serde(default = "default_system_prompt")]
+    pub system_prompt: String,
+    /// Show reviews longer than the terminal through `$PAGER`
+    #[serde(default = "default_pager")]
+    pub pager: bool,
+    /// Model calls in flight at once
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Web address of a self-hosted GitLab instance
+    #[serde(default)]
+    pub gitlab_url: Option<String>,
+    /// Web address of the Gitea or Forgejo instance
+    #[serde(default)]
+    pub gitea_url: Option<String>,
+    /// Incoming Webhook for `--notify slack`, else `SLACK_WEBHOOK_URL`
+    #[serde(default)]
+    pub slack_webhook_url: Option<String>,
+    /// Lines of the review included in Slack notifications
+    #[serde(default = "default_slack_review_lines")]
+    pub slack_review_lines: usize,
+    /// `--save-note` stores reviews under `refs/notes/<notes_ref>`
+    #[serde(default = "default_notes_ref")]
+    pub notes_ref: String,
+    /// Add to the note of an earlier review of the same commit instead of replacing it
+    #[serde(default)]
+    pub notes_append: bool,
+    /// Exit with status 1 when the review has a finding at or above this severity
+    #[serde(default)]
+    pub fail_on: Option<Severity>,
+    /// Make `check-message` fail when the model finds the commit message vague or misleading
+    #[serde(default)]
+    pub check_message_strict: bool,
+    /// Whether `check-message` lets the commit through when the model can't be reached
+    #[serde(default)]
+    pub check_message_on_error: FailurePolicy,
+    /// Ask for the findings as JSON instead of a free-text review
+    #[serde(default)]
+    pub structured: bool,
+    /// Proxy for requests to `http://` model APIs, instead of `HTTP_PROXY`
+    #[serde(default)]
+    pub http_proxy: Option<String>,
+    /// Proxy for requests to `https://` model APIs, instead of `HTTPS_PROXY`
+    #[serde(default)]
+    pub https_proxy: Option<String>,
+    /// Hosts the model APIs are reached on without a proxy, instead of `NO_PROXY`
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// PEM client certificate for model APIs that require mTLS
+    #[serde(default)]
+    pub tls_cert_path: Option<PathBuf>,
+    /// PKCS#8 PEM private key of `tls_cert_path`
+    #[serde(default)]
+    pub tls_key_path: Option<PathBuf>,
+    /// PEM certificate authority to trust for the model APIs, in addition to the system's
+    #[serde(default)]
+    pub tls_ca_cert_path: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Reads the user config and then `config` or `config.toml` from the current directory,
+    /// see [`Settings::load_from`].
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        Self::load_from(None, false)
+    }
+
+    /// Merges the config files that exist, later ones overriding earlier ones: the user config
+    /// (`$XDG_CONFIG_HOME/code_reviewer/config.toml`, else `~/.config/code_reviewer/config.toml`),
+    /// `config` or `config.toml` in the current directory, and then `path`. `CODE_REVIEWER_*`
+    /// environment variables override them all, e.g. `CODE_REVIEWER_MODEL`; lists such as
+    /// `CODE_REVIEWER_EXCLUDE_PATHS` are comma-separated. A setting that isn't in any of them
+    /// keeps its default. `path` must exist and its settings must be valid. Otherwise invalid
+    /// settings are reported and give the defaults, and unknown ones are reported and ignored;
+    /// with `strict`, both are errors.
+    pub fn load_from(path: Option<&Path>, strict: bool) -> Result<Self, Box<dyn Error>> {
+        let mut builder = Config::builder();
+        if let Some(user_config) = user_config_path() {
+            builder = builder.add_source(File::from(user_config).required(false));
+        }
+        builder = builder
+            .add_source(File::with_name("config").required(false))
+            .add_source(File::with_name("config.toml").required(false));
+        if let Some(path) = path {
+            if !path.is_file() {
+                return Err(format!("config file {} not found", path.display()).into());
+            }
+            // Without an extension to go by, the file is read as TOML
+            let file = match path.extension() {
+                Some(_) => File::from(path),
+                None => File::from(path).format(FileFormat::Toml),
+            };
+            builder = builder.add_source(file);
+        }
+
+        // Only the files are checked for unknown settings: CODE_REVIEWER_* variables that
+        // aren't settings, like CODE_REVIEWER_SKIP, are expected
+        let mut unknown = Vec::new();
+        let _: Result<Settings, _> = serde_ignored::deserialize(builder.build_cloned()?, |key| unknown.push(key.to_string()));
+        for key in &unknown {
+            if strict {
+                return Err(format!("invalid config: unknown setting '{}'", key).into());
+            }
+            warning!("unknown setting '{}' in the config is ignored", key);
+        }
+
+        let config = builder.add_source(environment()).build()?;
+        match (config.try_deserialize(), path) {
+            (Ok(settings), _) => Ok(settings),
+            (Err(e), Some(path)) => Err(format!("invalid config file {}: {}", path.display(), e).into()),
+            (Err(e), None) if strict => Err(format!("invalid config: {}", e).into()),
+            (Err(e), None) => {
+                warning!("invalid config: {}; using the default settings", e);
+                Ok(Settings::default())
+            }
+        }
+    }
+}
+
+/// `CODE_REVIEWER_<SETTING>` variables, with the list settings split at commas.
+fn environment() -> Environment {
+    let mut environment = Environment::with_prefix("CODE_REVIEWER")
+        .try_parsing(true)
+        .ignore_empty(true)
+        .list_separator(",");
+    for key in ["include_extensions", "exclude_extensions", "exclude_paths", "test_patterns"] {
+        environment = environment.with_list_parse_key(key);
+    }
+    environment
+}
+
+/// Where settings shared by every project live.
+fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("code_reviewer").join("config.toml"))
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            backend: BackendKind::default(),
+            ollama_url: default_ollama_url(),
+            ollama_api: OllamaApi::default(),
+            ollama_retries: default_ollama_retries(),
+            fallback_model: None,
+            anthropic_url: default_anthropic_url(),
+            anthropic_api_key: None,
+            max_tokens: default_max_tokens(),
+            model: default_model(),
+            max_files_context: default_max_files_context(),
+            max_diff_bytes: default_max_diff_bytes(),
+            large_diff: LargeDiffPolicy::default(),
+            max_file_size_kb: default_max_file_size_kb(),
+            max_file_content_chars: default_max_file_content_chars(),
+            max_file_content_lines: default_max_file_content_lines(),
+            include_extensions: Vec::new(),
+            exclude_extensions: default_exclude_extensions(),
+            exclude_paths: Vec::new(),
+            exclude_tests: false,
+            test_patterns: default_test_patterns(),
+            verify: false,
+            verifier_model: None,
+            score: false,
+            score_prompt: None,
+            system_prompt: default_system_prompt(),
+            pager: default_pager(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            gitlab_url: None,
+            gitea_url: None,
+            slack_webhook_url: None,
+            slack_review_lines: default_slack_review_lines(),
+            notes_ref: default_notes_ref(),
+            notes_append: false,
+            fail_on: None,
+            check_message_strict: false,
+            check_message_on_error: FailurePolicy::default(),
+            structured: false,
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: Vec::new(),
+            tls_cert_path: None,
+            tls_key_path: None,
+            tls_ca_cert_path: None,
+        }
+    }
+}
+
+fn default_ollama_url() -> String {
+    "http://localhost:11434".to_string()
+}
+
+fn default_ollama_retries() -> u32 {
+    2
+}
+
+fn default_anthropic_url() -> String {
+    "https://api.anthropic.com".to_string()
+}
+
+fn default_max_tokens() -> u32 {
+    4096
+}
+
+fn default_model() -> String {
+    "codellama".to_string()
+}
+
+fn default_max_diff_bytes() -> usize {
+    200 * 1024
+}
+
+fn default_max_files_context() -> usize {
+    5
+}
+
+fn default_exclude_extensions() -> Vec<String> {
+    ["lock", "min.js", "pb.go", "snap"].map(String::from).to_vec()
+}
+
+fn default_test_patterns() -> Vec<String> {
+    [
+        "**/tests/**",
+        "**/test/**",
+        "**/__tests__/**",
+        "**/*_test.rs",
+        "**/*_test.go",
+        "**/test_*.py",
+        "**/*_test.py",
+        "**/*.spec.ts",
+        "**/*.test.ts",
+        "**/*.spec.js",
+        "**/*.test.js",
+        "**/*Test.java",
+        "**/*_spec.rb",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_pager() -> bool {
+    true
+}
+
+fn default_max_concurrent_requests() -> usize {
+    2
+}
+
+fn default_slack_review_lines() -> usize {
+    20
+}
+
+fn default_notes_ref() -> String {
+    "code-review".to_string()
+}
+
+fn default_system_prompt() -> String {
+    "You are a senior software engineer doing a code review. You are thorough but concise, \
+    point to the exact file and line of each issue, explain why it matters, and don't comment \
+    on code that is fine."
+        .to_string()
+}
+
+fn default_max_file_size_kb() -> u64 {
+    256
+}
+
+fn default_max_file_content_chars() -> usize {
+    8000
+}
+
+fn default_max_file_content_lines() -> usize {
+    300
+}
+
+/// How many leading bytes are checked for NUL bytes to tell binary files apart.
+const BINARY_CHECK_LEN: usize = 8 * 1024;
+
+/// The outcome of reading one file of the codebase.
+enum SourceFile {
+    Text(String),
+    Binary,
+    TooLarge(u64),
+}
+
+async fn read_source_file(path: &Path, max_size: u64) -> Result<SourceFile, Box<dyn Error>> {
+    let size = tokio::fs::metadata(path).await?.len();
+    if size > max_size {
+        return Ok(SourceFile::TooLarge(size));
+    }
+    let bytes = tokio::fs::read(path).await?;
+    if bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return Ok(SourceFile::Binary);
+    }
+    Ok(SourceFile::Text(String::from_utf8(bytes)?))
+}
+
+/// Set by `--quiet`: only results and errors are printed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Silences warnings and status messages on stderr, for output that is piped into another
+/// program. Errors are still printed.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Whether `DEBUG=TRUE` asks for detailed logging on stderr.
+pub fn debug_enabled() -> bool {
+    std::env::var("DEBUG").unwrap_or_default() == "TRUE"
+}
+
+/// Clones share the backend and the usage totals, so a clone with a different `model`
+/// reviews with that model while its requests still count towards the same summary.
+#[derive(Clone)]
+pub struct CodeReviewTool {
+    backend: Arc<dyn Backend>,
+    model: String,
+    usage: Arc<Mutex<Usage>>,
+    /// Bounds how many generations are in flight at once, across all clones
+    requests: Arc<Semaphore>,
+    show_progress: bool,
+    verbose: bool,
+    /// Files larger than this many bytes are left out of the codebase context
+    max_file_size: u64,
+    /// Context files are cut off after this many characters
+    max_file_content_chars: usize,
+    /// Context files are cut off after this many lines
+    max_file_content_lines: usize,
+    /// Sent as the system prompt with every request
+    system_prompt: Option<String>,
+    /// Which codebase files are read for context
+    file_filter: FileFilter,
+    /// Extra gitignore-style exclusions for the codebase walk
+    ignore_file: Option<PathBuf>,
+    /// Put before the diff in the review prompt, saying where the changes come from
+    diff_description: Option<String>,
+    /// Functions and types the diff changes, by file, named before the diff
+    changed_symbols: BTreeMap<String, Vec<String>>,
+    /// Language whose extra checks the prompt asks for; those of every language in the diff
+    /// when unset
+    language: Option<LanguageProfile>,
+    /// Asks for the checks in [`language::RUST_ANTI_PATTERNS`], in the system prompt and in
+    /// the review prompt's focus list
+    rust_mode: bool,
+    /// Replaces the review request with the OWASP category ratings of [`audit::instructions`]
+    security_audit: bool,
+    /// Repository diffs are taken from and changed files read from, instead of the current
+    /// directory
+    repository: Option<PathBuf>,
+}
+
+/// A finished review together with what went into it, handed to every `Reporter`.
+#[derive(Debug, Default, Serialize)]
+pub struct ReviewResult {
+    /// Model API the review was generated with
+    pub backend: BackendKind,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ollama_url: Option<String>,
+    /// When the review finished, in RFC 3339
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Size of the reviewed diff
+    pub diff: DiffStats,
+    /// The reviewed diff itself
+    #[serde(skip)]
+    pub patch: String,
+    /// Files touched by the diff
+    pub files: Vec<String>,
+    /// Files sent to the model as context
+    pub context_files: Vec<String>,
+    /// The review as the model wrote it, in markdown
+    pub review: String,
+    /// Individual issues parsed from the review
+    pub findings: Vec<Finding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<ReviewScore>,
+    /// Category ratings, with `--security-audit`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_audit: Option<SecurityAuditReport>,
+    /// Tokens and time spent on every request for this review
+    pub usage: Usage,
+    /// Numbers from every stage of the review, with `--stats`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<ReviewStats>,
+}
+
+impl CodeReviewTool {
+    /// A reviewer that sends its requests to `backend`, using `model` or else `codellama`.
+    pub fn new(backend: Arc<dyn Backend>, model: Option<String>) -> Self {
+        CodeReviewTool {
+            backend,
+            model: model.unwrap_or_else(|| "codellama".to_string()),
+            usage: Arc::new(Mutex::new(Usage::default())),
+            requests: Arc::new(Semaphore::new(default_max_concurrent_requests())),
+            show_progress: true,
+            verbose: false,
+            max_file_size: default_max_file_size_kb() * 1024,
+            max_file_content_chars: default_max_file_content_chars(),
+            max_file_content_lines: default_max_file_content_lines(),
+            system_prompt: None,
+            file_filter: FileFilter::default(),
+            ignore_file: None,
+            diff_description: None,
+            changed_symbols: BTreeMap::new(),
+            language: None,
+            rust_mode: false,
+            security_audit: false,
+            repository: None,
+        }
+    }
+
+    /// Turns the progress spinners on stderr on or off.
+    pub fn with_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
+    }
+
+    /// Lists the context files included in each prompt on stderr.
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Sets the size limits for context files, see `max_file_size_kb`,
+    /// `max_file_content_chars` and `max_file_content_lines` in the settings.
+    pub fn with_file_limits(
+        mut self,
+        max_file_size_kb: u64,
+        max_file_content_chars: usize,
+        max_file_content_lines: usize,
+    ) -> Self {
+        self.max_file_size = max_file_size_kb * 1024;
+        self.max_file_content_chars = max_file_content_chars;
+        self.max_file_content_lines = max_file_content_lines;
+        self
+    }
+
+    /// Sets how many requests may be sent to the backend at the same time, at least one.
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.requests = Arc::new(Semaphore::new(max_concurrent_requests.max(1)));
+        self
+    }
+
+    /// Sets the persona sent as the system prompt; an empty prompt sends none.
+    pub fn with_system_prompt(mut self, system_prompt: String) -> Self {
+        self.system_prompt = (!system_prompt.trim().is_empty()).then_some(system_prompt);
+        self
+    }
+
+    /// Tells the model where the changes come from, e.g. which stash entry it is reviewing.
+    pub fn with_diff_description(mut self, diff_description: String) -> Self {
+        self.diff_description = Some(diff_description);
+        self
+    }
+
+    /// Names the functions and types the diff changes in the review prompt, see
+    /// [`symbols::parse_changed_symbols`].
+    pub fn with_changed_symbols(mut self, changed_symbols: BTreeMap<String, Vec<String>>) -> Self {
+        self.changed_symbols = changed_symbols;
+        self
+    }
+
+    /// Asks for the extra checks of `language` instead of those of the languages detected in
+    /// each diff.
+    pub fn with_language(mut self, language: Option<LanguageProfile>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Turns the Rust anti-pattern checks of `--rust-mode` on or off.
+    pub fn with_rust_mode(mut self, rust_mode: bool) -> Self {
+        self.rust_mode = rust_mode;
+        self
+    }
+
+    /// Asks for a security audit instead of a review: a rating per OWASP category that
+    /// [`audit::SecurityAuditReport::parse`] reads.
+    pub fn with_security_audit(mut self, security_audit: bool) -> Self {
+        self.security_audit = security_audit;
+        self
+    }
+
+    /// Restricts which files `tokenize_codebase_async` reads.
+    pub fn with_file_filter(mut self, file_filter: FileFilter) -> Self {
+        self.file_filter = file_filter;
+        self
+    }
+
+    /// Takes diffs from the git repository at `repository`, and reads the files they change
+    /// there, instead of in the current directory.
+    pub fn with_repository(mut self, repository: PathBuf) -> Self {
+        self.repository = Some(repository);
+        self
+    }
+
+    /// Leaves the paths matched by a gitignore-style file out of the codebase walk.
+    pub fn with_ignore_file(mut self, ignore_file: Option<PathBuf>) -> Self {
+        self.ignore_file = ignore_file;
+        self
+    }
+
+    /// A copy of this reviewer that reviews with `model` instead.
+    pub fn with_model(&self, model: &str) -> Self {
+        CodeReviewTool {
+            model: model.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this reviewer whose usage is counted apart from this one's, e.g. for one
+    /// review among many served at once. It still shares the limit on concurrent requests.
+    pub fn with_separate_usage(&self) -> Self {
+        CodeReviewTool {
+            usage: Arc::new(Mutex::new(Usage::default())),
+            ..self.clone()
+        }
+    }
+
+    /// Checks that the backend can be reached, see [`Backend::health`].
+    pub async fn health(&self) -> Result<(), Box<dyn Error>> {
+        self.backend.health().await
+    }
+
+    /// The model reviews are generated with.
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Token usage accumulated over every request made so far.
+    pub fn usage(&self) -> Usage {
+        self.usage.lock().unwrap().clone()
+    }
+
+    /// Diffs the working tree, the index, both, a stash entry or the commits since a base,
+    /// depending on `source`. Given `files`, a working tree diff is limited to them and taken
+    /// against HEAD, so it covers both their staged and unstaged changes. Stash entries are
+    /// always diffed in full.
+    pub async fn get_diff(&self, source: &DiffSource, files: &[String]) -> Result<String, Box<dyn Error>> {
+        let repository = self.repository.as_deref();
+        match source {
+            DiffSource::Stash(stash) => git::run_in(repository, &["stash", "show", "-p", stash.as_deref().unwrap_or(git::DEFAULT_STASH)]),
+            DiffSource::Staged => git_diff(repository, &["--staged"], files),
+            // Three dots diff against the merge base, leaving out what the base gained since
+            DiffSource::Base(base) => git_diff(repository, &[&format!("{}...HEAD", base)], files),
+            DiffSource::Commit(commit) => {
+                // A root commit has no parent, so it is compared to the empty tree
+                let parent = match git::resolve_commit(repository, &format!("{}^", commit)) {
+                    Some(parent) => parent,
+                    None => git::empty_tree(repository)?,
+                };
+                git_diff(repository, &[&parent, commit], files)
+            }
+            DiffSource::WorkingTree if !files.is_empty() => git_diff(repository, &["HEAD"], files),
+            DiffSource::WorkingTree => git_diff(repository, &[], files),
+            DiffSource::All => {
+                let staged = git_diff(repository, &["--staged"], files)?;
+                let unstaged = git_diff(repository, &[], files)?;
+                if staged.trim().is_empty() || unstaged.trim().is_empty() {
+                    return Ok(staged + &unstaged);
+                }
+                Ok(format!("{}\n{}{}\n{}", STAGED_LABEL, staged, UNSTAGED_LABEL, unstaged))
+            }
+        }
+    }
+
+    /// Reads every file under `root_path` that isn't ignored, up to 32 at a time.
+    pub async fn tokenize_codebase_async(&self, root_path: &Path) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+        let mut walk = WalkBuilder::new(root_path);
+        if let Some(ignore_file) = &self.ignore_file {
+            if let Some(e) = walk.add_ignore(ignore_file) {
+                warning!("Could not read ignore file {}: {}", ignore_file.display(), e);
+            }
+        }
+
+        let mut paths = Vec::new();
+        for entry in walk.build() {
+            match entry {
+                Ok(entry) => {
+                    let relative = entry.path().strip_prefix(root_path).unwrap_or(entry.path());
+                    if entry.path().is_file() && self.file_filter.allows(relative) {
+                        paths.push(entry.into_path());
+                    }
+                },
+                Err(e) => {
+                    warning!("Error accessing path: {}", e);
+                }
+            }
+        }
+
+        let progress = progress::counter(self.show_progress, "Reading codebase");
+        let max_size = self.max_file_size;
+        let mut reads = stream::iter(paths)
+            .map(|path| async move {
+                let file = read_source_file(&path, max_size).await;
+                (path, file)
+            })
+            .buffer_unordered(32);
+
+        let mut codebase = BTreeMap::new();
+        let mut stats = CodebaseStats::default();
+        while let Some((path, file)) = reads.next().await {
+            match file {
+                Ok(SourceFile::Text(content)) => {
+                    if let Ok(relative) = path.strip_prefix(root_path) {
+                        codebase.insert(relative.to_string_lossy().into_owned(), content);
+                    }
+                    stats.files_read += 1;
+                    progress.inc(1);
+                },
+                Ok(SourceFile::Binary) => {
+                    if self.verbose || debug_enabled() {
+                        progress.suspend(|| eprintln!("Skipping binary file {}", path.display()));
+                    }
+                    stats.binary_skipped += 1;
+                },
+                Ok(SourceFile::TooLarge(size)) => {
+                    if self.verbose || debug_enabled() {
+                        progress.suspend(|| {
+                            eprintln!(
+                                "Skipping {} ({} KB, over the {} KB limit)",
+                                path.display(),
+                                size.div_ceil(1024),
+                                max_size / 1024
+                            )
+                        });
+                    }
+                    stats.too_large += 1;
+                },
+                Err(e) => {
+                    progress.suspend(|| warning!("Could not read file {}: {}", path.display(), e));
+                }
+            }
+        }
+        progress.finish_and_clear();
+        if self.show_progress {
+            eprintln!("Codebase: {}", stats);
+        }
+        
+        if codebase.is_empty() {
+            warning!("No readable files found in the codebase");
+        }
+        
+        Ok(codebase)
+    }
+
+    /// Reads the files at `paths`, in the repository given to
+    /// [`CodeReviewTool::with_repository`] if any, skipping with a warning those that can't be
+    /// read.
+    pub fn load_files(&self, paths: &[String]) -> BTreeMap<String, String> {
+        let mut files = BTreeMap::new();
+
+        for path in paths {
+            let full_path = match &self.repository {
+                Some(repository) => repository.join(path),
+                None => PathBuf::from(path),
+            };
+            match fs::read_to_string(&full_path) {
+                Ok(content) => {
+                    files.insert(path.clone(), content);
+                },
+                Err(e) => {
+                    warning!("Could not read file {}: {}", path, e);
+                }
+            }
+        }
+
+        files
+    }
+
+    /// Builds the review prompt and asks the model for a review. Context files are keyed and
+    /// ordered by path, so the first `max_files_context` of them are always the same ones and
+    /// identical inputs produce an identical prompt.
+    pub async fn review_changes(
+        &self,
+        diff: String,
+        codebase_context: BTreeMap<String, String>,
+        max_files_context: usize,
+    ) -> Result<String, Box<dyn Error>> {
+        if self.verbose || debug_enabled() {
+            let context: Vec<_> = codebase_context.iter().take(max_files_context).collect();
+            log_context_files(&context);
+        }
+        let prompt = self.review_prompt(&diff, &codebase_context, max_files_context);
+        self.generate(&self.model, prompt).await
+    }
+
+    /// The prompt [`CodeReviewTool::review_changes`] sends: the diff, its description and up
+    /// to `max_files_context` context files.
+    pub fn review_prompt(
+        &self,
+        diff: &str,
+        codebase_context: &BTreeMap<String, String>,
+        max_files_context: usize,
+    ) -> String {
+        let mut prompt = String::new();
+        if let Some(description) = &self.diff_description {
+            prompt.push_str(&format!("{}\n\n", description));
+        }
+        // Only the files in this diff, which is one file's with --per-file
+        let symbols: Vec<String> = diff::changed_files(diff)
+            .iter()
+            .filter_map(|file| self.changed_symbols.get(file))
+            .flatten()
+            .map(|symbol| format!("`{}`", symbol))
+            .collect();
+        if !symbols.is_empty() {
+            prompt.push_str(&format!("The following functions were changed: {}.\n\n", symbols.join(", ")));
+        }
+        prompt.push_str(&format!(
+            "As a code reviewer, analyze the following changes:\n\n```diff\n{}\n```\n\n",
+            diff
+        ));
+
+        let context: Vec<_> = codebase_context.iter().take(max_files_context).collect();
+        if !context.is_empty() {
+            prompt.push_str("Relevant files from the codebase for context:\n\n");
+
+            let changed = diff::commentable_lines(diff);
+            for (filename, content) in context {
+                let content = truncate_content(
+                    content,
+                    self.max_file_content_chars,
+                    self.max_file_content_lines,
+                    changed.get(filename.as_str()),
+                );
+                prompt.push_str(&format!("{}:\n```{}\n{}\n```\n\n", filename, language::fence_language(filename), content));
+            }
+        }
+
+        let languages = match self.language {
+            Some(language) => vec![language],
+            None => {
+                let languages = language::detect_languages(diff);
+                if debug_enabled() {
+                    let names: Vec<String> = languages.iter().map(LanguageProfile::to_string).collect();
+                    eprintln!("Detected languages: {}", if names.is_empty() { "none".to_string() } else { names.join(", ") });
+                }
+                languages
+            }
+        };
+        if self.security_audit {
+            prompt.push_str(&audit::instructions(&languages));
+            return prompt;
+        }
+
+        prompt.push_str("\nPlease provide a detailed code review focusing on:\n\
+            1. Potential bugs or issues\n\
+            2. Code style and best practices\n\
+            3. Performance implications\n\
+            4. Security considerations\n\
+            5. Suggestions for improvement");
+        if self.rust_mode {
+            for (index, check) in language::RUST_ANTI_PATTERNS.iter().enumerate() {
+                prompt.push_str(&format!("\n{}. Rust: {}", index + 6, check));
+            }
+        }
+        match languages.as_slice() {
+            [] => {}
+            [language] => prompt.push_str(&format!("\n\nThis is {} code. {}", language, language.extra_instructions())),
+            _ => {
+                let names: Vec<String> = languages.iter().map(LanguageProfile::to_string).collect();
+                prompt.push_str(&format!("\n\nThis change has code in several languages: {}.", names.join(", ")));
+                for language in &languages {
+                    prompt.push_str(&format!("\nIn the {} code: {}", language, language.extra_instructions()));
+                }
+            }
+        }
+        prompt
+    }
+
+    /// Asks `verifier_model` to remove the claims in `review` that the diff doesn't support.
+    pub async fn verify_review(
+        &self,
+        diff: &str,
+        review: &str,
+        verifier_model: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        self.generate(verifier_model, verify_prompt(diff, review)).await
+    }
+
+    /// Reviews a diff and, when a verifier model is given, runs the verification pass over
+    /// the draft review.
+    pub async fn review_and_verify(
+        &self,
+        diff: &str,
+        codebase_context: BTreeMap<String, String>,
+        max_files_context: usize,
+        verifier_model: Option<&str>,
+    ) -> Result<String, Box<dyn Error>> {
+        let review = self.review_changes(diff.to_string(), codebase_context, max_files_context).await?;
+
+        let Some(verifier_model) = verifier_model else {
+            return Ok(review);
+        };
+        let verified = self.verify_review(diff, &review, verifier_model).await?;
+        if debug_enabled() {
+            eprintln!("Raw review:\n{}", review);
+            eprintln!("Verified review ({}):\n{}", verifier_model, verified);
+        }
+        Ok(verified)
+    }
+
+    /// Like [`CodeReviewTool::review_and_verify`], but asks for the findings as JSON in the
+    /// shape of [`structured::SCHEMA`] and returns them along with a markdown review written
+    /// from them.
+    pub async fn review_structured(
+        &self,
+        diff: &str,
+        codebase_context: BTreeMap<String, String>,
+        max_files_context: usize,
+        verifier_model: Option<&str>,
+    ) -> Result<(String, Vec<Finding>), Box<dyn Error>> {
+        if self.verbose || debug_enabled() {
+            let context: Vec<_> = codebase_context.iter().take(max_files_context).collect();
+            log_context_files(&context);
+        }
+        let prompt = self.review_prompt(diff, &codebase_context, max_files_context);
+        let (review, findings) = self.generate_findings(&self.model, prompt).await?;
+
+        let Some(verifier_model) = verifier_model else {
+            return Ok((review, findings));
+        };
+        self.generate_findings(verifier_model, verify_prompt(diff, &review)).await
+    }
+
+    /// Asks `model` for findings as JSON. An answer that can't be parsed is sent back once
+    /// with what was wrong with it; when the second answer is no better, it is read as a
+    /// free-text review instead.
+    async fn generate_findings(&self, model: &str, prompt: String) -> Result<(String, Vec<Finding>), Box<dyn Error>> {
+        let prompt = format!("{}\n\n{}", prompt, structured::INSTRUCTIONS);
+        let answer = self.generate_with(model, prompt.clone(), Some(&structured::SCHEMA)).await?;
+        let error = match structured::parse(&answer) {
+            Ok(findings) => return Ok((structured::render(&findings), findings)),
+            Err(error) => error,
+        };
+        if debug_enabled() {
+            eprintln!("Unparseable structured review ({}):\n{}", error, answer);
+        }
+
+        let retry = format!(
+            "{}\n\nYour previous answer could not be read as JSON findings ({}):\n\n{}\n\n\
+            Answer again with only the JSON object.",
+            prompt, error, answer
+        );
+        let answer = self.generate_with(model, retry, Some(&structured::SCHEMA)).await?;
+        match structured::parse(&answer) {
+            Ok(findings) => Ok((structured::render(&findings), findings)),
+            Err(error) => {
+                warning!("{} did not answer with JSON findings ({}), reading its answer as free text", model, error);
+                let findings = findings::parse_findings(&answer);
+                Ok((answer, findings))
+            }
+        }
+    }
+
+    /// Asks the model to rate the change, using `template` for the prompt.
+    pub async fn score_review(&self, template: &str, diff: &str, review: &str) -> Result<ReviewScore, Box<dyn Error>> {
+        let response = self.generate(&self.model, score::score_prompt(template, diff, review)).await?;
+        score::parse_score(&response)
+    }
+
+    /// Asks the model for unit test cases covering the functions changed in `diff`, written
+    /// for `test_framework` when given.
+    pub async fn suggest_tests(&self, diff: &str, test_framework: Option<&str>) -> Result<String, Box<dyn Error>> {
+        let mut prompt = format!(
+            "The following changes are under review:\n\n```diff\n{}\n```\n\n\
+            For each function or method modified in the diff, suggest specific unit test cases \
+            covering happy path, edge cases, and error conditions. Use idiomatic test patterns \
+            for the detected language.",
+            diff
+        );
+        if let Some(test_framework) = test_framework {
+            prompt.push_str(&format!(" Write the tests for {}.", test_framework));
+        }
+
+        self.generate(&self.model, prompt).await
+    }
+
+    /// Asks the model for a conventional commit message describing `diff`, scoped to the part
+    /// of the codebase it touches when that is clear from its paths.
+    pub async fn commit_message(&self, diff: &str) -> Result<String, Box<dyn Error>> {
+        let mut prompt = format!(
+            "Write a git commit message for the following staged changes:\n\n```diff\n{}\n```\n\n\
+            Use the Conventional Commits format: a subject line `type(scope): summary` of at most {} \
+            characters, in the imperative mood and without a trailing period, where type is one of \
+            feat, fix, refactor, perf, docs, test, build, ci or chore. Unless the subject says it all, \
+            follow it with a blank line and a body wrapped at 72 characters explaining what changed \
+            and why.",
+            diff,
+            commit::MAX_SUBJECT_LEN
+        );
+        let paths: Vec<String> = diff::parse(diff).into_iter().map(|file| file.path).collect();
+        match commit::infer_scope(&paths) {
+            Some(scope) => prompt.push_str(&format!(" Use `{}` as the scope.", scope)),
+            None => prompt.push_str(" Leave out the scope unless one area of the code clearly dominates."),
+        }
+        prompt.push_str(" Respond with only the commit message, without code fences or commentary.");
+
+        let message = self.generate(&self.model, prompt).await?;
+        Ok(commit::tidy_message(&message))
+    }
+
+    /// Asks the model whether `message` accurately and sufficiently describes `diff`, the
+    /// change it is about to be committed with.
+    pub async fn check_commit_message(&self, message: &str, diff: &str) -> Result<MessageCheck, Box<dyn Error>> {
+        let prompt = format!(
+            "A commit is about to be made with this message:\n\n{}\n\nThe staged changes:\n\n```diff\n{}\n```\n\n\
+            Judge whether the message accurately and sufficiently describes the changes. Start your \
+            answer with exactly one line: `VERDICT: GOOD` if it does, `VERDICT: INSUFFICIENT` if it \
+            is accurate but too vague to be useful (like \"wip\", \"fix\" or \"update\"), or \
+            `VERDICT: MISLEADING` if it claims something the changes don't do or leaves out what they \
+            mainly do. Then, unless the verdict is GOOD, list brief suggestions for a better message.",
+            message.trim(),
+            diff
+        );
+
+        Ok(MessageCheck::parse(&self.generate(&self.model, prompt).await?))
+    }
+
+    /// Reviews the same diff with every model in `models`, as many at a time as
+    /// `max_concurrent_requests` allows, returning the reviews in the order the models were given.
+    pub async fn compare_models(
+        &self,
+        models: &[String],
+        diff: &str,
+        codebase_context: &BTreeMap<String, String>,
+        max_files_context: usize,
+        verifier_model: Option<&str>,
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        // One spinner for all models; per-request spinners would draw over each other
+        let progress = progress::spinner(self.show_progress, format!("Waiting for {}", models.join(", ")));
+        let reviews = future::try_join_all(models.iter().map(|model| async move {
+            let reviewer = self.with_model(model).with_progress(false);
+            let review = reviewer
+                .review_and_verify(diff, codebase_context.clone(), max_files_context, verifier_model)
+                .await?;
+            Ok::<_, Box<dyn Error>>((model.clone(), review))
+        }))
+        .await;
+        progress.finish_and_clear();
+        reviews
+    }
+
+    /// Asks the model to merge reviews of the same change by different models into one.
+    pub async fn reconcile_reviews(&self, reviews: &[(String, String)]) -> Result<String, Box<dyn Error>> {
+        let mut prompt = String::from("The following are code reviews of the same change, each written by a different model:\n\n");
+        for (model, review) in reviews {
+            prompt.push_str(&format!("### {}\n\n{}\n\n", model, review));
+        }
+        prompt.push_str("Reconcile them into a single review. Point out where the reviews agree, \
+            where they contradict each other and which of them is right, and list the findings \
+            that only one review caught. Use the same list format as the reviews.");
+
+        self.generate(&self.model, prompt).await
+    }
+
+    /// Condenses per-file reviews into one overall summary.
+    pub async fn summarize_reviews(&self, reviews: &[(String, String)]) -> Result<String, Box<dyn Error>> {
+        let mut prompt = String::from("The following are code reviews of the individual files in one change:\n\n");
+        for (path, review) in reviews {
+            prompt.push_str(&format!("### {}\n\n{}\n\n", path, review));
+        }
+        prompt.push_str("Write a short overall summary of the change's quality, highlighting the most \
+            important issues across all files and any problems that span several files.");
+
+        self.generate(&self.model, prompt).await
+    }
+
+    async fn generate(&self, model: &str, prompt: String) -> Result<String, Box<dyn Error>> {
+        self.generate_with(model, prompt, None).await
+    }
+
+    /// The configured system prompt, followed in Rust mode by the anti-patterns to look for.
+    fn system_prompt(&self) -> Option<Cow<'_, str>> {
+        if !self.rust_mode {
+            return self.system_prompt.as_deref().map(Cow::Borrowed);
+        }
+        let mut system = self.system_prompt.clone().map(|system| format!("{}\n\n", system.trim_end())).unwrap_or_default();
+        system.push_str("When reviewing Rust code, always check for these anti-patterns and report each one you find:");
+        for check in language::RUST_ANTI_PATTERNS {
+            system.push_str(&format!("\n- {}", check));
+        }
+        Some(Cow::Owned(system))
+    }
+
+    /// Sends one prompt, asking for JSON matching `schema` when given one.
+    async fn generate_with(&self, model: &str, prompt: String, schema: Option<&Value>) -> Result<String, Box<dyn Error>> {
+        // Held until the response is in; the semaphore is never closed
+        let _permit = self.requests.acquire().await?;
+        let started = Instant::now();
+        let progress = progress::spinner(self.show_progress, format!("Waiting for {}", model));
+        let system = self.system_prompt();
+        let generation = match schema {
+            Some(schema) => self.backend.generate_structured(model, system.as_deref(), &prompt, schema).await,
+            None => self.backend.generate(model, system.as_deref(), &prompt).await,
+        };
+        progress.finish_and_clear();
+        let generation = generation?;
+
+        self.record_usage(model, &generation, started, prompt.len());
+        Ok(generation.text)
+    }
+
+    /// Asks the model for its next turn in `messages`, which alternate between the user and
+    /// the model and end with the user.
+    pub async fn chat(&self, messages: &[ChatMessage]) -> Result<String, Box<dyn Error>> {
+        let _permit = self.requests.acquire().await?;
+        let started = Instant::now();
+        let progress = progress::spinner(self.show_progress, format!("Waiting for {}", self.model));
+        let generation = self.backend.chat(&self.model, self.system_prompt().as_deref(), messages).await;
+        progress.finish_and_clear();
+        let generation = generation?;
+
+        let prompt_len = messages.iter().map(|message| message.content.len()).sum();
+        self.record_usage(&self.model, &generation, started, prompt_len);
+        Ok(generation.text)
+    }
+
+    /// Like [`chat`](Self::chat), but hands the answer to `on_text` piece by piece as the model
+    /// writes it, instead of showing a spinner.
+    pub async fn chat_streaming(
+        &self,
+        messages: &[ChatMessage],
+        on_text: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<String, Box<dyn Error>> {
+        let _permit = self.requests.acquire().await?;
+        let started = Instant::now();
+        let generation = self
+            .backend
+            .chat_streaming(&self.model, self.system_prompt().as_deref(), messages, on_text)
+            .await?;
+
+        let prompt_len = messages.iter().map(|message| message.content.len()).sum();
+        self.record_usage(&self.model, &generation, started, prompt_len);
+        Ok(generation.text)
+    }
+
+    /// Adds a finished request to the usage; `prompt_len` is the prompt's length in bytes,
+    /// without the system prompt.
+    fn record_usage(&self, model: &str, generation: &Generation, started: Instant, prompt_len: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.record(
+            model,
+            generation.prompt_tokens,
+            generation.completion_tokens,
+            generation.eval_duration_ns,
+            generation.total_duration_ns,
+            started.elapsed(),
+        );
+        let system_len = self.system_prompt().map_or(0, |system| system.len());
+        usage.prompt_bytes += (system_len + prompt_len) as u64;
+    }
+}
+
+/// The prompt asking a verifier model to clean up a draft review of `diff`.
+fn verify_prompt(diff: &str, review: &str) -> String {
+    format!(
+        "You are checking a draft code review written by another reviewer.\n\n\
+        The change under review:\n\n```diff\n{}\n```\n\n\
+        The draft review:\n\n{}\n\n\
+        Remove every claim that is incorrect or not supported by the diff above, \
+        and keep only the findings that the diff clearly supports. Do not add new findings. \
+        Return the cleaned-up review in the same format as the draft.",
+        diff, review
+    )
+}
+
+/// Lines kept before and after each line of the diff when a context file is truncated.
+const CHANGE_CONTEXT_LINES: usize = 10;
+
+/// Shortens a context file to at most `max_lines` lines and about `max_chars` characters.
+/// The top of the file, with its imports and signatures, is kept, and so are the lines
+/// around `changed`, the file's lines in the diff. Each gap is marked with
+/// `... [truncated N lines] ...`.
+fn truncate_content<'a>(
+    content: &'a str,
+    max_chars: usize,
+    max_lines: usize,
+    changed: Option<&BTreeSet<u32>>,
+) -> Cow<'a, str> {
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() <= max_lines && content.chars().count() <= max_chars {
+        return Cow::Borrowed(content);
+    }
+
+    // Half the lines go to the top of the file when there are changes to show as well
+    let changed: Vec<usize> = changed
+        .into_iter()
+        .flatten()
+        .map(|line| *line as usize)
+        .filter(|line| (1..=lines.len()).contains(line))
+        .collect();
+    let head = if changed.is_empty() { max_lines } else { max_lines / 2 };
+    let mut keep = vec![false; lines.len()];
+    let mut kept = head.min(lines.len());
+    keep[..kept].fill(true);
+    for line in changed {
+        let index = line - 1;
+        let end = (index + CHANGE_CONTEXT_LINES).min(lines.len() - 1);
+        for flag in &mut keep[index.saturating_sub(CHANGE_CONTEXT_LINES)..=end] {
+            if kept < max_lines && !*flag {
+                *flag = true;
+                kept += 1;
+            }
+        }
+    }
+
+    let mut truncated = String::new();
+    let mut chars = 0;
+    let mut skipped = 0;
+    let mut full = false;
+    for (line, keep) in lines.iter().zip(keep) {
+        let len = line.chars().count() + 1;
+        full |= keep && chars + len > max_chars;
+        if !keep || full {
+            skipped += 1;
+            continue;
+        }
+        if skipped > 0 {
+            truncated.push_str(&truncation_marker(skipped));
+            skipped = 0;
+        }
+        truncated.push_str(line);
+        truncated.push('\n');
+        chars += len;
+    }
+    if skipped > 0 {
+        truncated.push_str(&truncation_marker(skipped));
+    }
+    truncated.truncate(truncated.trim_end().len());
+    Cow::Owned(truncated)
+}
+
+fn truncation_marker(lines: usize) -> String {
+    let plural = if lines == 1 { "line" } else { "lines" };
+    format!("... [truncated {} {}] ...\n", lines, plural)
+}
+
+fn log_context_files(context: &[(&String, &String)]) {
+    if context.is_empty() {
+        eprintln!("Context: none");
+        return;
+    }
+    let total: usize = context.iter().map(|(_, content)| content.len()).sum();
+    let plural = if context.len() == 1 { "file" } else { "files" };
+    eprintln!("Context: {} {}, {} bytes", context.len(), plural, total);
+    for (filename, content) in context {
+        eprintln!("  {} ({} bytes)", filename, content.len());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+    use super::*;
+
+    /// Reading the codebase never calls the model.
+    struct NoBackend;
+
+    #[async_trait]
+    impl Backend for NoBackend {
+        async fn generate(&self, _: &str, _: Option<&str>, _: &str) -> Result<Generation, Box<dyn Error>> {
+            unreachable!("the codebase is read without the model")
+        }
+    }
+
+    fn reviewer() -> CodeReviewTool {
+        CodeReviewTool::new(Arc::new(NoBackend), None).with_progress(false)
+    }
+
+    /// A directory the walker treats as a git repository, without needing `git`.
+    fn repository() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn reads_files_with_relative_paths() {
+        let dir = repository();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "pub fn add() {}").unwrap();
+        fs::write(dir.path().join("README.md"), "# Readme").unwrap();
+
+        let codebase = reviewer().tokenize_codebase_async(dir.path()).await.unwrap();
+
+        let src_lib = Path::new("src").join("lib.rs").to_string_lossy().into_owned();
+        assert_eq!(codebase.keys().collect::<Vec<_>>(), ["README.md", src_lib.as_str()]);
+        assert_eq!(codebase[&src_lib], "pub fn add() {}");
+    }
+
+    #[tokio::test]
+    async fn skips_files_that_are_not_utf8() {
+        let dir = repository();
+        fs::write(dir.path().join("latin1.txt"), b"caf\xe9").unwrap();
+        fs::write(dir.path().join("image.bin"), b"\x89PNG\0\0").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let codebase = reviewer().tokenize_codebase_async(dir.path()).await.unwrap();
+
+        assert_eq!(codebase.keys().collect::<Vec<_>>(), ["main.rs"]);
+    }
+
+    #[tokio::test]
+    async fn honours_gitignore() {
+        let dir = repository();
+        fs::write(dir.path().join(".gitignore"), "secrets.env\ntarget/\n").unwrap();
+        fs::write(dir.path().join("secrets.env"), "TOKEN=1").unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target/out.rs"), "// generated").unwrap();
+        fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let codebase = reviewer().tokenize_codebase_async(dir.path()).await.unwrap();
+
+        assert_eq!(codebase.keys().collect::<Vec<_>>(), ["main.rs"]);
+    }
+
+    #[tokio::test]
+    async fn skips_files_over_the_size_limit() {
+        let dir = repository();
+        fs::write(dir.path().join("large.rs"), "x".repeat(2 * 1024 + 1)).unwrap();
+        fs::write(dir.path().join("small.rs"), "x".repeat(2 * 1024)).unwrap();
+
+        let codebase = reviewer()
+            .with_file_limits(2, default_max_file_content_chars(), default_max_file_content_lines())
+            .tokenize_codebase_async(dir.path())
+            .await
+            .unwrap();
+
+        assert_eq!(codebase.keys().collect::<Vec<_>>(), ["small.rs"]);
+    }
+
+    #[test]
+    fn unknown_settings_fail_only_strict_loads() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("review.toml");
+        fs::write(&path, "modle = \"llama3\"\nmax_tokens = 100\n").unwrap();
+
+        assert_eq!(Settings::load_from(Some(&path), false).unwrap().max_tokens, 100);
+        let error = Settings::load_from(Some(&path), true).unwrap_err().to_string();
+        assert_eq!(error, "invalid config: unknown setting 'modle'");
+    }
+}
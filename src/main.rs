@@ -1,200 +1,1344 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::path::Path;
-use std::process::Command;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::fs;
-use serde::{Deserialize, Serialize};
-use reqwest::Client;
-use tokio;
-use ignore::Walk;
-use config::{Config, File};
+use std::io::{self, IsTerminal};
+use clap::{Parser, Subcommand, ValueEnum};
+use futures::future;
+use tokio::net::TcpListener;
+use code_reviewer::audit::SecurityAuditReport;
+use code_reviewer::backend::{Backend, BackendKind};
+use code_reviewer::baseline::{Baseline, BASELINE_FILE};
+use code_reviewer::backend::anthropic::AnthropicBackend;
+use code_reviewer::backend::mock::MockBackend;
+use code_reviewer::backend::ollama::{OllamaApi, OllamaBackend};
+use code_reviewer::filter::FileFilter;
+use code_reviewer::findings::{self, Category, Severity};
+use code_reviewer::formats::{OutputFormat, RenderOptions};
+use code_reviewer::formats::diagnostics::SnippetSource;
+use code_reviewer::language::LanguageProfile;
+use code_reviewer::proxy::Proxies;
+use code_reviewer::tls::TlsFiles;
+use code_reviewer::reporters::Reporter;
+use code_reviewer::reporters::file::FileReporter;
+use code_reviewer::reporters::bitbucket::{BitbucketAuth, BitbucketReporter};
+use code_reviewer::reporters::gitea::GiteaReporter;
+use code_reviewer::reporters::github::{GithubReporter, PullRequestRef};
+use code_reviewer::reporters::gitlab::{GitlabReporter, MergeRequestRef};
+use code_reviewer::reporters::notes::NotesReporter;
+use code_reviewer::reporters::slack::SlackReporter;
+use code_reviewer::reporters::stdout::StdoutReporter;
+use code_reviewer::mcp::McpServer;
+use code_reviewer::server::Server;
+use code_reviewer::stats::ReviewStats;
+use code_reviewer::{commit, diff, gh, git, imports, patches, report, score, symbols, warning};
+use code_reviewer::diff::LargeDiffPolicy;
+use code_reviewer::commit::{FailurePolicy, Verdict};
+use code_reviewer::{CodeReviewTool, DiffSource, ReviewResult, Settings, STAGED_LABEL, UNSTAGED_LABEL};
 
-#[derive(Debug, Deserialize)]
-struct Settings {
-    #[serde(default = "default_ollama_url")]
-    ollama_url: String,
-    #[serde(default = "default_model")]
-    model: String,
+mod hooks;
+mod interactive;
+mod watch;
+
+use hooks::HookKind;
+
+/// Review the current git changes with a local Ollama model.
+#[derive(Debug, Clone, Parser)]
+#[command(name = "code_reviewer", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Review the staged changes instead of the working tree
+    #[arg(long)]
+    staged: bool,
+
+    /// Read settings from this file, on top of the user config and config.toml in the current
+    /// directory
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Fail on unknown or invalid settings in the config files instead of reporting them
+    #[arg(long)]
+    strict_config: bool,
+
+    /// Review the staged and the unstaged changes together, in separate sections
+    #[arg(long, conflicts_with_all = ["staged", "stash", "base", "diff_file"])]
+    all: bool,
+
+    /// Review the commits on HEAD since it branched off this revision, e.g. @{upstream} or main
+    #[arg(long, value_name = "REV", conflicts_with_all = ["staged", "stash"])]
+    base: Option<String>,
+
+    /// Review the changes of a single commit, with its message
+    #[arg(long, value_name = "SHA", conflicts_with_all = ["staged", "all", "stash", "base", "diff_file"])]
+    commit: Option<String>,
+
+    /// Review the diff in this file instead of asking git ('-' reads it from stdin)
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["staged", "base"])]
+    diff_file: Option<PathBuf>,
+
+    /// Review a stash entry instead of the working tree (defaults to stash@{0})
+    #[arg(long, num_args = 0..=1, value_name = "STASH", conflicts_with_all = ["staged", "diff_file", "files"])]
+    stash: Option<Option<String>>,
+
+    /// Review only these files, with just them as context ('-' reads the list from stdin),
+    /// e.g. `git diff --name-only HEAD | code_reviewer --files -`
+    #[arg(long, num_args = 1.., value_name = "FILE")]
+    files: Vec<String>,
+
+    /// Model API to send the review to
+    #[arg(long, value_enum)]
+    backend: Option<BackendKind>,
+
+    /// Answer with a canned review instead of asking a model, same as --backend mock
+    #[arg(long, conflicts_with = "backend")]
+    mock_backend: bool,
+
+    /// The canned review for the mock backend; implies --mock-backend
+    #[arg(long, value_name = "TEXT", allow_hyphen_values = true, conflicts_with = "backend")]
+    mock_response: Option<String>,
+
+    /// Ollama endpoint to send reviews to, overriding the configured ollama_api
+    #[arg(long, value_enum, value_name = "API")]
+    ollama_api: Option<OllamaApi>,
+
+    /// Model to review with, overriding the configured one
+    #[arg(long)]
+    model: Option<String>,
+
+    /// Print only the review: no banner, progress, warnings or status messages on stderr;
+    /// errors are still printed
+    #[arg(long, short)]
+    quiet: bool,
+
+    /// List the context files sent to the model, with their sizes
+    #[arg(long, short)]
+    verbose: bool,
+
+    /// Print statistics about the review when it is done: files, diff size, tokens and latency
+    #[arg(long)]
+    stats: bool,
+
+    /// Disable colored output (also honoured via the NO_COLOR environment variable)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Print long reviews directly instead of through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+
+    /// Run a second pass that removes findings not supported by the diff
+    #[arg(long)]
+    verify: bool,
+
+    /// Ask the model for findings as JSON instead of a free-text review, for more reliable
+    /// files, lines and severities; ignored with --per-file and --compare-models
+    #[arg(long)]
+    structured: bool,
+
+    /// Output format of the review
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Print the diff with the findings inserted after the lines they are about, same as
+    /// --format annotated-diff
+    #[arg(long, conflicts_with = "format")]
+    annotate_diff: bool,
+
+    /// Write the review in the selected format to this file and print the human-readable review
+    /// to the terminal ('-' means stdout)
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// With --output, add to the end of the file instead of overwriting it
+    #[arg(long, requires = "output")]
+    append: bool,
+
+    /// Exit with status 1 when the review has a finding at or above this severity, overriding
+    /// the configured fail_on; errors of the tool itself exit with status 2
+    #[arg(long, value_enum, value_name = "MIN")]
+    fail_on: Option<Severity>,
+
+    /// Only show findings at or above this severity
+    #[arg(long, value_enum, value_name = "MIN")]
+    severity_filter: Option<Severity>,
+
+    /// Only show findings in these categories, e.g. security,bugs
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "LIST")]
+    category_filter: Vec<Category>,
+
+    /// Drop findings without a line number from line-based formats instead of placing them on line 1
+    #[arg(long)]
+    drop_unlocated: bool,
+
+    /// Review a diff over max_diff_bytes in one request anyway
+    #[arg(long)]
+    force: bool,
+
+    /// Review each changed file separately, with that file's full contents as context
+    #[arg(long)]
+    per_file: bool,
+
+    /// With --per-file, finish with a summary generated from the individual reviews
+    #[arg(long, requires = "per_file")]
+    summary: bool,
+
+    /// Review with each of these models in parallel and reconcile their reviews, e.g. codellama,llama3
+    #[arg(long, alias = "models", value_delimiter = ',', value_name = "MODELS", conflicts_with = "per_file")]
+    compare_models: Vec<String>,
+
+    /// Ask the model for 1-10 quality ratings of the change after the review
+    #[arg(long)]
+    score: bool,
+
+    /// Language-specific checks to ask for, instead of those for the languages of the changed
+    /// files
+    #[arg(long, value_enum, value_name = "LANG")]
+    language: Option<LanguageProfile>,
+
+    /// Also check for common Rust anti-patterns: unwrap/expect outside tests, avoidable
+    /// clones, Box<dyn Error> in library code, Result without #[must_use], unsafe without a
+    /// SAFETY comment and unchecked integer overflow
+    #[arg(long)]
+    rust_mode: bool,
+
+    /// Audit the changes for security instead of reviewing them, rating each OWASP Top 10
+    /// category as N/A, OK or RISK with the language's usual pitfalls in mind
+    #[arg(long, conflicts_with_all = ["per_file", "compare_models", "structured", "verify"])]
+    security_audit: bool,
+
+    /// After the review, ask the model for unit test cases for the changed functions
+    #[arg(long)]
+    suggest_tests: bool,
+
+    /// With --suggest-tests, write the tests for this framework, e.g. pytest, jest or "cargo test"
+    #[arg(long, value_name = "NAME", requires = "suggest_tests")]
+    test_framework: Option<String>,
+
+    /// Which files to send to the model as context
+    #[arg(long, value_enum, default_value_t = ContextMode::Codebase)]
+    context: ContextMode,
+
+    /// Send as context only the files the added lines import, same as --context imports
+    #[arg(long, conflicts_with = "context")]
+    context_from_diff: bool,
+
+    /// Number of codebase files to send as context, overriding the configured max_files_context
+    #[arg(long, value_name = "N")]
+    max_files_context: Option<usize>,
+
+    /// Gitignore-style file of paths to leave out of the codebase context, instead of
+    /// .code_reviewer_ignore in the repository root
+    #[arg(long, value_name = "PATH")]
+    ignore_file: Option<PathBuf>,
+
+    /// Leave test files (matching the configured test_patterns) out of the codebase context
+    #[arg(long)]
+    exclude_tests: bool,
+
+    /// Review the diff alone, without building any codebase context
+    #[arg(long)]
+    no_context: bool,
+
+    /// Review again whenever files in the repository change, until Ctrl-C
+    #[arg(long, conflicts_with = "diff_file")]
+    watch: bool,
+
+    /// With --watch, only review again when a changed path matches one of these globs, relative
+    /// to the repository root, e.g. 'src/**'
+    #[arg(long, num_args = 1.., value_name = "GLOB", requires = "watch")]
+    on_change_paths: Vec<String>,
+
+    /// After the review, answer follow-up questions about it typed on stdin
+    #[arg(long, short, conflicts_with = "watch")]
+    interactive: bool,
+
+    /// After the review, apply the patches the model proposed in ```diff blocks to the working
+    /// tree, each one whole or not at all. Nothing is staged
+    #[arg(long, conflicts_with = "watch")]
+    apply: bool,
+
+    /// Like --apply, but only report whether each patch would apply
+    #[arg(long, conflicts_with_all = ["watch", "apply"])]
+    dry_run_apply: bool,
+
+    /// After the review, go through the findings one at a time and apply, skip or baseline
+    /// each. Only when stdin is a terminal
+    #[arg(long, conflicts_with = "watch")]
+    triage: bool,
+
+    /// Post the review to a GitHub pull request, given as owner/repo#number or as a number in
+    /// the repository of the origin remote
+    #[arg(long, value_name = "PR")]
+    github_pr: Option<PullRequestRef>,
+
+    /// With --github-pr, request changes instead of commenting when a finding is at or above this severity
+    #[arg(long, value_enum, value_name = "MIN", requires = "github_pr")]
+    github_review_event: Option<Severity>,
+
+    /// GitHub token for --github-pr (defaults to the GITHUB_TOKEN environment variable)
+    #[arg(long, value_name = "TOKEN")]
+    github_token: Option<String>,
+
+    /// Post the review to a GitLab merge request, given as project_id/mr_iid or as an iid in the
+    /// project of CI_PROJECT_ID (token from the GITLAB_TOKEN environment variable)
+    #[arg(long, value_name = "MR")]
+    gitlab_mr: Option<MergeRequestRef>,
+
+    /// Post the review to the merge request of the current GitLab CI pipeline
+    #[arg(long, conflicts_with = "gitlab_mr")]
+    gitlab: bool,
+
+    /// Post the review to a Gitea or Forgejo pull request, given as owner/repo#number or as a
+    /// number in the repository of the origin remote
+    #[arg(long, value_name = "PR")]
+    gitea_pr: Option<PullRequestRef>,
+
+    /// Gitea token for --gitea-pr (defaults to the GITEA_TOKEN environment variable)
+    #[arg(long, value_name = "TOKEN")]
+    gitea_token: Option<String>,
+
+    /// Post the review to a Bitbucket Cloud pull request, given as workspace/repo#id or as an id in
+    /// the repository of the origin remote (auth from BITBUCKET_TOKEN, or BITBUCKET_USERNAME and
+    /// BITBUCKET_APP_PASSWORD)
+    #[arg(long, value_name = "PR")]
+    bitbucket_pr: Option<PullRequestRef>,
+
+    /// Send a review summary to a Slack Incoming Webhook
+    #[arg(long, value_name = "URL")]
+    slack_webhook: Option<String>,
+
+    /// Store the review as a git note on the reviewed commit: HEAD, the --commit, or the stash entry
+    /// with --stash
+    #[arg(long)]
+    save_note: bool,
+
+    /// With --save-note, add to an earlier review's note on the same commit instead of replacing it
+    #[arg(long, requires = "save_note")]
+    note_append: bool,
+
+    /// Send a notification once the review is done; a failure to deliver it is only a warning
+    #[arg(long, value_enum, value_name = "TARGET", value_delimiter = ',')]
+    notify: Vec<NotifyTarget>,
+
+    /// Print what would be posted to GitHub instead of posting it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum Commands {
+    /// Run code_reviewer from git hooks, added to what the hooks already do; set
+    /// CODE_REVIEWER_SKIP=1 to skip it for a commit or push
+    #[command(alias = "install-hook")]
+    InstallHooks {
+        /// Which hooks to install, e.g. pre-commit,pre-push
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "pre-commit")]
+        hook: Vec<HookKind>,
+        /// Replace existing hooks instead of adding to them
+        #[arg(long, conflicts_with = "uninstall")]
+        force: bool,
+        /// Take code_reviewer out of the hooks instead
+        #[arg(long)]
+        uninstall: bool,
+    },
+    /// Take code_reviewer out of git hooks, same as install-hooks --uninstall
+    UninstallHook {
+        /// Which hooks to remove it from
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "pre-commit")]
+        hook: Vec<HookKind>,
+    },
+    /// Review a GitHub pull request against its title and description, fetched with the gh CLI;
+    /// review options go before the subcommand
+    Pr {
+        /// Pull request number, URL or branch
+        #[arg(value_name = "PR")]
+        pull_request: String,
+        /// Check out the pull request's branch first, so the codebase context is its version
+        #[arg(long)]
+        checkout: bool,
+    },
+    /// Write a conventional commit message for the staged changes and print it
+    CommitMsg {
+        /// Commit the staged changes with the message, after opening it in $EDITOR to confirm
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Check a commit message against the staged changes and suggest improvements; run by the
+    /// commit-msg hook
+    CheckMessage {
+        /// File holding the commit message, as passed to the commit-msg hook
+        #[arg(value_name = "MSGFILE")]
+        file: PathBuf,
+        /// Fail when the model finds the message vague or misleading, which blocks the commit
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Read reviews stored with --save-note
+    Notes {
+        #[command(subcommand)]
+        command: NotesCommand,
+    },
+    /// Manage the baseline of acknowledged findings, which reviews leave out
+    Baseline {
+        #[command(subcommand)]
+        command: BaselineCommand,
+    },
+    /// Review diffs posted over HTTP: POST /review takes {diff, context_files?, model?} and
+    /// answers with the review as JSON, GET /healthz checks that the model can be reached
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8088")]
+        listen: SocketAddr,
+        /// How many reviews run at once; further requests wait for one to finish
+        #[arg(long, value_name = "N", default_value_t = 4)]
+        max_reviews: usize,
+    },
+    /// Offer reviews to editors and agents as Model Context Protocol tools over stdin and
+    /// stdout: review_diff, review_staged and review_commit
+    Mcp,
+    /// Review again whenever files in the repository change, until Ctrl-C; same as --watch.
+    /// Review options go before the subcommand
+    Watch {
+        /// Only review again when a changed path matches one of these globs, relative to the
+        /// repository root, e.g. 'src/**'
+        #[arg(long, num_args = 1.., value_name = "GLOB")]
+        on_change_paths: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum BaselineCommand {
+    /// Review the changes and record every finding in the baseline, replacing what was there;
+    /// review options go before the subcommand
+    Write,
+    /// Drop the findings on files that no longer exist from the baseline
+    Prune,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum NotesCommand {
+    /// Print the review stored for a commit
+    Show {
+        /// Commit to show the review of
+        #[arg(default_value = "HEAD")]
+        commit: String,
+    },
 }
 
-fn default_ollama_url() -> String {
-    "http://localhost:11434".to_string()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ContextMode {
+    /// A selection of files from the whole codebase
+    Codebase,
+    /// The full contents of the files touched by the diff
+    ChangedFiles,
+    /// The files that lines added by the diff import, use, require or include
+    Imports,
 }
 
-fn default_model() -> String {
-    "codellama".to_string()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum NotifyTarget {
+    /// A Slack Incoming Webhook, from --slack-webhook, slack_webhook_url or SLACK_WEBHOOK_URL
+    Slack,
 }
 
+/// Exit status when the review or check went through and found the change wanting.
+const EXIT_REJECTED: i32 = 1;
+/// Exit status when the tool itself failed, e.g. because the model couldn't be reached.
+const EXIT_ERROR: i32 = 2;
+
+/// The change didn't pass: a finding at or above --fail-on, or a commit message rejected by
+/// check-message --strict. Unlike other errors, this isn't the tool failing.
 #[derive(Debug)]
-struct CodeReviewTool {
-    ollama_url: String,
-    model: String,
-    client: Client,
-}
-
-#[derive(Serialize)]
-struct OllamaRequest {
-    model: String,
-    prompt: String,
-    stream: bool,
-}
-
-#[derive(Debug, Deserialize)]
-struct OllamaResponse {
-    #[serde(default)]
-    response: String,
-    #[serde(default)]
-    done: bool,
-}
-
-impl CodeReviewTool {
-    fn new(ollama_url: Option<String>, model: Option<String>) -> Self {
-        CodeReviewTool {
-            ollama_url: ollama_url.unwrap_or_else(|| "http://localhost:11434".to_string()),
-            model: model.unwrap_or_else(|| "codellama".to_string()),
-            client: Client::new(),
-        }
-    }
-
-    async fn get_git_diff(&self, path: &str, staged: bool) -> Result<String, Box<dyn Error>> {
-        let mut cmd = Command::new("git");
-        cmd.arg("diff");
-        
-        if staged {
-            cmd.arg("--staged");
-        }
-        
-        cmd.arg(path);
-        
-        let output = cmd.output()?;
-        Ok(String::from_utf8(output.stdout)?)
-    }
-
-    fn tokenize_codebase(&self, root_path: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
-        let mut codebase = HashMap::new();
-        
-        for entry in Walk::new(root_path) {
-            match entry {
-                Ok(entry) => {
-                    let path = entry.path();
-                    
-                    if path.is_file() {
-                        match fs::read_to_string(path) {
-                            Ok(content) => {
-                                if let Ok(relative) = path.strip_prefix(root_path) {
-                                    codebase.insert(relative.to_string_lossy().into_owned(), content);
-                                }
-                            },
-                            Err(e) => {
-                                eprintln!("Warning: Could not read file {}: {}", path.display(), e);
-                                continue;
-                            }
-                        }
-                    }
-                },
-                Err(e) => {
-                    eprintln!("Warning: Error accessing path: {}", e);
-                    continue;
-                }
+struct Rejected(String);
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for Rejected {}
+
+#[tokio::main]
+async fn main() {
+    match run().await {
+        Ok(()) => {}
+        Err(e) if e.is::<Rejected>() => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_REJECTED);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+async fn run() -> Result<(), Box<dyn Error>> {
+    let mut cli = Cli::parse();
+    code_reviewer::set_quiet(cli.quiet);
+    if cli.annotate_diff {
+        cli.format = OutputFormat::AnnotatedDiff;
+    }
+    if cli.context_from_diff {
+        cli.context = ContextMode::Imports;
+    }
+
+    match &cli.command {
+        Some(Commands::InstallHooks { hook, force, uninstall: false }) => {
+            return hook.iter().try_for_each(|hook| hooks::install(*hook, *force));
+        }
+        Some(Commands::InstallHooks { hook, uninstall: true, .. } | Commands::UninstallHook { hook }) => {
+            return hook.iter().try_for_each(|hook| hooks::uninstall(*hook));
+        }
+        Some(Commands::Notes { command: NotesCommand::Show { commit } }) => return show_note(&cli, commit),
+        Some(Commands::CommitMsg { commit }) => return commit_message(&cli, *commit).await,
+        Some(Commands::CheckMessage { file, strict }) => return check_message(&cli, file, *strict).await,
+        Some(Commands::Baseline { command: BaselineCommand::Prune }) => return prune_baseline(),
+        Some(Commands::Serve { listen, max_reviews }) => return serve(&cli, *listen, *max_reviews).await,
+        Some(Commands::Mcp) => return mcp(&cli).await,
+        Some(Commands::Watch { on_change_paths }) => {
+            // What clap checks for --watch, which it can't for a subcommand
+            if cli.diff_file.is_some() || cli.interactive || cli.apply || cli.dry_run_apply || cli.triage {
+                return Err("watch can't be combined with --diff-file, --interactive, --apply, --dry-run-apply or --triage".into());
             }
+            cli = Cli { watch: true, on_change_paths: on_change_paths.clone(), command: None, ..cli };
         }
-        
-        if codebase.is_empty() {
-            eprintln!("Warning: No readable files found in the codebase");
+        Some(Commands::Pr { .. } | Commands::Baseline { command: BaselineCommand::Write }) | None => {}
+    }
+
+    if cli.watch {
+        if cli.command.is_some() {
+            return Err("--watch reviews the working tree and can't be combined with a subcommand".into());
         }
-        
-        Ok(codebase)
+        let root = git::toplevel().unwrap_or_else(|| PathBuf::from("."));
+        let ignore_file = cli
+            .ignore_file
+            .clone()
+            .or_else(|| Some(root.join(".code_reviewer_ignore")).filter(|path| path.is_file()));
+        let own_output: Vec<PathBuf> = cli.output.iter().cloned().collect();
+        // A pager would wait for the reader to quit it before watching resumes
+        let cli = Cli { no_pager: true, ..cli };
+        let only = cli.on_change_paths.clone();
+        return watch::watch(&root, ignore_file.as_deref(), &own_output, &only, || review(cli.clone())).await;
     }
 
-    async fn review_changes(
-        &self,
-        diff: String,
-        codebase_context: HashMap<String, String>,
-        max_files_context: usize,
-    ) -> Result<String, Box<dyn Error>> {
-        let mut prompt = format!(
-            "As a code reviewer, analyze the following changes:\n\n```diff\n{}\n```\n\n",
-            diff
-        );
+    review(cli).await
+}
 
-        prompt.push_str("Relevant files from the codebase for context:\n\n");
+/// Prints the review stored as a note on `commit`.
+fn show_note(cli: &Cli, commit: &str) -> Result<(), Box<dyn Error>> {
+    let settings = Settings::load_from(cli.config.as_deref(), cli.strict_config)?;
+    let sha = git::resolve_commit(None, commit).ok_or_else(|| format!("{} is not a commit", commit))?;
+    let note = git::note(&settings.notes_ref, &sha)?
+        .ok_or_else(|| format!("no review saved for {} in refs/notes/{}", commit, settings.notes_ref))?;
+    if report::color_enabled(cli.no_color) {
+        print!("{}", report::render_markdown(&note));
+    } else {
+        print!("{}", note);
+    }
+    Ok(())
+}
+
+/// Where the baseline of acknowledged findings is kept: the root of the working tree.
+fn baseline_path() -> PathBuf {
+    git::toplevel().unwrap_or_else(|| PathBuf::from(".")).join(BASELINE_FILE)
+}
+
+/// Drops the baseline entries for files that no longer exist.
+fn prune_baseline() -> Result<(), Box<dyn Error>> {
+    let path = baseline_path();
+    let mut baseline = Baseline::load(&path)?.ok_or_else(|| format!("no baseline at {}", path.display()))?;
+    let root = git::toplevel().unwrap_or_else(|| PathBuf::from("."));
+    let pruned = baseline.prune(&root);
+    baseline.save(&path)?;
+    eprintln!("Pruned {} of {} baselined findings", pruned, pruned + baseline.findings.len());
+    Ok(())
+}
+
+/// Contents of the file at `path` after the changes `source` diffs.
+fn changed_file(source: &DiffSource, path: &str) -> Option<String> {
+    let rev = match source {
+        DiffSource::Staged => "",
+        DiffSource::Commit(commit) => commit,
+        DiffSource::Stash(stash) => stash.as_deref().unwrap_or(git::DEFAULT_STASH),
+        DiffSource::WorkingTree | DiffSource::Base(_) | DiffSource::All => return fs::read_to_string(path).ok(),
+    };
+    git::run(&["show", &format!("{}:{}", rev, path)]).ok()
+}
 
-        for (filename, content) in codebase_context.iter().take(max_files_context) {
-            prompt.push_str(&format!("{}:\n```\n{}\n```\n\n", filename, content));
+/// Lists the patches in `review` with whether each applies to the working tree, applying
+/// those that do unless `dry_run`.
+fn apply_patches(review: &str, dry_run: bool) {
+    let patches = patches::extract(review);
+    if patches.is_empty() {
+        eprintln!("No patches in the review");
+        return;
+    }
+    let root = git::toplevel().unwrap_or_else(|| PathBuf::from("."));
+    for (index, patch) in patches.iter().enumerate() {
+        let name = format!("Patch {} ({})", index + 1, patch.files.join(", "));
+        match patch.check(&root) {
+            patches::Status::Applies if !dry_run => match patch.apply(&root) {
+                Ok(()) => eprintln!("{}: applied", name),
+                Err(e) => eprintln!("{}: does not apply:\n{}", name, e),
+            },
+            patches::Status::Fails(reason) => eprintln!("{}: does not apply:\n{}\n{}", name, reason, patch.diff.trim_end()),
+            status => eprintln!("{}: {}", name, status),
+        }
+    }
+}
+
+/// The model API selected on the command line or in the settings.
+fn backend(cli: &Cli, settings: &Settings) -> Result<(BackendKind, Arc<dyn Backend>), Box<dyn Error>> {
+    let backend_kind = if cli.mock_backend || cli.mock_response.is_some() {
+        BackendKind::Mock
+    } else {
+        cli.backend.unwrap_or(settings.backend)
+    };
+    let proxies = Proxies::from_settings(settings);
+    let tls = TlsFiles::from_settings(settings);
+    let backend: Arc<dyn Backend> = match backend_kind {
+        BackendKind::Ollama => Arc::new(
+            OllamaBackend::new(settings.ollama_url.clone())
+                .with_api(cli.ollama_api.unwrap_or(settings.ollama_api))
+                .with_retries(settings.ollama_retries, Duration::from_secs(1))
+                .with_fallback_model(settings.fallback_model.clone())
+                .with_connection(&proxies, &tls)?,
+        ),
+        BackendKind::Anthropic => {
+            let api_key = settings.anthropic_api_key.clone()
+                .or_else(|| std::env::var("ANTHROPIC_API_KEY").ok())
+                .ok_or("the anthropic backend requires anthropic_api_key in the config or ANTHROPIC_API_KEY")?;
+            Arc::new(
+                AnthropicBackend::new(settings.anthropic_url.clone(), api_key, settings.max_tokens)
+                    .with_connection(&proxies, &tls)?,
+            )
         }
+        BackendKind::Mock => Arc::new(match cli.mock_response.clone() {
+            Some(response) => MockBackend::with_response(response),
+            None => MockBackend::default(),
+        }),
+    };
+    Ok((backend_kind, backend))
+}
 
-        prompt.push_str("\nPlease provide a detailed code review focusing on:\n\
-            1. Potential bugs or issues\n\
-            2. Code style and best practices\n\
-            3. Performance implications\n\
-            4. Security considerations\n\
-            5. Suggestions for improvement");
+/// Serves reviews over HTTP on `listen` until interrupted.
+async fn serve(cli: &Cli, listen: SocketAddr, max_reviews: usize) -> Result<(), Box<dyn Error>> {
+    let settings = Settings::load_from(cli.config.as_deref(), cli.strict_config)?;
+    let (backend_kind, backend) = backend(cli, &settings)?;
+    let model = cli.model.clone().unwrap_or_else(|| settings.model.clone());
+    let verifier_model = settings.verifier_model.clone().unwrap_or_else(|| model.clone());
+    // Spinners from concurrent reviews would garble each other
+    let reviewer = CodeReviewTool::new(backend, Some(model))
+        .with_progress(false)
+        .with_verbose(cli.verbose)
+        .with_file_limits(
+            settings.max_file_size_kb,
+            settings.max_file_content_chars,
+            settings.max_file_content_lines,
+        )
+        .with_max_concurrent_requests(settings.max_concurrent_requests)
+        .with_system_prompt(settings.system_prompt.clone())
+        .with_language(cli.language)
+        .with_rust_mode(cli.rust_mode);
+    let server = Server::new(reviewer, backend_kind)
+        .with_ollama_url((backend_kind == BackendKind::Ollama).then(|| settings.ollama_url.clone()))
+        .with_max_files_context(cli.max_files_context.unwrap_or(settings.max_files_context))
+        .with_verifier_model((cli.verify || settings.verify).then_some(verifier_model))
+        .with_max_reviews(max_reviews);
 
-        let request = OllamaRequest {
-            model: self.model.clone(),
-            prompt,
-            stream: false,
-        };
+    let listener = TcpListener::bind(listen).await.map_err(|e| format!("could not listen on {}: {}", listen, e))?;
+    if !cli.quiet {
+        eprintln!("Listening on http://{}", listener.local_addr()?);
+    }
+    server.serve(listener, async {
+        tokio::signal::ctrl_c().await.ok();
+    }).await?;
+    Ok(())
+}
+
+/// Answers Model Context Protocol requests on stdin until it closes. Stdout carries the
+/// protocol, so nothing else may be printed there.
+async fn mcp(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let settings = Settings::load_from(cli.config.as_deref(), cli.strict_config)?;
+    let (_, backend) = backend(cli, &settings)?;
+    let model = cli.model.clone().unwrap_or_else(|| settings.model.clone());
+    let reviewer = CodeReviewTool::new(backend, Some(model))
+        .with_progress(false)
+        .with_file_limits(
+            settings.max_file_size_kb,
+            settings.max_file_content_chars,
+            settings.max_file_content_lines,
+        )
+        .with_max_concurrent_requests(settings.max_concurrent_requests)
+        .with_system_prompt(settings.system_prompt.clone())
+        .with_language(cli.language)
+        .with_rust_mode(cli.rust_mode);
+    let server = McpServer::new(reviewer)
+        .with_max_files_context(cli.max_files_context.unwrap_or(settings.max_files_context));
+    server.serve(tokio::io::BufReader::new(tokio::io::stdin()), tokio::io::stdout()).await?;
+    Ok(())
+}
+
+/// Prints a commit message for the staged changes or, with `commit`, commits them with it
+/// after the user has edited it.
+async fn commit_message(cli: &Cli, commit: bool) -> Result<(), Box<dyn Error>> {
+    if !git::inside_repository(None) {
+        return Err("not inside a git repository; run commit-msg from a git checkout".into());
+    }
+    let settings = Settings::load_from(cli.config.as_deref(), cli.strict_config)?;
+    let (_, backend) = backend(cli, &settings)?;
+    let model = cli.model.clone().unwrap_or_else(|| settings.model.clone());
+    let reviewer = CodeReviewTool::new(backend, Some(model))
+        .with_progress(!cli.quiet)
+        .with_system_prompt(settings.system_prompt.clone());
+
+    let diff = reviewer.get_diff(&DiffSource::Staged, &[]).await?;
+    if diff.trim().is_empty() {
+        return Err("nothing is staged; stage the changes to describe with git add first".into());
+    }
+
+    let message = reviewer.commit_message(&diff).await?;
+    if message.trim().is_empty() {
+        return Err(format!("{} answered with an empty commit message", reviewer.model()).into());
+    }
+    let subject_len = message.lines().next().unwrap_or_default().chars().count();
+    if subject_len > commit::MAX_SUBJECT_LEN {
+        warning!("the subject line is {} characters, over {}", subject_len, commit::MAX_SUBJECT_LEN);
+    }
+
+    if commit {
+        git::commit_with_editor(&message)
+    } else {
+        print!("{}", message);
+        Ok(())
+    }
+}
 
-        let response = self.client
-            .post(format!("{}/api/generate", self.ollama_url))
-            .json(&request)
-            .send()
-            .await?;
-            
-        // Get status before consuming response with text()
-        let status = response.status();
-        let text = response.text().await?;
-        
-        // Debug logging when DEBUG=TRUE
-        if std::env::var("DEBUG").unwrap_or_default() == "TRUE" {
-            eprintln!("Response status: {}", status);
-            eprintln!("Raw response: {}", text);
-        }
-
-        // Parse line by line as each line is a separate JSON object
-        let mut full_response = String::new();
-        for line in text.lines() {
-            if let Ok(resp) = serde_json::from_str::<OllamaResponse>(line) {
-                full_response.push_str(&resp.response);
-                if resp.done {
-                    break;
+/// Asks the model whether the commit message in `file` describes the staged changes, and prints
+/// its suggestions when it doesn't.
+async fn check_message(cli: &Cli, file: &Path, strict: bool) -> Result<(), Box<dyn Error>> {
+    let message = fs::read_to_string(file).map_err(|e| format!("could not read {}: {}", file.display(), e))?;
+    let message = commit::strip_comments(&message);
+    // git aborts a commit with an empty message by itself
+    if message.is_empty() {
+        return Ok(());
+    }
+
+    // A commit hook mustn't keep people waiting on retries of a server that isn't there
+    let settings = Settings { ollama_retries: 0, ..Settings::load_from(cli.config.as_deref(), cli.strict_config)? };
+    let (_, backend) = backend(cli, &settings)?;
+    let model = cli.model.clone().unwrap_or_else(|| settings.model.clone());
+    let reviewer = CodeReviewTool::new(backend, Some(model))
+        .with_progress(!cli.quiet)
+        .with_system_prompt(settings.system_prompt.clone());
+
+    let diff = reviewer.get_diff(&DiffSource::Staged, &[]).await?;
+    if diff.trim().is_empty() {
+        eprintln!("No staged changes to check the commit message against");
+        return Ok(());
+    }
+
+    let check = match reviewer.check_commit_message(&message, &diff).await {
+        Ok(check) => check,
+        Err(e) => {
+            return match settings.check_message_on_error {
+                FailurePolicy::Allow => {
+                    warning!("Could not check the commit message: {}", e);
+                    Ok(())
                 }
-            }
+                FailurePolicy::Block => Err(format!("could not check the commit message: {}", e).into()),
+            };
         }
+    };
 
-        Ok(full_response)
+    let problem = match check.verdict {
+        Verdict::Good => {
+            if !cli.quiet {
+                eprintln!("The commit message describes the change");
+            }
+            return Ok(());
+        }
+        Verdict::Insufficient => "too vague",
+        Verdict::Misleading => "misleading",
+    };
+    println!("The commit message is {}:\n\n{}", problem, check.feedback);
+    if strict || settings.check_message_strict {
+        return Err(Rejected(format!(
+            "Commit message rejected; edit it and commit again with `git commit --edit --file {}`",
+            file.display()
+        ))
+        .into());
     }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    // Load configuration
-    let config = Config::builder()
-        .add_source(File::with_name("config").required(false))
-        .add_source(File::with_name("config.toml").required(false))
-        .build()?;
-
-    let settings: Settings = config.try_deserialize().unwrap_or_else(|_| Settings {
-        ollama_url: default_ollama_url(),
-        model: default_model(),
-    });
-
-    let reviewer = CodeReviewTool::new(
-        Some(settings.ollama_url),
-        Some(settings.model)
-    );
-    
-    // Get codebase context
-    let codebase = reviewer.tokenize_codebase(Path::new("./"))?;
+/// Gets the diff, reviews it and hands the result to every reporter.
+async fn review(cli: Cli) -> Result<(), Box<dyn Error>> {
+    if cli.format == OutputFormat::Text && !cli.quiet && std::env::var("GITHUB_ACTIONS").is_ok_and(|v| v == "true") {
+        eprintln!("Hint: running in GitHub Actions, use --format github to get findings as annotations");
+    }
+
+    let settings = Settings::load_from(cli.config.as_deref(), cli.strict_config)?;
+    let (backend_kind, backend) = backend(&cli, &settings)?;
+
+    let model = cli.model.unwrap_or(settings.model);
+    let verifier_model = settings.verifier_model.clone().unwrap_or_else(|| model.clone());
+
+    let fail_on = cli.fail_on.or(settings.fail_on);
+
+    // Reporters run in this order once the review is done
+    let render_options = RenderOptions {
+        color: report::color_enabled(cli.no_color),
+        drop_unlocated: cli.drop_unlocated,
+        findings_only: cli.severity_filter.is_some() || !cli.category_filter.is_empty(),
+        snippets: if cli.staged { SnippetSource::Index } else { SnippetSource::WorkingTree },
+        fail_on,
+    };
+    let pager = !cli.no_pager && settings.pager;
+    let mut reporters: Vec<Box<dyn Reporter>> = Vec::new();
+    let report_path = cli.output
+        .as_deref()
+        .filter(|path| *path != Path::new("-"))
+        .map(|path| path.display().to_string());
+    let stdout = |format| StdoutReporter::new(format, render_options).with_pager(pager).with_banner(!cli.quiet);
+    match cli.output {
+        Some(path) if path != Path::new("-") => {
+            reporters.push(Box::new(stdout(OutputFormat::Text)));
+            let reporter = FileReporter::new(path, cli.format, RenderOptions {
+                color: false,
+                ..render_options
+            });
+            reporters.push(Box::new(reporter.with_append(cli.append)));
+        }
+        _ => reporters.push(Box::new(stdout(cli.format))),
+    }
+    if let Some(mut pull_request) = cli.github_pr {
+        if pull_request.needs_repository() {
+            let (owner, repo) = git::origin_repository()
+                .ok_or("could not find the GitHub repository from the origin remote, pass --github-pr owner/repo#number")?;
+            pull_request.owner = owner;
+            pull_request.repo = repo;
+        }
+        let token = cli.github_token.or_else(|| std::env::var("GITHUB_TOKEN").ok());
+        let token = match token {
+            Some(token) => token,
+            None if cli.dry_run => String::new(),
+            None => return Err("--github-pr requires a token via --github-token or GITHUB_TOKEN".into()),
+        };
+        reporters.push(Box::new(GithubReporter::new(pull_request, token)
+            .with_dry_run(cli.dry_run)
+            .with_review_event(cli.github_review_event)));
+    }
+    let merge_request = match cli.gitlab_mr {
+        Some(merge_request) => Some(merge_request),
+        None if cli.gitlab => Some(MergeRequestRef::from_ci().ok_or(
+            "--gitlab requires CI_PROJECT_ID and CI_MERGE_REQUEST_IID, run it in a merge request pipeline or pass --gitlab-mr",
+        )?),
+        None => None,
+    };
+    if let Some(mut merge_request) = merge_request {
+        if merge_request.needs_project() {
+            merge_request.project = std::env::var("CI_PROJECT_ID")
+                .map_err(|_| "could not find the GitLab project from CI_PROJECT_ID, pass --gitlab-mr project_id/mr_iid")?;
+        }
+        let token = std::env::var("GITLAB_TOKEN")
+            .map_err(|_| "posting to GitLab requires a token via GITLAB_TOKEN")?;
+        reporters.push(Box::new(
+            GitlabReporter::new(merge_request, token).with_gitlab_url(settings.gitlab_url.as_deref()),
+        ));
+    }
+    if let Some(mut pull_request) = cli.gitea_pr {
+        let remote = git::origin_remote();
+        if pull_request.needs_repository() {
+            let remote = remote.as_ref()
+                .ok_or("could not find the Gitea repository from the origin remote, pass --gitea-pr owner/repo#number")?;
+            pull_request.owner = remote.owner.clone();
+            pull_request.repo = remote.repo.clone();
+        }
+        let base_url = settings.gitea_url.clone()
+            .or_else(|| remote.map(|remote| remote.web_url))
+            .ok_or("could not find the Gitea instance from the origin remote, set gitea_url in the config")?;
+        let token = cli.gitea_token.or_else(|| std::env::var("GITEA_TOKEN").ok())
+            .ok_or("--gitea-pr requires a token via --gitea-token or GITEA_TOKEN")?;
+        reporters.push(Box::new(GiteaReporter::new(&base_url, pull_request, token)));
+    }
+    if let Some(mut pull_request) = cli.bitbucket_pr {
+        if pull_request.needs_repository() {
+            let (workspace, repo) = git::origin_repository()
+                .ok_or("could not find the Bitbucket repository from the origin remote, pass --bitbucket-pr workspace/repo#id")?;
+            pull_request.owner = workspace;
+            pull_request.repo = repo;
+        }
+        let auth = BitbucketAuth::from_env()
+            .ok_or("--bitbucket-pr requires BITBUCKET_TOKEN, or BITBUCKET_USERNAME and BITBUCKET_APP_PASSWORD")?;
+        reporters.push(Box::new(BitbucketReporter::new(pull_request, auth)));
+    }
+
+    // Notifications run after the reporters, and failing to deliver one doesn't fail the review
+    let mut notifiers: Vec<Box<dyn Reporter>> = Vec::new();
+    if cli.slack_webhook.is_some() || cli.notify.contains(&NotifyTarget::Slack) {
+        let webhook_url = cli.slack_webhook
+            .or(settings.slack_webhook_url.clone())
+            .or_else(|| std::env::var("SLACK_WEBHOOK_URL").ok())
+            .ok_or("--notify slack requires --slack-webhook, slack_webhook_url in the config or SLACK_WEBHOOK_URL")?;
+        notifiers.push(Box::new(
+            SlackReporter::new(webhook_url)
+                .with_review_lines(settings.slack_review_lines)
+                .with_report_path(report_path),
+        ));
+    }
+
+    let mut exclude_paths = settings.exclude_paths.clone();
+    if cli.exclude_tests || settings.exclude_tests {
+        exclude_paths.extend(settings.test_patterns.iter().cloned());
+    }
+    let reviewer = CodeReviewTool::new(backend, Some(model))
+        .with_progress(!cli.quiet)
+        .with_verbose(cli.verbose)
+        .with_file_limits(
+            settings.max_file_size_kb,
+            settings.max_file_content_chars,
+            settings.max_file_content_lines,
+        )
+        .with_max_concurrent_requests(settings.max_concurrent_requests)
+        .with_system_prompt(settings.system_prompt)
+        .with_language(cli.language)
+        .with_rust_mode(cli.rust_mode)
+        .with_security_audit(cli.security_audit)
+        .with_file_filter(FileFilter::new(
+            &settings.include_extensions,
+            &settings.exclude_extensions,
+            &exclude_paths,
+        ))
+        .with_ignore_file(cli.ignore_file.clone().or_else(|| {
+            git::toplevel()
+                .map(|root| root.join(".code_reviewer_ignore"))
+                .filter(|path| path.is_file())
+        }));
     
+    if cli.interactive && (cli.diff_file.as_deref() == Some(Path::new("-")) || cli.files == ["-"]) {
+        return Err("--interactive reads questions from stdin, so the diff and files can't come from it".into());
+    }
     // Get current changes
-    let diff = reviewer.get_git_diff(".", false).await?;
-    
-    // Get review
-    let review = reviewer.review_changes(diff, codebase, 5).await?;
-    println!("\nCode Review Results:");
-    println!("{}", review);
-    
+    let files = if cli.files == ["-"] {
+        if cli.diff_file.as_deref() == Some(Path::new("-")) {
+            return Err("--files - and --diff-file - can't both read from stdin".into());
+        }
+        io::read_to_string(io::stdin())?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect()
+    } else {
+        cli.files.clone()
+    };
+    if !cli.files.is_empty() && files.is_empty() {
+        if !cli.quiet {
+            eprintln!("No files to review");
+        }
+        return Ok(());
+    }
+
+    let pull_request = match &cli.command {
+        Some(Commands::Pr { pull_request, checkout }) => {
+            if cli.staged || cli.all || cli.stash.is_some() || cli.base.is_some() || cli.commit.is_some() || cli.diff_file.is_some() || !cli.files.is_empty() {
+                return Err("pr can't be combined with --staged, --all, --stash, --base, --commit, --diff-file or --files".into());
+            }
+            if *checkout {
+                gh::checkout(pull_request)?;
+            }
+            Some(pull_request.as_str())
+        }
+        _ => None,
+    };
+
+    // Resolved to a full hash up front, so a short or symbolic name can't come to mean
+    // another commit during the review
+    let reviewed_commit = match &cli.commit {
+        Some(rev) => Some(git::resolve_commit(None, rev).ok_or_else(|| format!("--commit: {} is not a commit", rev))?),
+        None => None,
+    };
+
+    // Resolved now, after a pull request checkout, so the note lands on the reviewed commit
+    // even if HEAD moves during the review
+    if cli.save_note {
+        let rev = match (&cli.command, &cli.stash, &reviewed_commit) {
+            (Some(Commands::Pr { checkout: false, .. }), _, _) => {
+                return Err("--save-note with pr requires --checkout, to have the pull request's commit locally".into());
+            }
+            (_, Some(stash), _) => stash.as_deref().unwrap_or(git::DEFAULT_STASH),
+            (_, _, Some(commit)) => commit,
+            _ => "HEAD",
+        };
+        let commit = git::resolve_commit(None, rev).ok_or_else(|| format!("--save-note: {} is not a commit", rev))?;
+        reporters.push(Box::new(
+            NotesReporter::new(settings.notes_ref.clone(), commit).with_append(cli.note_append || settings.notes_append),
+        ));
+    }
+
+    let source = match (cli.stash, &cli.base, &reviewed_commit) {
+        (Some(stash), _, _) => DiffSource::Stash(stash),
+        (None, Some(base), _) => DiffSource::Base(base.clone()),
+        (None, None, Some(commit)) => DiffSource::Commit(commit.clone()),
+        (None, None, None) if cli.staged => DiffSource::Staged,
+        (None, None, None) if cli.all => DiffSource::All,
+        (None, None, None) => DiffSource::WorkingTree,
+    };
+    let diff = if let Some(pull_request) = pull_request {
+        gh::pull_request_diff(pull_request)?
+    } else {
+        match &cli.diff_file {
+            Some(path) if path == Path::new("-") => io::read_to_string(io::stdin())?,
+            Some(path) => fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?,
+            None => {
+                if !git::inside_repository(None) {
+                    return Err("not inside a git repository; run code_reviewer from a git checkout or pass --diff-file".into());
+                }
+                reviewer.get_diff(&source, &files).await?
+            }
+        }
+    };
+    if diff.trim().is_empty() {
+        if !cli.quiet {
+            eprintln!("No changes to review");
+        }
+        return Ok(());
+    }
+
+    let mut per_file = cli.per_file;
+    if settings.max_diff_bytes > 0 && diff.len() > settings.max_diff_bytes && !per_file {
+        let size = format!("the diff is {} bytes, over the max_diff_bytes limit of {}", diff.len(), settings.max_diff_bytes);
+        if cli.force {
+            warning!("{}; reviewing it in one request because of --force", size);
+        } else if settings.large_diff == LargeDiffPolicy::PerFile {
+            warning!("{}; reviewing it file by file", size);
+            per_file = true;
+        } else {
+            return Err(format!("{}; review fewer changes, pass --per-file, or pass --force to review it in one request anyway", size).into());
+        }
+    }
+
+    let reviewer = if let Some(pull_request) = pull_request {
+        let pull_request = gh::pull_request(pull_request)?;
+        reviewer.with_diff_description(format!(
+            "These changes are a pull request. Check that they do what its author says they do:\n\n{}",
+            pull_request.description()
+        ))
+    } else {
+        match &source {
+            DiffSource::Stash(stash) => {
+                let stash = stash.as_deref().unwrap_or(git::DEFAULT_STASH);
+                reviewer.with_diff_description(format!(
+                    "These changes are stashed work in progress, not yet committed. The stash entry:\n\n{}",
+                    git::stash_description(stash)?
+                ))
+            }
+            DiffSource::Commit(commit) => reviewer.with_diff_description(format!(
+                "These changes are a single commit. Check that they do what its message says they do:\n\n{}",
+                git::commit_description(None, commit)?
+            )),
+            DiffSource::All if diff.contains(UNSTAGED_LABEL) => reviewer.with_diff_description(format!(
+                "These changes are everything touched since the last commit, in two parts: the changes \
+                staged for the next commit under \"{}\", then the unstaged changes under \"{}\". A file \
+                can be in both parts; its unstaged hunks are relative to its staged version.",
+                STAGED_LABEL, UNSTAGED_LABEL
+            )),
+            _ => reviewer,
+        }
+    };
+    let reviewer = reviewer.with_changed_symbols(symbols::parse_changed_symbols(&diff, |path| changed_file(&source, path)));
+
+    let verifier_model = (cli.verify || settings.verify).then_some(verifier_model.as_str());
+    let max_files_context = cli.max_files_context.unwrap_or(settings.max_files_context);
+    let use_context = !cli.no_context && max_files_context > 0;
+
+    // How many files the codebase walk found, for --stats
+    let mut codebase_files = None;
+    // The ratings of --security-audit, when the model's answer had them
+    let mut security_audit = None;
+    let (review, findings, context_files) = if per_file {
+        let files = diff::parse(&diff);
+        let mut review = String::new();
+        let mut findings = Vec::new();
+        let mut context_files = Vec::new();
+        let mut reviews = Vec::new();
+
+        // Files are reviewed concurrently, up to max_concurrent_requests at a time, so the
+        // per-request spinners are replaced by a line per file
+        let file_reviewer = reviewer.clone().with_progress(false);
+        let (total, quiet) = (files.len(), cli.quiet);
+        let max_diff_bytes = (settings.max_diff_bytes > 0 && !cli.force).then_some(settings.max_diff_bytes);
+        let file_reviews = future::try_join_all(files.iter().enumerate().map(|(index, file)| {
+            let file_reviewer = &file_reviewer;
+            async move {
+                if !quiet {
+                    eprintln!("[{}/{}] Reviewing {}...", index + 1, total, file.path);
+                }
+                let started = Instant::now();
+
+                // A deleted or binary file has no contents to send
+                let context = if use_context && !file.is_deleted() && !file.binary {
+                    file_reviewer.load_files(std::slice::from_ref(&file.path))
+                } else {
+                    BTreeMap::new()
+                };
+                let context_files: Vec<String> = context.keys().cloned().collect();
+
+                // A file still over max_diff_bytes on its own is reviewed in pieces
+                let parts = match max_diff_bytes {
+                    Some(max_bytes) if file.diff.len() > max_bytes => diff::chunk_diff(&file.diff, max_bytes),
+                    _ => vec![file.diff.clone()],
+                };
+                if parts.len() > 1 && !quiet {
+                    eprintln!("[{}/{}] {} is {} bytes, reviewing it in {} parts", index + 1, total, file.path, file.diff.len(), parts.len());
+                }
+                let mut part_reviews = Vec::new();
+                for (part, part_diff) in parts.iter().enumerate() {
+                    let part_review = file_reviewer.review_and_verify(part_diff, context.clone(), 1, verifier_model).await?;
+                    part_reviews.push(if parts.len() > 1 {
+                        format!("### Part {} of {}\n\n{}", part + 1, parts.len(), part_review.trim_end())
+                    } else {
+                        part_review
+                    });
+                }
+                let file_review = part_reviews.join("\n\n");
+                if !quiet {
+                    eprintln!("[{}/{}] {} done in {:.1}s", index + 1, total, file.path, started.elapsed().as_secs_f64());
+                }
+                Ok::<_, Box<dyn Error>>((file_review, context_files))
+            }
+        }))
+        .await?;
+
+        for (file, (file_review, file_context)) in files.iter().zip(file_reviews) {
+            context_files.extend(file_context);
+            // Findings that don't name a file are about the file under review
+            for mut finding in findings::parse_findings(&file_review) {
+                finding.file.get_or_insert_with(|| file.path.clone());
+                findings.push(finding);
+            }
+            review.push_str(&format!("## {}\n\n{}\n\n", file.path, file_review.trim_end()));
+            reviews.push((file.path.clone(), file_review));
+        }
+
+        if cli.summary && !reviews.is_empty() {
+            if !cli.quiet {
+                eprintln!("Summarizing {} reviews...", reviews.len());
+            }
+            let summary = reviewer.summarize_reviews(&reviews).await?;
+            review.push_str(&format!("## Summary\n\n{}\n", summary.trim_end()));
+        }
+
+        (review, findings, context_files)
+    } else {
+        // Get codebase context
+        let (codebase, max_files_context) = match cli.context {
+            _ if !use_context => (BTreeMap::new(), 0),
+            // Explicitly listed files replace the codebase walk
+            _ if !files.is_empty() => {
+                let files = reviewer.load_files(&files);
+                let count = files.len();
+                (files, count)
+            }
+            ContextMode::Codebase => {
+                let codebase = reviewer.tokenize_codebase_async(Path::new("./")).await?;
+                codebase_files = Some(codebase.len());
+                (codebase, max_files_context)
+            }
+            ContextMode::ChangedFiles => {
+                let files = reviewer.load_files(&diff::changed_files(&diff));
+                let count = files.len();
+                (files, count)
+            }
+            ContextMode::Imports => {
+                let mut codebase = reviewer.tokenize_codebase_async(Path::new("./")).await?;
+                codebase_files = Some(codebase.len());
+                let imported = imports::imported_files(&diff, &codebase.keys().map(String::as_str).collect());
+                codebase.retain(|path, _| imported.contains(path));
+                if !cli.quiet {
+                    eprintln!("Using {} imported file(s) as context", codebase.len());
+                }
+                let count = codebase.len();
+                (codebase, count)
+            }
+        };
+        let context_files: Vec<String> = codebase.keys().take(max_files_context).cloned().collect();
+
+        // Get review
+        let (review, findings) = if cli.security_audit {
+            let answer = reviewer.review_changes(diff.clone(), codebase, max_files_context).await?;
+            match SecurityAuditReport::parse(&answer) {
+                Ok(report) => {
+                    let review = report.render();
+                    let findings = report.findings();
+                    security_audit = Some(report);
+                    (review, findings)
+                }
+                Err(e) => {
+                    warning!("{}, reading the answer as a free-text review", e);
+                    let findings = findings::parse_findings(&answer);
+                    (answer, findings)
+                }
+            }
+        } else if cli.compare_models.is_empty() && (cli.structured || settings.structured) {
+            reviewer.review_structured(&diff, codebase, max_files_context, verifier_model).await?
+        } else if cli.compare_models.is_empty() {
+            let review = reviewer.review_and_verify(&diff, codebase, max_files_context, verifier_model).await?;
+            let findings = findings::parse_findings(&review);
+            (review, findings)
+        } else {
+            let reviews = reviewer
+                .compare_models(&cli.compare_models, &diff, &codebase, max_files_context, verifier_model)
+                .await?;
+            if !cli.quiet {
+                eprintln!("Reconciling {} reviews with {}...", reviews.len(), reviewer.model());
+            }
+            let reconciled = reviewer.reconcile_reviews(&reviews).await?;
+
+            let mut review = String::new();
+            for (model, model_review) in &reviews {
+                review.push_str(&format!("=== {} ===\n\n{}\n\n", model, model_review.trim_end()));
+            }
+            review.push_str(&format!("=== Reconciled ({}) ===\n\n{}\n", reviewer.model(), reconciled.trim_end()));
+
+            // Findings come from the reconciled review only, so findings that several models
+            // agree on aren't reported once per model
+            let findings = findings::parse_findings(&reconciled);
+            (review, findings)
+        };
+        (review, findings, context_files)
+    };
+
+    let score = if cli.score || settings.score {
+        let template = settings.score_prompt.as_deref().unwrap_or(score::DEFAULT_SCORE_PROMPT);
+        match reviewer.score_review(template, &diff, &review).await {
+            Ok(score) => Some(score),
+            Err(e) => {
+                warning!("Could not score the review: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Appended after the findings were parsed, so proposed test cases aren't reported as findings
+    let mut review = review;
+    if cli.suggest_tests {
+        match reviewer.suggest_tests(&diff, cli.test_framework.as_deref()).await {
+            Ok(tests) => review = format!("{}\n\n## Suggested Tests\n\n{}\n", review.trim_end(), tests.trim_end()),
+            Err(e) => warning!("Could not suggest tests: {}", e),
+        }
+    }
+
+    let mut result = ReviewResult {
+        backend: backend_kind,
+        model: reviewer.model().to_string(),
+        ollama_url: (backend_kind == BackendKind::Ollama).then_some(settings.ollama_url),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        repository: git::repository_name(),
+        branch: git::current_branch(),
+        commit: reviewed_commit.or_else(git::head_commit),
+        diff: diff::diff_stats(&diff),
+        files: diff::parse(&diff).into_iter().map(|file| file.path).collect(),
+        patch: diff,
+        context_files,
+        findings,
+        review,
+        score,
+        security_audit,
+        usage: reviewer.usage(),
+        stats: None,
+    };
+    if cli.stats {
+        result.stats = Some(ReviewStats::new(codebase_files, result.context_files.len(), &result.patch, &result.usage));
+    }
+
+    let baseline_path = baseline_path();
+    if let Some(Commands::Baseline { command: BaselineCommand::Write }) = &cli.command {
+        let baseline = Baseline::from_findings(&result.findings);
+        baseline.save(&baseline_path)?;
+        eprintln!("Wrote {} findings to {}", baseline.findings.len(), baseline_path.display());
+    }
+    // Before the other filters and the --fail-on gate, so acknowledged findings never fail a review
+    if let Some(baseline) = Baseline::load(&baseline_path)? {
+        let suppressed = baseline.filter(&mut result.findings);
+        if suppressed > 0 && !cli.quiet {
+            eprintln!("{} baselined findings suppressed.", suppressed);
+        }
+    }
+
+    if let Some(min) = cli.severity_filter {
+        let suppressed = findings::filter_by_severity(&mut result.findings, min);
+        for (severity, count) in suppressed.iter().rev().filter(|_| !cli.quiet) {
+            let plural = if *count == 1 { "finding" } else { "findings" };
+            eprintln!("{} {}-level {} suppressed.", count, severity, plural);
+        }
+    }
+    if !cli.category_filter.is_empty() {
+        let suppressed = findings::filter_by_category(&mut result.findings, &cli.category_filter);
+        if suppressed > 0 && !cli.quiet {
+            eprintln!("{} findings outside the selected categories suppressed.", suppressed);
+        }
+    }
+
+    // A reporter failing, e.g. posting to GitHub, must not keep the others from running
+    let mut failed = 0;
+    for reporter in &reporters {
+        if let Err(e) = reporter.report(&result).await {
+            eprintln!("Error: {}", e);
+            failed += 1;
+        }
+    }
+    for notifier in &notifiers {
+        if let Err(e) = notifier.report(&result).await {
+            warning!("Could not send notification: {}", e);
+        }
+    }
+
+    if !cli.quiet {
+        eprintln!("\n{}", result.usage);
+    }
+    if let Some(stats) = &result.stats {
+        eprint!("\n{}", stats);
+    }
+
+    if cli.apply || cli.dry_run_apply {
+        apply_patches(&result.review, cli.dry_run_apply);
+    }
+
+    if cli.triage && !result.findings.is_empty() {
+        if io::stdin().is_terminal() {
+            interactive::triage(&result.review, &result.findings, &render_options, &baseline_path)?;
+        } else {
+            warning!("--triage needs stdin to be a terminal; skipping it");
+        }
+    }
+
+    if cli.interactive {
+        let context = reviewer.load_files(&result.context_files);
+        let prompt = reviewer.review_prompt(&result.patch, &context, context.len());
+        interactive::follow_up(&reviewer, prompt, result.review.clone()).await?;
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} reporters failed", failed, reporters.len()).into());
+    }
+    if let Some(min) = fail_on {
+        let failing = result.findings.iter().filter(|finding| finding.severity >= min).count();
+        if failing > 0 {
+            let plural = if failing == 1 { "finding" } else { "findings" };
+            return Err(Rejected(format!("Review failed: {} {} at or above {}", failing, plural, min)).into());
+        }
+    }
     Ok(())
 }
\ No newline at end of file
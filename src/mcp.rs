@@ -0,0 +1,369 @@
+use std::error::Error;
+use std::path::Path;
+use serde_json::{json, Value};
+use tokio::io::{self, AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use crate::{diff, git, CodeReviewTool, DiffSource};
+
+/// Protocol version answered to clients that don't ask for one.
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A Model Context Protocol server, so editors and agents can ask for reviews as tool calls.
+/// It reads JSON-RPC messages from stdin, one per line, and answers on stdout; anything
+/// else it has to say goes to stderr.
+///
+/// The tools are `review_diff`, `review_staged` and `review_commit`. Each answers with the
+/// review text or, when called with `structured`, with the findings as structured content too.
+pub struct McpServer {
+    reviewer: CodeReviewTool,
+    max_files_context: usize,
+}
+
+/// A tool call that couldn't be made, and whose fault that is.
+enum CallError {
+    /// The arguments don't fit the tool, which is a protocol error
+    InvalidParams(String),
+    /// The review failed, which the tool result reports to the model
+    Failed(String),
+}
+
+impl From<Box<dyn Error>> for CallError {
+    fn from(e: Box<dyn Error>) -> Self {
+        CallError::Failed(e.to_string())
+    }
+}
+
+impl McpServer {
+    /// A server reviewing with `reviewer`, which should not show progress on stderr.
+    pub fn new(reviewer: CodeReviewTool) -> Self {
+        McpServer {
+            reviewer,
+            max_files_context: crate::default_max_files_context(),
+        }
+    }
+
+    /// Sends at most `max_files_context` changed files along with a review of staged changes.
+    pub fn with_max_files_context(mut self, max_files_context: usize) -> Self {
+        self.max_files_context = max_files_context;
+        self
+    }
+
+    /// Answers the messages on `input` until it ends. Requests are handled one at a time, in
+    /// the order they come.
+    pub async fn serve(&self, input: impl AsyncBufRead + Unpin, mut output: impl AsyncWrite + Unpin) -> io::Result<()> {
+        let mut lines = input.lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line).await {
+                output.write_all(format!("{}\n", response).as_bytes()).await?;
+                output.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The response to one JSON-RPC message, or `None` for a notification.
+    pub async fn handle(&self, message: &str) -> Option<Value> {
+        let message: Value = match serde_json::from_str(message) {
+            Ok(message) => message,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, &format!("invalid JSON: {}", e))),
+        };
+        let Some(method) = message["method"].as_str() else {
+            let id = message.get("id").cloned().unwrap_or(Value::Null);
+            return Some(error_response(id, INVALID_REQUEST, "the message has no method"));
+        };
+        // Notifications, like notifications/initialized, have no id and get no answer
+        let id = message.get("id")?.clone();
+        let params = &message["params"];
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "code_reviewer", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools() })),
+            "tools/call" => self.call(params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    /// Runs the tool `params` names. Failed reviews are tool results with `isError`, so the
+    /// model calling the tool sees what went wrong.
+    async fn call(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params["name"].as_str().unwrap_or_default();
+        let arguments = &params["arguments"];
+        let structured = arguments["structured"].as_bool().unwrap_or(false);
+        let reviewed = match name {
+            "review_diff" => match arguments["diff"].as_str() {
+                Some(diff) => self.review(&self.reviewer, diff.to_string(), Vec::new(), structured).await,
+                None => Err(CallError::InvalidParams("review_diff needs a diff".to_string())),
+            },
+            "review_staged" => self.review_staged(arguments, structured).await,
+            "review_commit" => self.review_commit(arguments, structured).await,
+            _ => return Err((INVALID_PARAMS, format!("unknown tool {}", name))),
+        };
+        match reviewed {
+            Ok(result) => Ok(result),
+            Err(CallError::InvalidParams(message)) => Err((INVALID_PARAMS, message)),
+            Err(CallError::Failed(message)) => Ok(json!({
+                "content": [{ "type": "text", "text": message }],
+                "isError": true,
+            })),
+        }
+    }
+
+    async fn review_staged(&self, arguments: &Value, structured: bool) -> Result<Value, CallError> {
+        let reviewer = self.reviewer_for(arguments)?;
+        let diff = reviewer.get_diff(&DiffSource::Staged, &[]).await?;
+        // Staged files are read from the working tree, which is close enough for context
+        let changed: Vec<String> = diff::parse(&diff)
+            .into_iter()
+            .filter(|file| !file.is_deleted() && !file.binary)
+            .map(|file| file.path)
+            .collect();
+        self.review(&reviewer, diff, changed, structured).await
+    }
+
+    async fn review_commit(&self, arguments: &Value, structured: bool) -> Result<Value, CallError> {
+        let Some(rev) = arguments["sha"].as_str() else {
+            return Err(CallError::InvalidParams("review_commit needs a sha".to_string()));
+        };
+        let reviewer = self.reviewer_for(arguments)?;
+        let repository = repository(arguments);
+        let commit = git::resolve_commit(repository, rev).ok_or_else(|| CallError::Failed(format!("{} is not a commit", rev)))?;
+        let reviewer = reviewer.with_diff_description(format!(
+            "These changes are a single commit. Check that they do what its message says they do:\n\n{}",
+            git::commit_description(repository, &commit)?
+        ));
+        let diff = reviewer.get_diff(&DiffSource::Commit(commit), &[]).await?;
+        self.review(&reviewer, diff, Vec::new(), structured).await
+    }
+
+    /// The reviewer for the repository the `path` argument names, or for the one the server
+    /// was started in. Each call names its own, so nothing carries over between calls.
+    fn reviewer_for(&self, arguments: &Value) -> Result<CodeReviewTool, CallError> {
+        let repository = repository(arguments);
+        if let Some(path) = repository {
+            if !path.is_dir() {
+                return Err(CallError::Failed(format!("could not open {}: not a directory", path.display())));
+            }
+        }
+        if !git::inside_repository(repository) {
+            return Err(CallError::Failed("not inside a git repository; pass the repository as path".to_string()));
+        }
+        Ok(match repository {
+            Some(path) => self.reviewer.clone().with_repository(path.to_path_buf()),
+            None => self.reviewer.clone(),
+        })
+    }
+
+    /// Reviews `diff` with the files at `context` as context, as a tool result.
+    async fn review(
+        &self,
+        reviewer: &CodeReviewTool,
+        diff: String,
+        context: Vec<String>,
+        structured: bool,
+    ) -> Result<Value, CallError> {
+        if diff.trim().is_empty() {
+            return Ok(json!({ "content": [{ "type": "text", "text": "No changes to review." }] }));
+        }
+        let context = reviewer.load_files(&context);
+        if !structured {
+            let review = reviewer.review_changes(diff, context, self.max_files_context).await?;
+            return Ok(json!({ "content": [{ "type": "text", "text": review }] }));
+        }
+        let (review, findings) = reviewer.review_structured(&diff, context, self.max_files_context, None).await?;
+        Ok(json!({
+            "content": [{ "type": "text", "text": review }],
+            "structuredContent": { "findings": findings },
+        }))
+    }
+}
+
+/// The repository the `path` argument names, if any.
+fn repository(arguments: &Value) -> Option<&Path> {
+    arguments["path"].as_str().map(Path::new)
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// The tools advertised by `tools/list`, with the JSON schemas of their arguments.
+fn tools() -> Value {
+    let structured = json!({
+        "type": "boolean",
+        "description": "Also return the findings as structured content, each with file, line, severity, category and message",
+    });
+    let path = json!({
+        "type": "string",
+        "description": "Path of the git repository; defaults to the directory the server was started in",
+    });
+    json!([
+        {
+            "name": "review_diff",
+            "description": "Review a unified diff and return the review.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "diff": { "type": "string", "description": "The changes to review, as a unified diff" },
+                    "structured": structured,
+                },
+                "required": ["diff"],
+            },
+        },
+        {
+            "name": "review_staged",
+            "description": "Review the changes staged in a git repository, with the changed files as context.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "path": path, "structured": structured },
+            },
+        },
+        {
+            "name": "review_commit",
+            "description": "Review a commit against its message.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "sha": { "type": "string", "description": "The commit, as a hash or any name git accepts" },
+                    "path": path,
+                    "structured": structured,
+                },
+                "required": ["sha"],
+            },
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::backend::mock::MockBackend;
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n";
+
+    fn mcp_server(response: &str) -> McpServer {
+        McpServer::new(CodeReviewTool::new(Arc::new(MockBackend::with_response(response)), None).with_progress(false))
+    }
+
+    async fn request(server: &McpServer, method: &str, params: Value) -> Value {
+        let message = json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params });
+        server.handle(&message.to_string()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn initializes_and_lists_the_tools() {
+        let server = mcp_server("Looks fine.");
+        let response = request(&server, "initialize", json!({ "protocolVersion": "2025-03-26" })).await;
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(response["result"]["serverInfo"]["name"], "code_reviewer");
+
+        let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert_eq!(server.handle(&initialized.to_string()).await, None);
+
+        let response = request(&server, "tools/list", json!({})).await;
+        let names: Vec<&str> = response["result"]["tools"].as_array().unwrap().iter().map(|tool| tool["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["review_diff", "review_staged", "review_commit"]);
+    }
+
+    #[tokio::test]
+    async fn reviews_a_diff() {
+        let server = mcp_server("- `src/lib.rs:1` unwrap panics (error)");
+        let response = request(&server, "tools/call", json!({ "name": "review_diff", "arguments": { "diff": DIFF } })).await;
+        assert_eq!(response["result"]["content"][0]["text"], "- `src/lib.rs:1` unwrap panics (error)");
+        assert!(response["result"].get("structuredContent").is_none());
+
+        let findings = r#"{"findings": [{"file": "src/lib.rs", "line": 1, "severity": "error", "category": "bugs", "message": "unwrap panics"}]}"#;
+        let structured = mcp_server(findings);
+        let arguments = json!({ "diff": DIFF, "structured": true });
+        let response = request(&structured, "tools/call", json!({ "name": "review_diff", "arguments": arguments })).await;
+        assert_eq!(response["result"]["structuredContent"]["findings"][0]["message"], "unwrap panics");
+    }
+
+    #[tokio::test]
+    async fn errors_map_to_json_rpc_codes() {
+        let server = mcp_server("Looks fine.");
+        assert_eq!(server.handle("{not json").await.unwrap()["error"]["code"], PARSE_ERROR);
+        assert_eq!(request(&server, "resources/list", json!({})).await["error"]["code"], METHOD_NOT_FOUND);
+        let unknown = request(&server, "tools/call", json!({ "name": "format_code", "arguments": {} })).await;
+        assert_eq!(unknown["error"]["code"], INVALID_PARAMS);
+        let missing = request(&server, "tools/call", json!({ "name": "review_diff", "arguments": {} })).await;
+        assert_eq!(missing["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn failed_reviews_are_tool_errors() {
+        let server = McpServer::new(CodeReviewTool::new(Arc::new(MockBackend::new(Vec::new())), None).with_progress(false));
+        let response = request(&server, "tools/call", json!({ "name": "review_diff", "arguments": { "diff": DIFF } })).await;
+        assert_eq!(response["result"]["isError"], true);
+        assert_eq!(response["result"]["content"][0]["text"], "the mock backend has no responses");
+    }
+
+    /// Runs git in `dir`, with an identity to commit as.
+    fn git_in(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[tokio::test]
+    async fn each_call_reviews_the_repository_it_names() {
+        let repository = tempfile::tempdir().unwrap();
+        git_in(repository.path(), &["init", "--quiet"]);
+        std::fs::write(repository.path().join("a.rs"), "fn a() {}\n").unwrap();
+        git_in(repository.path(), &["add", "a.rs"]);
+        git_in(repository.path(), &["commit", "--quiet", "--message", "Add a"]);
+        let sha = git_in(repository.path(), &["rev-parse", "HEAD"]).trim().to_string();
+        std::fs::write(repository.path().join("b.rs"), "fn b() {}\n").unwrap();
+        git_in(repository.path(), &["add", "b.rs"]);
+        let path = repository.path().to_str().unwrap();
+        let server = mcp_server("Looks fine.");
+        let current_dir = std::env::current_dir().unwrap();
+
+        let staged = request(&server, "tools/call", json!({ "name": "review_staged", "arguments": { "path": path } })).await;
+        assert_eq!(staged["result"]["content"][0]["text"], "Looks fine.");
+        let commit = json!({ "name": "review_commit", "arguments": { "sha": sha, "path": path } });
+        assert_eq!(request(&server, "tools/call", commit).await["result"]["content"][0]["text"], "Looks fine.");
+
+        // Without a path, the commit is looked for where the server runs, not where the
+        // last call was
+        let commit = json!({ "name": "review_commit", "arguments": { "sha": sha } });
+        assert_eq!(request(&server, "tools/call", commit).await["result"]["isError"], true);
+        assert_eq!(std::env::current_dir().unwrap(), current_dir);
+    }
+
+    #[tokio::test]
+    async fn serves_one_line_per_response() {
+        let server = mcp_server("Looks fine.");
+        let input = format!(
+            "{}\n\n{}\n",
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" }),
+        );
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":{}}\n");
+    }
+}
@@ -0,0 +1,54 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use terminal_size::{terminal_size, Height};
+
+/// Writes `text` to stdout, through the pager when `use_pager` is set, stdout is a
+/// terminal and the text doesn't fit on the screen. As with git, the pager is `$PAGER`,
+/// defaulting to `less -R` so colors survive, and `LESS=FRX` is set unless `LESS` already
+/// is. A reader closing the pipe early (quitting the pager, `| head`) is not an error.
+pub fn write_output(text: &str, use_pager: bool) -> io::Result<()> {
+    let result = match pager_command(text, use_pager) {
+        Some(command) => page(&command, text),
+        None => io::stdout().lock().write_all(text.as_bytes()),
+    };
+
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+fn pager_command(text: &str, use_pager: bool) -> Option<String> {
+    if !use_pager || !io::stdout().is_terminal() {
+        return None;
+    }
+    let (_, Height(rows)) = terminal_size()?;
+    if text.lines().count() < usize::from(rows) {
+        return None;
+    }
+
+    let command = env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    let command = command.trim();
+    (!command.is_empty() && command != "cat").then(|| command.to_string())
+}
+
+fn page(command: &str, text: &str) -> io::Result<()> {
+    let mut pager = Command::new("sh");
+    pager.args(["-c", command]).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        pager.env("LESS", "FRX");
+    }
+
+    let mut child = match pager.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warning!("Could not start pager {:?}: {}", command, e);
+            return io::stdout().lock().write_all(text.as_bytes());
+        }
+    };
+
+    let written = child.stdin.take().map_or(Ok(()), |mut stdin| stdin.write_all(text.as_bytes()));
+    child.wait()?;
+    written
+}
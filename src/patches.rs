@@ -0,0 +1,207 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use crate::git;
+
+// Lets `git apply` fix the line counts in hunk headers, which models rarely get right
+const APPLY_ARGS: [&str; 2] = ["--recount", "--whitespace=nowarn"];
+
+/// A unified diff the model proposed in a fenced code block of the review.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    /// Files the patch changes, by their new path
+    pub files: Vec<String>,
+    /// The diff, with `a/` and `b/` prefixes on its paths
+    pub diff: String,
+}
+
+/// Whether a patch fits the working tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Applies,
+    /// The working tree already has the change
+    AlreadyApplied,
+    /// A file is missing or its lines don't match the patch; git's explanation
+    Fails(String),
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Applies => write!(f, "applies cleanly"),
+            Status::AlreadyApplied => write!(f, "already applied"),
+            Status::Fails(reason) => write!(f, "does not apply:\n{}", reason),
+        }
+    }
+}
+
+impl Patch {
+    /// Checks the patch against the files under `root` without changing them.
+    pub fn check(&self, root: &Path) -> Status {
+        match git::apply(root, &self.diff, &[&["--check"], &APPLY_ARGS[..]].concat()) {
+            Ok(()) => Status::Applies,
+            Err(_) if git::apply(root, &self.diff, &[&["--check", "--reverse"], &APPLY_ARGS[..]].concat()).is_ok() => {
+                Status::AlreadyApplied
+            }
+            Err(e) => Status::Fails(e.to_string()),
+        }
+    }
+
+    /// Applies the patch to the files under `root`, all of it or, on failure, none of it.
+    /// Nothing is staged.
+    pub fn apply(&self, root: &Path) -> Result<(), Box<dyn Error>> {
+        git::apply(root, &self.diff, &APPLY_ARGS)
+    }
+}
+
+/// The patches in fenced blocks of `review`: blocks marked `diff` or `patch`, and unmarked
+/// blocks that hold a diff. Blocks without `---`/`+++` file headers and a hunk can't be
+/// applied and are left out.
+pub fn extract(review: &str) -> Vec<Patch> {
+    let mut patches = Vec::new();
+    let mut block: Option<(&str, bool, Vec<&str>)> = None;
+
+    for line in review.lines() {
+        let trimmed = line.trim_start();
+        match &mut block {
+            None => {
+                if let Some(info) = trimmed.strip_prefix("```") {
+                    let language = info.split_whitespace().next().unwrap_or_default();
+                    let marked = matches!(language, "diff" | "patch" | "udiff");
+                    block = Some((&line[..line.len() - trimmed.len()], marked || language.is_empty(), Vec::new()));
+                }
+            }
+            Some((indent, candidate, lines)) => {
+                if trimmed.starts_with("```") {
+                    if *candidate {
+                        patches.extend(patch(lines));
+                    }
+                    block = None;
+                } else {
+                    lines.push(line.strip_prefix(*indent).unwrap_or(trimmed));
+                }
+            }
+        }
+    }
+
+    patches
+}
+
+/// `lines` as a patch `git apply` takes, or `None` when they aren't a diff.
+fn patch(lines: &[&str]) -> Option<Patch> {
+    let mut diff = String::new();
+    let mut files = Vec::new();
+    let mut in_hunk = false;
+    let mut hunks = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        let next = lines.get(index + 1).copied().unwrap_or_default();
+        if line.starts_with("--- ") && next.starts_with("+++ ") {
+            in_hunk = false;
+            diff.push_str(&header("--- ", "a/", line));
+        } else if line.starts_with("+++ ") && !in_hunk {
+            let path = header_path(line);
+            let old = diff.lines().last().map(header_path).unwrap_or_default();
+            let file = if path == "/dev/null" { old } else { path };
+            files.push(file.strip_prefix("a/").or_else(|| file.strip_prefix("b/")).unwrap_or(file).to_string());
+            diff.push_str(&header("+++ ", "b/", line));
+        } else if line.starts_with("@@") {
+            in_hunk = true;
+            hunks += 1;
+            diff.push_str(line);
+            diff.push('\n');
+        } else if in_hunk {
+            // Editors and models drop the space that starts an empty context line
+            diff.push_str(if line.is_empty() { " " } else { line });
+            diff.push('\n');
+        }
+        // Anything else, like `diff --git` and `index` lines or prose, is left out
+    }
+
+    (!files.is_empty() && hunks > 0).then_some(Patch { files, diff })
+}
+
+/// The path of a `---` or `+++` header line, without a trailing timestamp.
+fn header_path(line: &str) -> &str {
+    line[4..].split('\t').next().unwrap_or_default().trim()
+}
+
+/// A `---` or `+++` header line with the `a/` or `b/` prefix `git apply` expects.
+fn header(marker: &str, prefix: &str, line: &str) -> String {
+    let path = header_path(line);
+    if path == "/dev/null" || path.starts_with(prefix) {
+        format!("{}{}\n", marker, path)
+    } else {
+        format!("{}{}{}\n", marker, prefix, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+    use super::*;
+
+    const SOURCE: &str = "fn parse(input: &str) -> u32 {\n\n    input.parse().unwrap()\n}\n";
+
+    const REVIEW: &str = "\
+- `src/lib.rs:3` unwrap panics on bad input (error)
+
+  ```diff
+  --- src/lib.rs
+  +++ src/lib.rs
+  @@ -1,4 +1,4 @@
+   fn parse(input: &str) -> u32 {
+
+  -    input.parse().unwrap()
+  +    input.parse().unwrap_or(0)
+   }
+  ```
+
+```rust
+let x = 1;
+```
+";
+
+    fn tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), SOURCE).unwrap();
+        dir
+    }
+
+    #[test]
+    fn extracts_diff_blocks() {
+        let patches = extract(REVIEW);
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].files, ["src/lib.rs"]);
+        assert!(patches[0].diff.starts_with("--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,4 +1,4 @@\n"), "{}", patches[0].diff);
+        assert!(patches[0].diff.contains("{\n \n-"), "{}", patches[0].diff);
+    }
+
+    #[test]
+    fn blocks_without_file_headers_are_left_out() {
+        assert!(extract("```diff\n@@ -1 +1 @@\n-a\n+b\n```\n").is_empty());
+        assert!(extract("```\nplain text\n```\n").is_empty());
+    }
+
+    #[test]
+    fn applies_once() {
+        let dir = tree();
+        let patch = &extract(REVIEW)[0];
+        assert_eq!(patch.check(dir.path()), Status::Applies);
+        patch.apply(dir.path()).unwrap();
+        assert!(fs::read_to_string(dir.path().join("src/lib.rs")).unwrap().contains("unwrap_or(0)"));
+        assert_eq!(patch.check(dir.path()), Status::AlreadyApplied);
+    }
+
+    #[test]
+    fn mismatched_patch_changes_nothing() {
+        let dir = tree();
+        fs::write(dir.path().join("src/lib.rs"), SOURCE.replace("input.parse()", "input.trim().parse()")).unwrap();
+        let patch = &extract(REVIEW)[0];
+        assert!(matches!(patch.check(dir.path()), Status::Fails(_)));
+        assert!(patch.apply(dir.path()).is_err());
+        assert!(fs::read_to_string(dir.path().join("src/lib.rs")).unwrap().contains("input.trim().parse().unwrap()"));
+    }
+}
@@ -0,0 +1,25 @@
+use std::time::Duration;
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// A spinner on stderr, or a hidden bar when progress is disabled. indicatif also stays
+/// silent on its own when stderr isn't a terminal.
+pub fn spinner(enabled: bool, message: impl Into<String>) -> ProgressBar {
+    ticking(enabled, "{spinner} {msg} ({elapsed})", message.into())
+}
+
+/// A spinner that also shows a running count, for work of unknown size.
+pub fn counter(enabled: bool, message: impl Into<String>) -> ProgressBar {
+    ticking(enabled, "{spinner} {msg}: {pos} files", message.into())
+}
+
+fn ticking(enabled: bool, template: &str, message: String) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+
+    let bar = ProgressBar::new_spinner();
+    bar.set_style(ProgressStyle::with_template(template).unwrap());
+    bar.set_message(message);
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+use reqwest::{ClientBuilder, NoProxy, Proxy};
+use crate::Settings;
+
+/// Proxies the model backends connect through, for networks that only reach remote servers
+/// that way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Proxies {
+    /// Proxy for `http://` URLs
+    pub http: Option<String>,
+    /// Proxy for `https://` URLs
+    pub https: Option<String>,
+    /// Hosts and domains reached directly, in `NO_PROXY` syntax
+    pub no_proxy: Vec<String>,
+}
+
+impl Proxies {
+    /// The proxies from the settings, each falling back to its environment variable:
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`, or their lowercase spellings.
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::resolve(settings, |name| std::env::var(name).ok())
+    }
+
+    fn resolve(settings: &Settings, env: impl Fn(&str) -> Option<String>) -> Self {
+        let env = |name: &str| {
+            env(name)
+                .or_else(|| env(&name.to_lowercase()))
+                .filter(|value| !value.trim().is_empty())
+        };
+        let no_proxy = if settings.no_proxy.is_empty() {
+            env("NO_PROXY")
+                .map(|hosts| hosts.split(',').map(|host| host.trim().to_string()).filter(|host| !host.is_empty()).collect())
+                .unwrap_or_default()
+        } else {
+            settings.no_proxy.clone()
+        };
+
+        Proxies {
+            http: settings.http_proxy.clone().or_else(|| env("HTTP_PROXY")),
+            https: settings.https_proxy.clone().or_else(|| env("HTTPS_PROXY")),
+            no_proxy,
+        }
+    }
+
+    /// Routes the requests of `builder` through the proxies. Without any, the builder is
+    /// returned as it was.
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder, Box<dyn Error>> {
+        let no_proxy = || NoProxy::from_string(&self.no_proxy.join(","));
+        if let Some(url) = &self.http {
+            let proxy = Proxy::http(url).map_err(|e| format!("invalid http_proxy {}: {}", url, e))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy()));
+        }
+        if let Some(url) = &self.https {
+            let proxy = Proxy::https(url).map_err(|e| format!("invalid https_proxy {}: {}", url, e))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy()));
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    fn resolve(settings: &Settings, env: &[(&str, &str)]) -> Proxies {
+        let env: HashMap<String, String> = env.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        Proxies::resolve(settings, |name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn settings_take_precedence_over_the_environment() {
+        let settings = Settings {
+            https_proxy: Some("http://proxy.corp:3128".to_string()),
+            no_proxy: vec!["localhost".to_string()],
+            ..Settings::default()
+        };
+        let proxies = resolve(&settings, &[("HTTPS_PROXY", "http://other:8080"), ("NO_PROXY", "example.com")]);
+        assert_eq!(proxies.https.as_deref(), Some("http://proxy.corp:3128"));
+        assert_eq!(proxies.no_proxy, ["localhost"]);
+    }
+
+    #[test]
+    fn environment_fills_in_what_the_settings_leave_out() {
+        let proxies = resolve(
+            &Settings::default(),
+            &[("http_proxy", "http://proxy.corp:3128"), ("HTTPS_PROXY", ""), ("NO_PROXY", "localhost, .corp ,")],
+        );
+        assert_eq!(proxies.http.as_deref(), Some("http://proxy.corp:3128"));
+        assert_eq!(proxies.https, None);
+        assert_eq!(proxies.no_proxy, ["localhost", ".corp"]);
+    }
+
+    #[test]
+    fn invalid_proxy_url_is_an_error() {
+        let proxies = Proxies { http: Some("http://proxy corp".to_string()), ..Proxies::default() };
+        assert!(proxies.apply(reqwest::Client::builder()).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::IsTerminal;
+use std::sync::LazyLock;
+use owo_colors::{Effect, OwoColorize, Style};
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use regex::{Captures, Regex};
+use crate::findings::Finding;
+
+// File paths (optionally with :line[:col]) and severity keywords, matched in a single pass
+// so that highlighting one never mangles the escape codes of the other.
+static HIGHLIGHT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+        (?P<path>(?:[\w.-]+/)*[\w.-]+\.(?:rs|py|js|jsx|ts|tsx|go|java|kt|c|h|cc|cpp|hpp|cs|rb|php|swift|scala|sh|toml|ya?ml|json|md|html|css|sql)(?::\d+(?::\d+)?)?\b)
+        |
+        (?P<keyword>(?i)\b(?:critical|high|error|bug|vulnerability|warning|medium|low|info|suggestion|note)\b)
+        ",
+    )
+    .unwrap()
+});
+
+/// Decides whether the review should be colorized: only when stdout is a terminal and
+/// neither `--no-color` nor a non-empty `NO_COLOR` environment variable is set.
+pub fn color_enabled(no_color: bool) -> bool {
+    if no_color {
+        return false;
+    }
+    if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    std::io::stdout().is_terminal()
+}
+
+// Tokens for the basic highlighting of fenced code: comments, strings, numbers and
+// keywords common to most languages.
+static CODE_TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?x)
+        (?P<comment>//.*|\#.*|--\s.*)
+        |
+        (?P<string>"(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*')
+        |
+        (?P<number>\b\d+(?:\.\d+)?\b)
+        |
+        (?P<keyword>\b(?:fn|let|mut|const|static|struct|enum|impl|trait|pub|use|mod|match|if|else|for|while|loop|return|async|await|def|class|import|from|function|var|func|package|public|private|protected|new|try|catch|except|finally|raise|throw|null|nil|None|true|false|True|False|self|this|SELECT|FROM|WHERE|INSERT|UPDATE|DELETE)\b)
+        "#,
+    )
+    .unwrap()
+});
+
+/// Renders the review's markdown for the terminal: styled headings, emphasis and lists,
+/// highlighted code fences, and file paths and severity keywords picked out in plain text.
+pub fn render_markdown(review: &str) -> String {
+    let mut out = String::with_capacity(review.len());
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut code_block: Option<String> = None;
+    let mut code = String::new();
+    let mut link: Option<String> = None;
+    let mut style = Style::new();
+    let mut heading = false;
+
+    for event in Parser::new_ext(review, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => heading = true,
+            Event::End(TagEnd::Heading(_)) => {
+                heading = false;
+                out.push_str("\n\n");
+            }
+            Event::End(TagEnd::Paragraph) => {
+                out.push('\n');
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() && !out.ends_with('\n') {
+                    out.push('\n');
+                }
+                lists.push(start);
+            }
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                let marker = match lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}.", *number - 1)
+                    }
+                    _ => "•".to_string(),
+                };
+                out.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                out.push_str(&format!("{} ", marker.cyan()));
+            }
+            Event::End(TagEnd::Item) if !out.ends_with('\n') => out.push('\n'),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                code_block = Some(match kind {
+                    CodeBlockKind::Fenced(language) => language.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                });
+                code.clear();
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                let language = code_block.take().unwrap_or_default();
+                let indent = "  ".repeat(lists.len() + 1);
+                for line in code.lines() {
+                    out.push_str(&indent);
+                    out.push_str(&highlight_code(line, &language));
+                    out.push('\n');
+                }
+                if lists.is_empty() {
+                    out.push('\n');
+                }
+            }
+            Event::Start(Tag::Strong) => style = style.bold(),
+            Event::End(TagEnd::Strong) => style = style.remove_effect(Effect::Bold),
+            Event::Start(Tag::Emphasis) => style = style.italic(),
+            Event::End(TagEnd::Emphasis) => style = style.remove_effect(Effect::Italic),
+            Event::Start(Tag::Strikethrough) => style = style.strikethrough(),
+            Event::End(TagEnd::Strikethrough) => style = style.remove_effect(Effect::Strikethrough),
+            Event::Start(Tag::Link { dest_url, .. }) => link = Some(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = link.take() {
+                    out.push_str(&format!(" <{}>", url).dimmed().to_string());
+                }
+            }
+            Event::Text(text) if code_block.is_some() => code.push_str(&text),
+            Event::Text(text) if heading => out.push_str(&text.bold().magenta().to_string()),
+            Event::Text(text) if style.is_plain() => out.push_str(&highlight_text(&text)),
+            Event::Text(text) => out.push_str(&text.style(style).to_string()),
+            Event::Code(text) => out.push_str(&text.yellow().to_string()),
+            Event::Html(html) | Event::InlineHtml(html) => out.push_str(&html),
+            Event::SoftBreak | Event::HardBreak => {
+                out.push('\n');
+                out.push_str(&"  ".repeat(lists.len()));
+            }
+            Event::Rule => out.push_str(&format!("{}\n\n", "─".repeat(40).dimmed())),
+            _ => {}
+        }
+    }
+
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    out.push('\n');
+    out
+}
+
+/// Highlights file paths and severity keywords.
+fn highlight_text(text: &str) -> String {
+    HIGHLIGHT
+        .replace_all(text, |caps: &Captures| {
+            if let Some(path) = caps.name("path") {
+                path.as_str().blue().underline().to_string()
+            } else {
+                paint_keyword(&caps[0])
+            }
+        })
+        .into_owned()
+}
+
+/// Colors one line of a fenced code block. Diffs get added and removed lines colored;
+/// other blocks with a language hint get keyword, string, number and comment colors.
+fn highlight_code(line: &str, language: &str) -> String {
+    match language {
+        "" => line.to_string(),
+        "diff" | "patch" => {
+            if line.starts_with('+') {
+                line.green().to_string()
+            } else if line.starts_with('-') {
+                line.red().to_string()
+            } else if line.starts_with("@@") {
+                line.cyan().to_string()
+            } else {
+                line.to_string()
+            }
+        }
+        _ => CODE_TOKEN
+            .replace_all(line, |caps: &Captures| {
+                let token = &caps[0];
+                if caps.name("comment").is_some() {
+                    token.dimmed().to_string()
+                } else if caps.name("string").is_some() {
+                    token.green().to_string()
+                } else if caps.name("number").is_some() {
+                    token.cyan().to_string()
+                } else {
+                    token.blue().bold().to_string()
+                }
+            })
+            .into_owned(),
+    }
+}
+
+fn paint_keyword(word: &str) -> String {
+    match word.to_ascii_lowercase().as_str() {
+        "critical" | "high" | "error" | "bug" | "vulnerability" => word.red().bold().to_string(),
+        "warning" | "medium" => word.yellow().bold().to_string(),
+        _ => word.cyan().to_string(),
+    }
+}
+
+/// Lists findings one per line, for when only some of the review's findings are shown.
+pub fn findings_text(findings: &[Finding]) -> String {
+    let mut out = String::new();
+
+    for finding in findings {
+        out.push_str(&format!("- {}: ", finding.severity));
+        if let Some(file) = &finding.file {
+            match finding.line {
+                Some(line) => out.push_str(&format!("{}:{}: ", file, line)),
+                None => out.push_str(&format!("{}: ", file)),
+            }
+        }
+        out.push_str(&finding.message.replace('\n', "\n  "));
+        out.push('\n');
+    }
+
+    out
+}
@@ -0,0 +1,168 @@
+use std::error::Error;
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder};
+use serde::Serialize;
+use crate::diff;
+use crate::ReviewResult;
+use super::github::PullRequestRef;
+use super::Reporter;
+
+/// How to authenticate with Bitbucket Cloud.
+#[derive(Debug, Clone)]
+pub enum BitbucketAuth {
+    /// A repository, project or workspace access token
+    AccessToken(String),
+    /// A username and app password
+    AppPassword { username: String, password: String },
+}
+
+impl BitbucketAuth {
+    /// Reads `BITBUCKET_TOKEN`, or else `BITBUCKET_USERNAME` and `BITBUCKET_APP_PASSWORD`.
+    pub fn from_env() -> Option<Self> {
+        if let Ok(token) = std::env::var("BITBUCKET_TOKEN") {
+            return Some(BitbucketAuth::AccessToken(token));
+        }
+        Some(BitbucketAuth::AppPassword {
+            username: std::env::var("BITBUCKET_USERNAME").ok()?,
+            password: std::env::var("BITBUCKET_APP_PASSWORD").ok()?,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct Comment {
+    content: Content,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inline: Option<Inline>,
+}
+
+#[derive(Serialize)]
+struct Content {
+    raw: String,
+}
+
+/// Places a comment on line `to` of the new version of `path`.
+#[derive(Serialize)]
+struct Inline {
+    path: String,
+    to: u32,
+}
+
+/// Posts reviews to a Bitbucket Cloud pull request through the 2.0 API.
+pub struct BitbucketReporter {
+    api_url: String,
+    auth: BitbucketAuth,
+    pull_request: PullRequestRef,
+    client: Client,
+}
+
+impl BitbucketReporter {
+    pub fn new(pull_request: PullRequestRef, auth: BitbucketAuth) -> Self {
+        BitbucketReporter {
+            api_url: std::env::var("BITBUCKET_API_URL")
+                .unwrap_or_else(|_| "https://api.bitbucket.org/2.0".to_string()),
+            auth,
+            pull_request,
+            client: Client::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let pull_request = &self.pull_request;
+        let request = self.client.request(
+            method,
+            format!(
+                "{}/repositories/{}/{}/pullrequests/{}/{}",
+                self.api_url, pull_request.owner, pull_request.repo, pull_request.number, path
+            ),
+        );
+        match &self.auth {
+            BitbucketAuth::AccessToken(token) => request.bearer_auth(token),
+            BitbucketAuth::AppPassword { username, password } => request.basic_auth(username, Some(password)),
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, Box<dyn Error>> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await?;
+            return Err(format!("Bitbucket API returned {} for {}: {}", status, self.pull_request, text).into());
+        }
+        Ok(response)
+    }
+
+    async fn post_comment(&self, raw: String, inline: Option<Inline>) -> Result<(), Box<dyn Error>> {
+        let comment = Comment {
+            content: Content { raw },
+            inline,
+        };
+        self.send(self.request(Method::POST, "comments").json(&comment)).await?;
+        Ok(())
+    }
+}
+
+/// Adapts GitHub-flavoured markdown from the model to Bitbucket's renderer, which has no
+/// task lists, only knows backtick fences and needs a blank line before a fence.
+fn bitbucket_markdown(text: &str) -> String {
+    const TASK_MARKERS: [&str; 6] = ["- [ ] ", "- [x] ", "- [X] ", "* [ ] ", "* [x] ", "* [X] "];
+
+    let mut out = String::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let task = TASK_MARKERS.iter().find_map(|marker| trimmed.strip_prefix(marker));
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            if !in_fence && !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            in_fence = !in_fence;
+            out.push_str(&format!("{}```{}", indent, &trimmed[3..]));
+        } else if let Some(rest) = task.filter(|_| !in_fence) {
+            out.push_str(&format!("{}- {}", indent, rest));
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[async_trait]
+impl Reporter for BitbucketReporter {
+    /// Posts the full review as a pull request comment, then an inline comment for each
+    /// finding on a line of the pull request's diff.
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let mut body = format!("## Code Review\n\n{}", bitbucket_markdown(&result.review).trim_end());
+        if let Some(score) = &result.score {
+            body.push_str(&format!("\n\n**{}**", score));
+        }
+        self.post_comment(body, None).await?;
+
+        if !result.findings.iter().any(|finding| finding.file.is_some() && finding.line.is_some()) {
+            return Ok(());
+        }
+        let response = self.send(self.request(Method::GET, "diff")).await?;
+        let diff = response.text().await?;
+        let commentable = diff::commentable_lines(&diff);
+
+        for finding in &result.findings {
+            let (Some(path), Some(line)) = (&finding.file, finding.line) else {
+                continue;
+            };
+            // Findings outside the diff are only in the review comment
+            if !commentable.get(path).is_some_and(|lines| lines.contains(&line)) {
+                continue;
+            }
+            let inline = Inline {
+                path: path.clone(),
+                to: line,
+            };
+            self.post_comment(bitbucket_markdown(&finding.message).trim_end().to_string(), Some(inline)).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use async_trait::async_trait;
+use crate::formats::{self, OutputFormat, RenderOptions};
+use crate::ReviewResult;
+use super::Reporter;
+
+/// Writes the review to a file in the selected format.
+pub struct FileReporter {
+    path: PathBuf,
+    format: OutputFormat,
+    options: RenderOptions,
+    append: bool,
+}
+
+impl FileReporter {
+    pub fn new(path: PathBuf, format: OutputFormat, options: RenderOptions) -> Self {
+        FileReporter { path, format, options, append: false }
+    }
+
+    /// Adds to the end of an existing file instead of replacing it.
+    pub fn with_append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+}
+
+#[async_trait]
+impl Reporter for FileReporter {
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let mut contents = formats::render(self.format, result, &self.options)?;
+        if self.append {
+            match fs::read_to_string(&self.path) {
+                Ok(existing) => contents.insert_str(0, &existing),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("could not read {}: {}", self.path.display(), e).into()),
+            }
+        }
+
+        write_atomically(&self.path, contents.as_bytes())
+            .map_err(|e| format!("could not write {}: {}", self.path.display(), e))?;
+        if !crate::quiet() {
+            eprintln!("Wrote review to {}", self.path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it into place, so readers never
+/// see a half-written review. Missing parent directories are created.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let temp = dir.join(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+
+    let written = fs::write(&temp, contents).and_then(|()| fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
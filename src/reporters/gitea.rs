@@ -0,0 +1,163 @@
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use crate::diff;
+use crate::findings::Finding;
+use crate::ReviewResult;
+use super::github::{PullRequestRef, MARKER};
+use super::Reporter;
+
+#[derive(Serialize)]
+struct CommentRequest {
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct IssueComment {
+    id: u64,
+    #[serde(default)]
+    body: String,
+}
+
+#[derive(Serialize)]
+struct ReviewRequest {
+    body: String,
+    event: &'static str,
+    comments: Vec<ReviewComment>,
+}
+
+/// A line comment; Gitea counts `new_position` in lines of the new file, and
+/// `old_position` 0 means the comment isn't on a removed line.
+#[derive(Serialize)]
+struct ReviewComment {
+    path: String,
+    new_position: u32,
+    old_position: u32,
+    body: String,
+}
+
+/// Posts reviews to a Gitea or Forgejo pull request through the REST API.
+pub struct GiteaReporter {
+    api_url: String,
+    token: String,
+    pull_request: PullRequestRef,
+    client: Client,
+}
+
+impl GiteaReporter {
+    /// `base_url` is the instance's web address, e.g. `https://git.example.com`.
+    pub fn new(base_url: &str, pull_request: PullRequestRef, token: String) -> Self {
+        GiteaReporter {
+            api_url: format!("{}/api/v1", base_url.trim_end_matches('/')),
+            token,
+            pull_request,
+            client: Client::new(),
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let pull_request = &self.pull_request;
+        self.client
+            .request(method, format!("{}/repos/{}/{}/{}", self.api_url, pull_request.owner, pull_request.repo, path))
+            .header("Authorization", format!("token {}", self.token))
+            .header("Accept", "application/json")
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, Box<dyn Error>> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await?;
+            return Err(format!("Gitea API returned {} for {}: {}", status, self.pull_request, text).into());
+        }
+        Ok(response)
+    }
+
+    /// Fetches the pull request's diff, to know which lines line comments can go on.
+    async fn commentable_lines(&self) -> Result<HashMap<String, BTreeSet<u32>>, Box<dyn Error>> {
+        let path = format!("pulls/{}.diff", self.pull_request.number);
+        let response = self.send(self.request(Method::GET, &path)).await?;
+        let diff = response.text().await?;
+        Ok(diff::commentable_lines(&diff))
+    }
+
+    /// Finds the summary comment posted by an earlier run, looking through every page of comments.
+    async fn find_summary_comment(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        for page in 1.. {
+            let path = format!("issues/{}/comments?limit=50&page={}", self.pull_request.number, page);
+            let response = self.send(self.request(Method::GET, &path)).await?;
+            let comments: Vec<IssueComment> = response.json().await?;
+            if let Some(comment) = comments.iter().find(|comment| comment.body.contains(MARKER)) {
+                return Ok(Some(comment.id));
+            }
+            if comments.is_empty() {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Posts the full review as an issue comment, replacing the one from an earlier run.
+    async fn upsert_summary_comment(&self, body: String) -> Result<(), Box<dyn Error>> {
+        let request = match self.find_summary_comment().await? {
+            Some(id) => self.request(Method::PATCH, &format!("issues/comments/{}", id)),
+            None => self.request(Method::POST, &format!("issues/{}/comments", self.pull_request.number)),
+        };
+        self.send(request.json(&CommentRequest { body })).await?;
+        Ok(())
+    }
+}
+
+/// Places a finding on the first of its lines that is inside the pull request's diff.
+fn review_comment(finding: &Finding, commentable: &HashMap<String, BTreeSet<u32>>) -> Option<ReviewComment> {
+    let path = finding.file.as_deref()?;
+    let lines = commentable.get(path)?;
+    let start = finding.line?;
+    let end = finding.end_line.filter(|end| *end > start).unwrap_or(start);
+    let line = *lines.range(start..=end).next()?;
+
+    Some(ReviewComment {
+        path: path.to_string(),
+        new_position: line,
+        old_position: 0,
+        body: finding.message.clone(),
+    })
+}
+
+#[async_trait]
+impl Reporter for GiteaReporter {
+    /// Posts or updates the summary comment with the full review, then creates a pull
+    /// request review with a line comment for each finding on a line of the diff.
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let mut summary = format!("{}\n## Code Review\n\n{}", MARKER, result.review.trim_end());
+        if let Some(score) = &result.score {
+            summary.push_str(&format!("\n\n**{}**", score));
+        }
+        self.upsert_summary_comment(summary).await?;
+
+        if !result.findings.iter().any(|finding| finding.file.is_some() && finding.line.is_some()) {
+            return Ok(());
+        }
+        let commentable = self.commentable_lines().await?;
+        let comments: Vec<ReviewComment> = result
+            .findings
+            .iter()
+            .filter_map(|finding| review_comment(finding, &commentable))
+            .collect();
+
+        if !comments.is_empty() {
+            let plural = if comments.len() == 1 { "finding" } else { "findings" };
+            let review = ReviewRequest {
+                body: format!("{} {} from code_reviewer, see the summary comment for the full review.", comments.len(), plural),
+                event: "COMMENT",
+                comments,
+            };
+            let path = format!("pulls/{}/reviews", self.pull_request.number);
+            self.send(self.request(Method::POST, &path).json(&review)).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use async_trait::async_trait;
+use reqwest::{Client, Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use crate::diff;
+use crate::findings::{Finding, Severity};
+use crate::ReviewResult;
+use super::Reporter;
+
+/// Hidden marker identifying the summary comment this tool posts, so later runs update it.
+pub(super) const MARKER: &str = "<!-- code_reviewer:summary -->";
+
+/// GitHub rejects comment bodies longer than this many characters.
+const MAX_COMMENT_CHARS: usize = 65536;
+
+/// A pull request given on the command line as `owner/repo#number`, or as a bare number
+/// whose repository is filled in from the origin remote.
+#[derive(Debug, Clone)]
+pub struct PullRequestRef {
+    pub owner: String,
+    pub repo: String,
+    pub number: u64,
+}
+
+impl FromStr for PullRequestRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected owner/repo#number or a PR number, got '{}'", s);
+        if let Ok(number) = s.trim_start_matches('#').parse() {
+            return Ok(PullRequestRef {
+                owner: String::new(),
+                repo: String::new(),
+                number,
+            });
+        }
+        let (repo_path, number) = s.split_once('#').ok_or_else(invalid)?;
+        let (owner, repo) = repo_path.split_once('/').ok_or_else(invalid)?;
+        if owner.is_empty() || repo.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(PullRequestRef {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            number: number.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl PullRequestRef {
+    /// Whether the repository still has to be filled in, because only a number was given.
+    pub fn needs_repository(&self) -> bool {
+        self.owner.is_empty()
+    }
+}
+
+impl fmt::Display for PullRequestRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}#{}", self.owner, self.repo, self.number)
+    }
+}
+
+#[derive(Serialize)]
+struct CommentRequest {
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct IssueComment {
+    id: u64,
+    #[serde(default)]
+    body: String,
+}
+
+#[derive(Serialize)]
+struct ReviewRequest {
+    body: String,
+    event: &'static str,
+    comments: Vec<ReviewComment>,
+}
+
+#[derive(Serialize)]
+struct ReviewComment {
+    path: String,
+    line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_side: Option<&'static str>,
+    side: &'static str,
+    body: String,
+}
+
+/// Posts reviews to a GitHub pull request through the REST API.
+pub struct GithubReporter {
+    api_url: String,
+    token: String,
+    pull_request: PullRequestRef,
+    client: Client,
+    dry_run: bool,
+    request_changes_at: Option<Severity>,
+}
+
+impl GithubReporter {
+    pub fn new(pull_request: PullRequestRef, token: String) -> Self {
+        GithubReporter {
+            api_url: std::env::var("GITHUB_API_URL")
+                .unwrap_or_else(|_| "https://api.github.com".to_string()),
+            token,
+            pull_request,
+            client: Client::new(),
+            dry_run: false,
+            request_changes_at: None,
+        }
+    }
+
+    /// Prints what would be posted instead of calling the API.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Requests changes, instead of only commenting, when a finding is at or above `severity`.
+    pub fn with_review_event(mut self, severity: Option<Severity>) -> Self {
+        self.request_changes_at = severity;
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.request_as(method, path, "application/vnd.github+json")
+    }
+
+    /// A request for `accept`, the media type GitHub should answer with.
+    fn request_as(&self, method: Method, path: &str, accept: &str) -> RequestBuilder {
+        let pull_request = &self.pull_request;
+        self.client
+            .request(method, format!("{}/repos/{}/{}/{}", self.api_url, pull_request.owner, pull_request.repo, path))
+            .bearer_auth(&self.token)
+            .header("Accept", accept)
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "code_reviewer")
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, Box<dyn Error>> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await?;
+            return Err(format!("GitHub API returned {} for {}: {}", status, self.pull_request, text).into());
+        }
+        Ok(response)
+    }
+
+    /// Fetches the pull request's diff, to know which lines inline comments can go on.
+    async fn commentable_hunks(&self) -> Result<HashMap<String, Vec<RangeInclusive<u32>>>, Box<dyn Error>> {
+        let path = format!("pulls/{}", self.pull_request.number);
+        let request = self.request_as(Method::GET, &path, "application/vnd.github.v3.diff");
+        let response = self.send(request).await?;
+        let diff = response.text().await?;
+        Ok(diff::commentable_hunks(&diff))
+    }
+
+    /// Finds the summary comment posted by an earlier run, looking through every page of comments.
+    async fn find_summary_comment(&self) -> Result<Option<u64>, Box<dyn Error>> {
+        for page in 1.. {
+            let path = format!("issues/{}/comments?per_page=100&page={}", self.pull_request.number, page);
+            let response = self.send(self.request(Method::GET, &path)).await?;
+            let comments: Vec<IssueComment> = response.json().await?;
+            if let Some(comment) = comments.iter().find(|comment| comment.body.contains(MARKER)) {
+                return Ok(Some(comment.id));
+            }
+            if comments.len() < 100 {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Posts the full review as an issue comment, replacing the one from an earlier run.
+    async fn upsert_summary_comment(&self, body: String) -> Result<(), Box<dyn Error>> {
+        let request = match self.find_summary_comment().await? {
+            Some(id) => self.request(Method::PATCH, &format!("issues/comments/{}", id)),
+            None => self.request(Method::POST, &format!("issues/{}/comments", self.pull_request.number)),
+        };
+        self.send(request.json(&CommentRequest { body })).await?;
+        Ok(())
+    }
+}
+
+/// Anchors a finding to lines GitHub accepts comments on, which must be inside one of the
+/// pull request's diff hunks. A range that doesn't fit in one hunk is cut to its last line,
+/// or to its first if only that is in the diff, as GitHub rejects the whole review otherwise.
+fn review_comment(finding: &Finding, commentable: &HashMap<String, Vec<RangeInclusive<u32>>>) -> Option<ReviewComment> {
+    let path = finding.file.as_deref()?;
+    let hunks = commentable.get(path)?;
+    let start = finding.line?;
+    let end = finding.end_line.filter(|end| *end > start).unwrap_or(start);
+
+    let hunk_of = |line: u32| hunks.iter().position(|hunk| hunk.contains(&line));
+    let (start_line, line) = match (hunk_of(start), hunk_of(end)) {
+        (Some(first), Some(last)) if first == last && end > start => (Some(start), end),
+        (_, Some(_)) => (None, end),
+        (Some(_), None) => (None, start),
+        (None, None) => return None,
+    };
+    Some(ReviewComment {
+        path: path.to_string(),
+        line,
+        start_line,
+        start_side: start_line.map(|_| "RIGHT"),
+        side: "RIGHT",
+        body: finding.message.clone(),
+    })
+}
+
+/// The review body: a pointer to the summary comment, followed by the findings that
+/// couldn't be placed on a line of the diff.
+fn review_body(total: usize, unanchored: &[&Finding]) -> String {
+    let plural = if total == 1 { "finding" } else { "findings" };
+    let mut body = format!("{} {} from code_reviewer, see the summary comment for the full review.", total, plural);
+    if !unanchored.is_empty() {
+        body.push_str("\n\nOutside the lines changed in this pull request:\n");
+        for finding in unanchored {
+            let location = match (&finding.file, finding.line) {
+                (Some(file), Some(line)) => format!("`{}:{}`: ", file, line),
+                (Some(file), None) => format!("`{}`: ", file),
+                _ => String::new(),
+            };
+            body.push_str(&format!("\n- {}{}", location, finding.message));
+        }
+    }
+    body
+}
+
+/// The summary comment: the marker, the review and the score, cut to GitHub's size limit.
+fn summary_comment(result: &ReviewResult) -> String {
+    let mut body = format!("{}\n## Code Review\n\n{}", MARKER, result.review.trim_end());
+    if let Some(score) = &result.score {
+        body.push_str(&format!("\n\n**{}**", score));
+    }
+
+    let note = "\n\n*The review was truncated to fit GitHub's comment size limit.*";
+    if body.chars().count() > MAX_COMMENT_CHARS {
+        let end = body
+            .char_indices()
+            .nth(MAX_COMMENT_CHARS - note.chars().count())
+            .map_or(body.len(), |(end, _)| end);
+        body.truncate(end);
+        body.push_str(note);
+    }
+    body
+}
+
+#[async_trait]
+impl Reporter for GithubReporter {
+    /// Posts or updates the summary comment with the full review, then creates a pull
+    /// request review with an inline comment for each finding on a line of the diff.
+    /// Findings with a location outside the diff are listed in the review body instead.
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let commentable = match self.commentable_hunks().await {
+            Ok(commentable) => commentable,
+            Err(e) if self.dry_run => {
+                warning!("Could not fetch the diff of {}: {}", self.pull_request, e);
+                HashMap::new()
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut comments = Vec::new();
+        let mut unanchored = Vec::new();
+        for finding in result.findings.iter().filter(|finding| finding.file.is_some()) {
+            match review_comment(finding, &commentable) {
+                Some(comment) => comments.push(comment),
+                None => unanchored.push(finding),
+            }
+        }
+        let request_changes = self
+            .request_changes_at
+            .is_some_and(|threshold| result.findings.iter().any(|finding| finding.severity >= threshold));
+        let event = if request_changes { "REQUEST_CHANGES" } else { "COMMENT" };
+        let summary = summary_comment(result);
+
+        if self.dry_run {
+            eprintln!("Would post this comment to {}:\n\n{}\n", self.pull_request, summary);
+            for comment in &comments {
+                eprintln!("Would comment on {}:{}: {}", comment.path, comment.line, comment.body);
+            }
+            for finding in &unanchored {
+                eprintln!("Would list outside the diff: {}", finding.message);
+            }
+            eprintln!("Would submit the review as {}", event);
+            return Ok(());
+        }
+
+        self.upsert_summary_comment(summary).await?;
+
+        if !comments.is_empty() || !unanchored.is_empty() || request_changes {
+            let review = ReviewRequest {
+                body: review_body(comments.len() + unanchored.len(), &unanchored),
+                event,
+                comments,
+            };
+            let path = format!("pulls/{}/reviews", self.pull_request.number);
+            self.send(self.request(Method::POST, &path).json(&review)).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::parse_findings;
+
+    fn comment(review: &str) -> Option<ReviewComment> {
+        let commentable = HashMap::from([("src/lib.rs".to_string(), vec![1..=5, 20..=30])]);
+        review_comment(&parse_findings(review)[0], &commentable)
+    }
+
+    #[test]
+    fn ranges_in_one_hunk_keep_their_start() {
+        let anchored = comment("- `src/lib.rs:2-4` unwrap panics (error)").unwrap();
+        assert_eq!((anchored.start_line, anchored.line, anchored.start_side), (Some(2), 4, Some("RIGHT")));
+        let anchored = comment("- `src/lib.rs:22` unwrap panics (error)").unwrap();
+        assert_eq!((anchored.start_line, anchored.line), (None, 22));
+    }
+
+    #[test]
+    fn ranges_spanning_hunks_are_cut_to_one_line() {
+        let anchored = comment("- `src/lib.rs:4-25` unwrap panics (error)").unwrap();
+        assert_eq!((anchored.start_line, anchored.line, anchored.start_side), (None, 25, None));
+        let anchored = comment("- `src/lib.rs:4-10` unwrap panics (error)").unwrap();
+        assert_eq!((anchored.start_line, anchored.line), (None, 4));
+        let anchored = comment("- `src/lib.rs:10-21` unwrap panics (error)").unwrap();
+        assert_eq!((anchored.start_line, anchored.line), (None, 21));
+    }
+
+    #[test]
+    fn findings_outside_the_diff_have_no_comment() {
+        assert!(comment("- `src/lib.rs:10-12` unwrap panics (error)").is_none());
+        assert!(comment("- `src/main.rs:2` unwrap panics (error)").is_none());
+        assert!(comment("- `src/lib.rs` has no tests (suggestion)").is_none());
+    }
+
+    #[test]
+    fn long_summaries_are_cut_to_the_size_limit() {
+        let short = ReviewResult { review: "Looks good.\n".to_string(), ..ReviewResult::default() };
+        assert_eq!(summary_comment(&short), format!("{}\n## Code Review\n\nLooks good.", MARKER));
+
+        let long = ReviewResult { review: "é".repeat(MAX_COMMENT_CHARS), ..ReviewResult::default() };
+        let summary = summary_comment(&long);
+        assert_eq!(summary.chars().count(), MAX_COMMENT_CHARS);
+        assert!(summary.starts_with(MARKER));
+        assert!(summary.ends_with("*The review was truncated to fit GitHub's comment size limit.*"));
+    }
+}
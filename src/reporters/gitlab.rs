@@ -0,0 +1,219 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use crate::findings::Finding;
+use crate::{debug_enabled, ReviewResult};
+use super::Reporter;
+
+/// A merge request given on the command line as `project_id/mr_iid`, or as a bare iid whose
+/// project is filled in from `CI_PROJECT_ID`. The project may be a numeric id or a full path
+/// such as `group/subgroup/project`.
+#[derive(Debug, Clone)]
+pub struct MergeRequestRef {
+    pub project: String,
+    pub iid: u64,
+}
+
+impl FromStr for MergeRequestRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected project_id/mr_iid or an MR iid, got '{}'", s);
+        if let Ok(iid) = s.trim_start_matches('!').parse() {
+            return Ok(MergeRequestRef {
+                project: String::new(),
+                iid,
+            });
+        }
+        let (project, iid) = s.rsplit_once('/').ok_or_else(invalid)?;
+        if project.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(MergeRequestRef {
+            project: project.to_string(),
+            iid: iid.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl MergeRequestRef {
+    /// The merge request of the current GitLab CI pipeline, from `CI_PROJECT_ID` and
+    /// `CI_MERGE_REQUEST_IID`, which are only set in merge request pipelines.
+    pub fn from_ci() -> Option<Self> {
+        Some(MergeRequestRef {
+            project: std::env::var("CI_PROJECT_ID").ok()?,
+            iid: std::env::var("CI_MERGE_REQUEST_IID").ok()?.parse().ok()?,
+        })
+    }
+
+    /// Whether the project still has to be filled in, because only an iid was given.
+    pub fn needs_project(&self) -> bool {
+        self.project.is_empty()
+    }
+}
+
+impl fmt::Display for MergeRequestRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}!{}", self.project, self.iid)
+    }
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    diff_refs: DiffRefs,
+}
+
+#[derive(Deserialize)]
+struct DiffRefs {
+    base_sha: String,
+    start_sha: String,
+    head_sha: String,
+}
+
+#[derive(Serialize)]
+struct Note {
+    body: String,
+}
+
+#[derive(Serialize)]
+struct Discussion<'a> {
+    body: String,
+    position: Position<'a>,
+}
+
+#[derive(Serialize)]
+struct Position<'a> {
+    position_type: &'static str,
+    base_sha: &'a str,
+    start_sha: &'a str,
+    head_sha: &'a str,
+    old_path: &'a str,
+    new_path: &'a str,
+    new_line: u32,
+}
+
+/// Posts reviews to a GitLab merge request through the REST API.
+pub struct GitlabReporter {
+    api_url: String,
+    token: String,
+    merge_request: MergeRequestRef,
+    client: Client,
+}
+
+impl GitlabReporter {
+    pub fn new(merge_request: MergeRequestRef, token: String) -> Self {
+        GitlabReporter {
+            api_url: std::env::var("CI_API_V4_URL")
+                .unwrap_or_else(|_| "https://gitlab.com/api/v4".to_string()),
+            token,
+            merge_request,
+            client: Client::new(),
+        }
+    }
+
+    /// Targets a self-hosted instance at `gitlab_url`, e.g. `https://gitlab.example.com`.
+    pub fn with_gitlab_url(mut self, gitlab_url: Option<&str>) -> Self {
+        if let Some(gitlab_url) = gitlab_url {
+            self.api_url = format!("{}/api/v4", gitlab_url.trim_end_matches('/'));
+        }
+        self
+    }
+
+    fn merge_request_url(&self) -> String {
+        format!(
+            "{}/projects/{}/merge_requests/{}",
+            self.api_url,
+            self.merge_request.project.replace('/', "%2F"),
+            self.merge_request.iid
+        )
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<String, Box<dyn Error>> {
+        let response = request
+            .header("PRIVATE-TOKEN", &self.token)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(format!("GitLab API returned {} for {}: {}", status, self.merge_request, text).into());
+        }
+
+        Ok(text)
+    }
+}
+
+/// The note: the full review and the score, followed by the findings that couldn't be
+/// placed on a line of the merge request's diff.
+fn note_body(result: &ReviewResult, unplaced: &[&Finding]) -> String {
+    let mut body = format!("## Code Review\n\n{}", result.review.trim_end());
+    if let Some(score) = &result.score {
+        body.push_str(&format!("\n\n**{}**", score));
+    }
+    if !unplaced.is_empty() {
+        body.push_str("\n\nOutside the lines changed in this merge request:\n");
+        for finding in unplaced {
+            let location = match (&finding.file, finding.line) {
+                (Some(file), Some(line)) => format!("`{}:{}`: ", file, line),
+                _ => String::new(),
+            };
+            body.push_str(&format!("\n- {}{}", location, finding.message));
+        }
+    }
+    body
+}
+
+#[async_trait]
+impl Reporter for GitlabReporter {
+    /// Starts a diff discussion on the line of each finding that carries a file and line, then
+    /// adds the full review as a note. GitLab rejects positions outside the diff, so findings
+    /// it refuses are listed in the note instead.
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let url = self.merge_request_url();
+        let inline: Vec<&Finding> = result
+            .findings
+            .iter()
+            .filter(|finding| finding.file.is_some() && finding.line.is_some())
+            .collect();
+        let mut unplaced = Vec::new();
+
+        if !inline.is_empty() {
+            let text = self.send(self.client.get(&url)).await?;
+            let merge_request: MergeRequest = serde_json::from_str(&text)?;
+            let refs = &merge_request.diff_refs;
+
+            for finding in inline {
+                let path = finding.file.as_deref().unwrap_or_default();
+                let discussion = Discussion {
+                    body: finding.message.clone(),
+                    position: Position {
+                        position_type: "text",
+                        base_sha: &refs.base_sha,
+                        start_sha: &refs.start_sha,
+                        head_sha: &refs.head_sha,
+                        old_path: path,
+                        new_path: path,
+                        new_line: finding.line.unwrap_or_default(),
+                    },
+                };
+                let request = self.client.post(format!("{}/discussions", url)).json(&discussion);
+                if let Err(e) = self.send(request).await {
+                    if debug_enabled() {
+                        warning!("Could not place finding on {}: {}", path, e);
+                    }
+                    unplaced.push(finding);
+                }
+            }
+        }
+
+        let body = note_body(result, &unplaced);
+        self.send(self.client.post(format!("{}/notes", url)).json(&Note { body })).await?;
+
+        Ok(())
+    }
+}
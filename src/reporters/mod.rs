@@ -0,0 +1,18 @@
+use std::error::Error;
+use async_trait::async_trait;
+use crate::ReviewResult;
+
+pub mod bitbucket;
+pub mod file;
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+pub mod notes;
+pub mod slack;
+pub mod stdout;
+
+/// A destination that a finished review is delivered to.
+#[async_trait]
+pub trait Reporter {
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>>;
+}
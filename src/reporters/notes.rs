@@ -0,0 +1,53 @@
+use std::error::Error;
+use async_trait::async_trait;
+use crate::git;
+use crate::ReviewResult;
+use super::Reporter;
+
+/// Stores the review as a git note on the reviewed commit, so it travels with the
+/// repository instead of living in an external service.
+pub struct NotesReporter {
+    notes_ref: String,
+    commit: String,
+    append: bool,
+}
+
+impl NotesReporter {
+    /// Notes go to `refs/notes/<notes_ref>` on `commit`, which should be a full hash so a
+    /// review that finishes after HEAD moved still lands on the reviewed commit.
+    pub fn new(notes_ref: String, commit: String) -> Self {
+        NotesReporter {
+            notes_ref,
+            commit,
+            append: false,
+        }
+    }
+
+    /// Adds to a note from an earlier review of the same commit instead of replacing it.
+    pub fn with_append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+}
+
+/// The review with its score, followed by which model wrote it and when.
+fn note_text(result: &ReviewResult) -> String {
+    let mut text = result.review.trim_end().to_string();
+    if let Some(score) = &result.score {
+        text.push_str(&format!("\n\n{}", score));
+    }
+    text.push_str(&format!("\n\nReviewed by code_reviewer with {} at {}\n", result.model, result.timestamp));
+    text
+}
+
+#[async_trait]
+impl Reporter for NotesReporter {
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        git::save_note(&self.notes_ref, &self.commit, &note_text(result), self.append)?;
+        let short = &self.commit[..self.commit.len().min(7)];
+        if !crate::quiet() {
+            eprintln!("Saved review as a note on {} in refs/notes/{}", short, self.notes_ref);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+use std::error::Error;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use crate::findings::Severity;
+use crate::ReviewResult;
+use super::Reporter;
+
+/// Slack rejects `text` fields in section blocks longer than this.
+const BLOCK_TEXT_LIMIT: usize = 3000;
+const TOP_FINDINGS: usize = 3;
+/// The review is split over at most this many section blocks; Slack allows 50 blocks in all.
+const MAX_REVIEW_BLOCKS: usize = 5;
+
+/// Posts a review summary to a Slack Incoming Webhook.
+pub struct SlackReporter {
+    webhook_url: String,
+    review_lines: usize,
+    report_path: Option<String>,
+    client: Client,
+}
+
+impl SlackReporter {
+    pub fn new(webhook_url: String) -> Self {
+        SlackReporter {
+            webhook_url,
+            review_lines: 20,
+            report_path: None,
+            client: Client::new(),
+        }
+    }
+
+    /// Only the first `review_lines` lines of the review are sent.
+    pub fn with_review_lines(mut self, review_lines: usize) -> Self {
+        self.review_lines = review_lines;
+        self
+    }
+
+    /// Points readers to the full report written with `--output`.
+    pub fn with_report_path(mut self, report_path: Option<String>) -> Self {
+        self.report_path = report_path;
+        self
+    }
+
+    fn message(&self, result: &ReviewResult) -> Value {
+        let mut blocks = vec![
+            json!({
+                "type": "header",
+                "text": { "type": "plain_text", "text": format!("Code review: {} findings", result.findings.len()) },
+            }),
+            json!({
+                "type": "section",
+                "fields": [
+                    { "type": "mrkdwn", "text": format!("*Repository:* {}", escape(result.repository.as_deref().unwrap_or("unknown"))) },
+                    { "type": "mrkdwn", "text": format!("*Branch:* {}", escape(result.branch.as_deref().unwrap_or("unknown"))) },
+                    { "type": "mrkdwn", "text": format!("*Files changed:* {}", result.diff.files_changed) },
+                    { "type": "mrkdwn", "text": format!("*Lines:* +{} / -{}", result.diff.insertions, result.diff.deletions) },
+                    { "type": "mrkdwn", "text": format!("*Model:* {}", escape(&result.model)) },
+                    { "type": "mrkdwn", "text": format!("*Severity:* {}", severity_breakdown(result)) },
+                ],
+            }),
+        ];
+
+        let mut top: Vec<_> = result.findings.iter().collect();
+        top.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+        if !top.is_empty() {
+            let mut text = String::from("*Top findings*\n");
+            for finding in top.into_iter().take(TOP_FINDINGS) {
+                text.push_str(&format!("• *{}* {}\n", finding.severity, escape_truncated(&finding.message, 600).0));
+            }
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": text },
+            }));
+        }
+
+        blocks.push(json!({ "type": "divider" }));
+        for text in review_sections(&result.review, self.review_lines) {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": text },
+            }));
+        }
+
+        if let Some(path) = &self.report_path {
+            blocks.push(json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": format!("Full report: `{}`", escape(path)) }],
+            }));
+        }
+
+        json!({
+            "text": format!("Code review: {} findings", result.findings.len()),
+            "blocks": blocks,
+        })
+    }
+}
+
+/// Splits the first `max_lines` lines of the review, escaped, into code blocks that each fit
+/// in a section, ending with a note when anything was left out.
+fn review_sections(review: &str, max_lines: usize) -> Vec<String> {
+    // Leave room for the code fence and the note
+    const CHUNK_LIMIT: usize = BLOCK_TEXT_LIMIT - 200;
+
+    let total_lines = review.lines().count();
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut shown_lines = 0;
+    let mut elided = total_lines > max_lines;
+    for line in review.lines().take(max_lines) {
+        // A line on its own longer than a block is cut
+        let (line, cut) = escape_truncated(line, CHUNK_LIMIT - 1);
+        elided |= cut;
+        if current.chars().count() + line.chars().count() + 1 > CHUNK_LIMIT {
+            if chunks.len() + 1 == MAX_REVIEW_BLOCKS {
+                elided = true;
+                break;
+            }
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(&line);
+        current.push('\n');
+        shown_lines += 1;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    let mut sections: Vec<String> = chunks
+        .iter()
+        // Fences in the review would end the code block early
+        .map(|chunk| format!("```{}```", chunk.trim_end().replace("```", "'''")))
+        .collect();
+    if elided {
+        let note = format!("_Review truncated: showing {} of {} lines._", shown_lines, total_lines);
+        match sections.last_mut() {
+            Some(last) => last.push_str(&format!("\n{}", note)),
+            None => sections.push(note),
+        }
+    }
+    sections
+}
+
+fn severity_breakdown(result: &ReviewResult) -> String {
+    Severity::ALL
+        .iter()
+        .map(|severity| {
+            let count = result.findings.iter().filter(|f| f.severity == *severity).count();
+            format!("{} {}", count, severity)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Escapes `&`, `<` and `>`, which Slack reads as markup in mrkdwn, e.g. `<!channel>`.
+fn escape(text: &str) -> String {
+    escape_truncated(text, usize::MAX).0
+}
+
+/// Escapes `text` like [`escape`] and cuts the result to at most `max` characters without
+/// splitting an escape, reporting whether anything was dropped.
+fn escape_truncated(text: &str, max: usize) -> (String, bool) {
+    let mut escaped = String::new();
+    let mut len = 0;
+    for c in text.chars() {
+        let (piece, piece_len) = match c {
+            '&' => ("&amp;", 5),
+            '<' => ("&lt;", 4),
+            '>' => ("&gt;", 4),
+            _ => ("", 1),
+        };
+        if len + piece_len > max {
+            return (escaped, true);
+        }
+        if piece.is_empty() {
+            escaped.push(c);
+        } else {
+            escaped.push_str(piece);
+        }
+        len += piece_len;
+    }
+    (escaped, false)
+}
+
+#[async_trait]
+impl Reporter for SlackReporter {
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let response = self.client
+            .post(&self.webhook_url)
+            .json(&self.message(result))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await?;
+            return Err(format!("Slack webhook returned {}: {}", status, text).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::DiffStats;
+    use crate::findings::parse_findings;
+    use super::*;
+
+    /// `count` lines of 99 characters each, numbered.
+    fn long_review(count: usize) -> String {
+        (0..count).map(|line| format!("{:04} {}\n", line, "x".repeat(94))).collect()
+    }
+
+    #[test]
+    fn short_reviews_are_one_code_block() {
+        assert_eq!(review_sections("Looks good.\nShip it.\n", 20), ["```Looks good.\nShip it.```"]);
+    }
+
+    #[test]
+    fn long_reviews_are_split_to_fit_sections() {
+        let sections = review_sections(&long_review(60), 100);
+        assert_eq!(sections.len(), 3);
+        assert!(sections.iter().all(|section| section.chars().count() <= BLOCK_TEXT_LIMIT));
+        assert!(!sections.concat().contains("truncated"));
+        assert!(sections[2].ends_with(&format!("0059 {}```", "x".repeat(94))));
+    }
+
+    #[test]
+    fn at_most_five_sections_are_sent() {
+        let sections = review_sections(&long_review(200), 1000);
+        assert_eq!(sections.len(), MAX_REVIEW_BLOCKS);
+        assert!(sections.iter().all(|section| section.chars().count() <= BLOCK_TEXT_LIMIT));
+        let shown: usize = sections.iter().map(|section| section.lines().filter(|line| line.contains("xxx")).count()).sum();
+        assert!(sections[4].ends_with(&format!("_Review truncated: showing {} of 200 lines._", shown)));
+    }
+
+    #[test]
+    fn lines_past_the_limit_are_noted() {
+        let sections = review_sections(&long_review(30), 20);
+        assert_eq!(sections.len(), 1);
+        assert!(sections[0].ends_with("```\n_Review truncated: showing 20 of 30 lines._"));
+    }
+
+    #[test]
+    fn fences_and_markup_in_the_review_are_neutralized() {
+        let sections = review_sections("Use this:\n```rust\nlet names: Vec<String> = a && b;\n```\n<!channel>\n", 20);
+        assert_eq!(
+            sections,
+            ["```Use this:\n'''rust\nlet names: Vec&lt;String&gt; = a &amp;&amp; b;\n'''\n&lt;!channel&gt;```"]
+        );
+    }
+
+    #[test]
+    fn message_summarizes_the_review() {
+        let result = ReviewResult {
+            repository: Some("shop".to_string()),
+            branch: Some("feature/<cart>".to_string()),
+            model: "codellama".to_string(),
+            diff: DiffStats { files_changed: 2, insertions: 10, deletions: 3 },
+            review: "Review text\n".to_string(),
+            findings: parse_findings(
+                "- `src/a.rs:1` odd name (info)\n\
+                - `src/a.rs:2` `Vec<u8>` unwrap panics (error)\n\
+                - `src/a.rs:3` quadratic loop (warning)\n\
+                - `src/a.rs:4` rename (suggestion)\n",
+            ),
+            ..ReviewResult::default()
+        };
+        let reporter = SlackReporter::new("http://127.0.0.1:9/hook".to_string()).with_report_path(Some("review.md".to_string()));
+        let message = reporter.message(&result);
+
+        assert_eq!(message["text"], "Code review: 4 findings");
+        let blocks = message["blocks"].as_array().unwrap();
+        let types: Vec<&str> = blocks.iter().map(|block| block["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["header", "section", "section", "divider", "section", "context"]);
+        let fields: Vec<&str> = blocks[1]["fields"].as_array().unwrap().iter().map(|field| field["text"].as_str().unwrap()).collect();
+        assert_eq!(
+            fields,
+            [
+                "*Repository:* shop",
+                "*Branch:* feature/&lt;cart&gt;",
+                "*Files changed:* 2",
+                "*Lines:* +10 / -3",
+                "*Model:* codellama",
+                "*Severity:* 1 error, 1 warning, 1 info, 1 suggestion",
+            ]
+        );
+        assert_eq!(
+            blocks[2]["text"]["text"],
+            "*Top findings*\n\
+            • *error* `src/a.rs:2` `Vec&lt;u8&gt;` unwrap panics (error)\n\
+            • *warning* `src/a.rs:3` quadratic loop (warning)\n\
+            • *info* `src/a.rs:1` odd name (info)\n"
+        );
+        assert_eq!(blocks[4]["text"]["text"], "```Review text```");
+        assert_eq!(blocks[5]["elements"][0]["text"], "Full report: `review.md`");
+    }
+}
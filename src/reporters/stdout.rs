@@ -0,0 +1,64 @@
+use std::error::Error;
+use async_trait::async_trait;
+use owo_colors::OwoColorize;
+use crate::formats::{self, OutputFormat, RenderOptions};
+use crate::pager;
+use crate::report;
+use crate::ReviewResult;
+use super::Reporter;
+
+/// Prints the review to stdout in the selected format.
+pub struct StdoutReporter {
+    format: OutputFormat,
+    options: RenderOptions,
+    pager: bool,
+    banner: bool,
+}
+
+impl StdoutReporter {
+    pub fn new(format: OutputFormat, options: RenderOptions) -> Self {
+        StdoutReporter { format, options, pager: false, banner: true }
+    }
+
+    /// Shows reviews that don't fit on the terminal through the pager.
+    pub fn with_pager(mut self, pager: bool) -> Self {
+        self.pager = pager;
+        self
+    }
+
+    /// Puts a "Code Review Results:" heading above text reviews.
+    pub fn with_banner(mut self, banner: bool) -> Self {
+        self.banner = banner;
+        self
+    }
+}
+
+#[async_trait]
+impl Reporter for StdoutReporter {
+    async fn report(&self, result: &ReviewResult) -> Result<(), Box<dyn Error>> {
+        let out = formats::render(self.format, result, &self.options)?;
+
+        let out = match self.format {
+            OutputFormat::Text if self.options.color => {
+                let banner = if self.banner { format!("\n{}\n", "Code Review Results:".bold().green()) } else { String::new() };
+                let mut text = format!("{}{}", banner, report::render_markdown(&out));
+                if let Some(score) = &result.score {
+                    text.push_str(&format!("\n{}\n", score.bold()));
+                }
+                text
+            }
+            OutputFormat::Text => {
+                let banner = if self.banner { "\nCode Review Results:\n" } else { "" };
+                let mut text = format!("{}{}", banner, out);
+                if let Some(score) = &result.score {
+                    text.push_str(&format!("\n{}\n", score));
+                }
+                text
+            }
+            _ => out,
+        };
+
+        pager::write_output(&out, self.pager)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// Default template for the scoring prompt. `{diff}` and `{review}` are replaced with the
+/// change and the finished review.
+pub const DEFAULT_SCORE_PROMPT: &str = "Rate the code change below on a scale from 1 to 10, where 10 is best, \
+for each of these dimensions: bugs (freedom from bugs), style, performance, security, and \
+suggestions (how little room for improvement is left). Use the review for guidance.
+
+```diff
+{diff}
+```
+
+Review:
+
+{review}
+
+Respond with only a JSON object of the form \
+{\"bugs\": 7, \"style\": 8, \"performance\": 9, \"security\": 10, \"suggestions\": 6, \"overall\": 8.0}";
+
+/// Numeric quality ratings for a change, from 1 (worst) to 10 (best).
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewScore {
+    pub bugs: u8,
+    pub style: u8,
+    pub performance: u8,
+    pub security: u8,
+    pub suggestions: u8,
+    pub overall: f32,
+}
+
+#[derive(Deserialize)]
+struct RawScore {
+    bugs: u8,
+    style: u8,
+    performance: u8,
+    security: u8,
+    suggestions: u8,
+    overall: Option<f32>,
+}
+
+pub fn score_prompt(template: &str, diff: &str, review: &str) -> String {
+    template.replace("{diff}", diff).replace("{review}", review)
+}
+
+/// Reads the JSON object out of the model's answer, ignoring any prose or code fence
+/// around it. A missing `overall` is the mean of the other ratings.
+pub fn parse_score(text: &str) -> Result<ReviewScore, Box<dyn Error>> {
+    let start = text.find('{').ok_or("no JSON object in the scoring response")?;
+    let end = text.rfind('}').ok_or("no JSON object in the scoring response")?;
+    let raw: RawScore = serde_json::from_str(&text[start..=end])?;
+
+    let clamp = |value: u8| value.clamp(1, 10);
+    let ratings = [raw.bugs, raw.style, raw.performance, raw.security, raw.suggestions].map(clamp);
+    let mean = ratings.iter().map(|r| *r as f32).sum::<f32>() / ratings.len() as f32;
+
+    Ok(ReviewScore {
+        bugs: ratings[0],
+        style: ratings[1],
+        performance: ratings[2],
+        security: ratings[3],
+        suggestions: ratings[4],
+        overall: raw.overall.map(|o| o.clamp(1.0, 10.0)).unwrap_or(mean),
+    })
+}
+
+impl fmt::Display for ReviewScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Score: {:.1}/10 (bugs {}, style {}, performance {}, security {}, suggestions {})",
+            self.overall, self.bugs, self.style, self.performance, self.security, self.suggestions
+        )
+    }
+}
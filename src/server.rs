@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use crate::backend::BackendKind;
+use crate::{diff, CodeReviewTool, ReviewResult};
+
+/// What a client posts to `/review`.
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    /// The changes to review, as a unified diff
+    pub diff: String,
+    /// Files to send as context, by path; the server has no codebase of its own to read
+    #[serde(default)]
+    pub context_files: BTreeMap<String, String>,
+    /// Model to review with instead of the configured one
+    pub model: Option<String>,
+}
+
+/// Reviews diffs posted over HTTP with one shared reviewer, so machines without a model of
+/// their own can use a central one.
+///
+/// `POST /review` takes a [`ReviewRequest`] and answers with the [`ReviewResult`] of a
+/// structured review, the same JSON `--format json` prints. `GET /healthz` says whether the
+/// backend can be reached. A review stops when its client disconnects.
+pub struct Server {
+    reviewer: CodeReviewTool,
+    backend: BackendKind,
+    ollama_url: Option<String>,
+    max_files_context: usize,
+    verifier_model: Option<String>,
+    /// Bounds how many reviews run at once; the others wait for a permit
+    reviews: Semaphore,
+}
+
+impl Server {
+    /// A server reviewing with `reviewer`, whose requests go to a `backend` API.
+    pub fn new(reviewer: CodeReviewTool, backend: BackendKind) -> Self {
+        Server {
+            reviewer,
+            backend,
+            ollama_url: None,
+            max_files_context: crate::default_max_files_context(),
+            verifier_model: None,
+            reviews: Semaphore::new(4),
+        }
+    }
+
+    /// Reports `ollama_url` as the server the reviews come from.
+    pub fn with_ollama_url(mut self, ollama_url: Option<String>) -> Self {
+        self.ollama_url = ollama_url;
+        self
+    }
+
+    /// Sends at most `max_files_context` of the posted context files with each review.
+    pub fn with_max_files_context(mut self, max_files_context: usize) -> Self {
+        self.max_files_context = max_files_context;
+        self
+    }
+
+    /// Has `verifier_model` check each review, see [`CodeReviewTool::review_structured`].
+    pub fn with_verifier_model(mut self, verifier_model: Option<String>) -> Self {
+        self.verifier_model = verifier_model;
+        self
+    }
+
+    /// Runs at most `max_reviews` reviews at once, and at least one.
+    pub fn with_max_reviews(mut self, max_reviews: usize) -> Self {
+        self.reviews = Semaphore::new(max_reviews.max(1));
+        self
+    }
+
+    /// The routes, for serving or for testing without a socket.
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/review", post(review))
+            .route("/healthz", get(health))
+            .with_state(Arc::new(self))
+    }
+
+    /// Answers requests on `listener` until `shutdown` completes, letting the reviews in
+    /// progress finish first.
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> io::Result<()> {
+        axum::serve(listener, self.router()).with_graceful_shutdown(shutdown).await
+    }
+}
+
+fn error(status: StatusCode, message: impl ToString) -> Response {
+    (status, Json(json!({ "error": message.to_string() }))).into_response()
+}
+
+// Hyper drops this future when the client disconnects, which drops the request to the model
+async fn review(State(server): State<Arc<Server>>, Json(request): Json<ReviewRequest>) -> Response {
+    if request.diff.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "the diff is empty");
+    }
+    let Ok(_permit) = server.reviews.acquire().await else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "the server is shutting down");
+    };
+
+    let reviewer = match &request.model {
+        Some(model) => server.reviewer.with_model(model).with_separate_usage(),
+        None => server.reviewer.with_separate_usage(),
+    };
+    let context_files: Vec<String> = request.context_files.keys().take(server.max_files_context).cloned().collect();
+    let reviewed = reviewer
+        .review_structured(&request.diff, request.context_files, server.max_files_context, server.verifier_model.as_deref())
+        .await;
+    let (review, findings) = match reviewed {
+        Ok(reviewed) => reviewed,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, e),
+    };
+
+    let result = ReviewResult {
+        backend: server.backend,
+        model: reviewer.model().to_string(),
+        ollama_url: server.ollama_url.clone(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        diff: diff::diff_stats(&request.diff),
+        files: diff::parse(&request.diff).into_iter().map(|file| file.path).collect(),
+        patch: request.diff,
+        context_files,
+        review,
+        findings,
+        usage: reviewer.usage(),
+        ..ReviewResult::default()
+    };
+    Json(result).into_response()
+}
+
+async fn health(State(server): State<Arc<Server>>) -> Response {
+    match server.reviewer.health().await {
+        Ok(()) => Json(json!({ "status": "ok" })).into_response(),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "unavailable", "error": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::time::Duration;
+use serde::Serialize;
+use crate::language;
+
+/// Token counts and timings reported by the model, summed over every request of a review.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Usage {
+    pub models: Vec<String>,
+    pub requests: u32,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Time the model spent generating the completion, in nanoseconds.
+    pub eval_duration_ns: u64,
+    /// Total time the server spent on the requests, in nanoseconds.
+    pub total_duration_ns: u64,
+    /// Round-trip time of the requests as measured by this tool.
+    #[serde(rename = "wall_time_secs", serialize_with = "serialize_secs")]
+    pub wall_time: Duration,
+    /// Length of the prompts sent, system prompts included, in bytes.
+    #[serde(skip)]
+    pub prompt_bytes: u64,
+}
+
+impl Usage {
+    pub fn record(
+        &mut self,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        eval_duration_ns: u64,
+        total_duration_ns: u64,
+        wall_time: Duration,
+    ) {
+        if !self.models.iter().any(|m| m == model) {
+            self.models.push(model.to_string());
+        }
+        self.requests += 1;
+        self.prompt_tokens += prompt_tokens;
+        self.completion_tokens += completion_tokens;
+        self.eval_duration_ns += eval_duration_ns;
+        self.total_duration_ns += total_duration_ns;
+        self.wall_time += wall_time;
+    }
+
+    pub fn tokens_per_second(&self) -> f64 {
+        if self.eval_duration_ns == 0 {
+            return 0.0;
+        }
+        self.completion_tokens as f64 / (self.eval_duration_ns as f64 / 1e9)
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "model: {} | prompt tokens: {} | completion tokens: {} | {:.1} tokens/s | wall time: {:.1}s",
+            self.models.join(", "),
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.tokens_per_second(),
+            self.wall_time.as_secs_f64(),
+        )?;
+        if self.requests > 1 {
+            write!(f, " ({} requests)", self.requests)?;
+        }
+        Ok(())
+    }
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// What happened to the files found while reading the codebase.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CodebaseStats {
+    pub files_read: usize,
+    pub binary_skipped: usize,
+    pub too_large: usize,
+}
+
+impl fmt::Display for CodebaseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "files read: {} | binary files skipped: {} | files too large: {}",
+            self.files_read, self.binary_skipped, self.too_large,
+        )
+    }
+}
+
+/// Rough number of bytes per token, for estimating prompt sizes without a tokenizer.
+const BYTES_PER_TOKEN: u64 = 4;
+
+/// Numbers from every stage of a review, to help tune how much context is sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReviewStats {
+    pub models: Vec<String>,
+    /// Files read from the codebase, when it was read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codebase_files: Option<usize>,
+    /// Files sent to the model as context
+    pub context_files: usize,
+    pub diff_lines: usize,
+    /// Languages detected in the diff, the one with the most changed files first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    /// Prompt size estimated from its length, to compare with what the model counted
+    pub estimated_prompt_tokens: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub requests: u32,
+    /// Round-trip time of the requests
+    #[serde(rename = "latency_secs", serialize_with = "serialize_secs")]
+    pub latency: Duration,
+}
+
+impl ReviewStats {
+    pub fn new(codebase_files: Option<usize>, context_files: usize, diff: &str, usage: &Usage) -> Self {
+        ReviewStats {
+            models: usage.models.clone(),
+            codebase_files,
+            context_files,
+            diff_lines: diff.lines().count(),
+            languages: language::detect_languages(diff).iter().map(ToString::to_string).collect(),
+            estimated_prompt_tokens: usage.prompt_bytes.div_ceil(BYTES_PER_TOKEN),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            requests: usage.requests,
+            latency: usage.wall_time,
+        }
+    }
+}
+
+impl fmt::Display for ReviewStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codebase_files = self.codebase_files.map_or("-".to_string(), |files| files.to_string());
+        let rows = [
+            ("Model", self.models.join(", ")),
+            ("Codebase files", codebase_files),
+            ("Context files", self.context_files.to_string()),
+            ("Diff lines", self.diff_lines.to_string()),
+            ("Languages", if self.languages.is_empty() { "-".to_string() } else { self.languages.join(", ") }),
+            ("Prompt tokens (estimated)", self.estimated_prompt_tokens.to_string()),
+            ("Prompt tokens", self.prompt_tokens.to_string()),
+            ("Response tokens", self.completion_tokens.to_string()),
+            ("Requests", self.requests.to_string()),
+            ("Latency", format!("{:.1}s", self.latency.as_secs_f64())),
+        ];
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        writeln!(f, "Review statistics")?;
+        for (name, value) in rows {
+            writeln!(f, "  {:<width$}  {}", name, value, width = width)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+use std::sync::LazyLock;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::findings::{Category, Finding, Severity};
+
+/// JSON schema of the answer asked for in structured mode. Ollama constrains the model's output
+/// to it; other backends only get it described in the prompt.
+pub static SCHEMA: LazyLock<Value> = LazyLock::new(|| {
+    json!({
+        "type": "object",
+        "properties": {
+            "findings": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "file": { "type": ["string", "null"] },
+                        "line": { "type": ["integer", "null"] },
+                        "severity": { "type": "string", "enum": ["error", "warning", "info", "suggestion"] },
+                        "category": { "type": "string", "enum": ["bugs", "style", "performance", "security", "suggestions"] },
+                        "message": { "type": "string" },
+                        "suggestion": { "type": ["string", "null"] }
+                    },
+                    "required": ["severity", "category", "message"]
+                }
+            }
+        },
+        "required": ["findings"]
+    })
+});
+
+/// Appended to a prompt to ask for findings as JSON instead of a free-text review.
+pub const INSTRUCTIONS: &str = "Respond with only a JSON object, without code fences or commentary, of the form \
+    {\"findings\": [{\"file\": \"src/lib.rs\", \"line\": 42, \"severity\": \"error\", \"category\": \"bugs\", \
+    \"message\": \"...\", \"suggestion\": \"...\"}]}. `severity` is one of error, warning, info or suggestion, \
+    and `category` one of bugs, style, performance, security or suggestions. `file` and `line` locate the \
+    finding in the new version of the code and are null when it isn't about one place; `suggestion` says how \
+    to fix it and is null when there is nothing to add. Use an empty list when there is nothing to report.";
+
+/// One finding as the model wrote it. Every field is optional and loosely typed, because models
+/// get the details wrong more often than the shape.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawFinding {
+    #[serde(alias = "path", alias = "filename")]
+    file: Option<String>,
+    #[serde(alias = "line_number", alias = "lines")]
+    line: Option<Value>,
+    #[serde(alias = "level", alias = "priority")]
+    severity: Option<String>,
+    #[serde(alias = "type")]
+    category: Option<String>,
+    #[serde(alias = "description", alias = "issue", alias = "comment")]
+    message: Option<String>,
+    #[serde(alias = "fix", alias = "recommendation")]
+    suggestion: Option<String>,
+}
+
+impl RawFinding {
+    fn into_finding(self) -> Option<Finding> {
+        let message = self.message.map(|message| message.trim().to_string()).filter(|message| !message.is_empty())?;
+        let (line, end_line) = self.line.as_ref().map_or((None, None), lines);
+        let severity = match self.severity.as_deref().map(|severity| severity.trim().to_lowercase()) {
+            Some(severity) => match severity.as_str() {
+                "error" => Severity::Error,
+                "warning" => Severity::Warning,
+                "info" => Severity::Info,
+                "suggestion" => Severity::Suggestion,
+                other => Severity::infer(other),
+            },
+            None => Severity::infer(&message),
+        };
+        let category = self
+            .category
+            .as_deref()
+            .and_then(Category::from_keywords)
+            .or_else(|| Category::from_keywords(&message))
+            .unwrap_or(Category::Suggestions);
+
+        Some(Finding {
+            file: self.file.map(|file| file.trim().to_string()).filter(|file| !file.is_empty()),
+            line,
+            end_line,
+            severity,
+            category,
+            message,
+            suggestion: self.suggestion.map(|suggestion| suggestion.trim().to_string()).filter(|suggestion| !suggestion.is_empty()),
+        })
+    }
+}
+
+/// A line number written as `42`, `"42"` or `"42-50"`.
+fn lines(value: &Value) -> (Option<u32>, Option<u32>) {
+    match value {
+        Value::Number(number) => (number.as_u64().and_then(|line| u32::try_from(line).ok()), None),
+        Value::String(text) => {
+            let (start, end) = text.split_once('-').unwrap_or((text, ""));
+            (start.trim().parse().ok(), end.trim().parse().ok())
+        }
+        _ => (None, None),
+    }
+}
+
+/// The JSON value in `text`: the whole of it, or else whatever is between the first `{` or `[`
+/// and the last matching bracket, which drops code fences and chatter around the JSON.
+fn extract_json(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    let first_error = match serde_json::from_str(text) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+
+    let start = text.find(['{', '[']).ok_or("the response contains no JSON")?;
+    let close = if text[start..].starts_with('{') { '}' } else { ']' };
+    let end = text.rfind(close).filter(|&end| end > start).ok_or_else(|| first_error.to_string())?;
+    serde_json::from_str(&text[start..=end]).map_err(|e| e.to_string())
+}
+
+/// Parses the model's structured answer into findings. Accepts the `{"findings": [...]}` object
+/// asked for, a bare list of findings or a single finding, with or without code fences around
+/// it. Entries without a message are dropped; the error says why nothing could be parsed.
+pub fn parse(text: &str) -> Result<Vec<Finding>, String> {
+    let value = extract_json(text)?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(mut object) => match ["findings", "issues", "comments", "review"]
+            .iter()
+            .find_map(|key| object.remove(*key))
+        {
+            Some(Value::Array(items)) => items,
+            Some(_) => return Err("the findings are not a list".to_string()),
+            None if object.contains_key("message") => vec![Value::Object(object)],
+            None => return Err("the response has no findings list".to_string()),
+        },
+        _ => return Err("the response is not a JSON object or list".to_string()),
+    };
+
+    Ok(items
+        .into_iter()
+        .filter_map(|item| serde_json::from_value::<RawFinding>(item).ok())
+        .filter_map(RawFinding::into_finding)
+        .collect())
+}
+
+/// Writes findings as a markdown review, for the formats that show the review text.
+pub fn render(findings: &[Finding]) -> String {
+    if findings.is_empty() {
+        return "No issues found.\n".to_string();
+    }
+
+    let mut review = String::new();
+    for finding in findings {
+        review.push_str(&format!("- **{}** ({})", finding.severity, finding.category));
+        match (&finding.file, finding.line) {
+            (Some(file), Some(line)) => review.push_str(&format!(" `{}:{}`", file, line)),
+            (Some(file), None) => review.push_str(&format!(" `{}`", file)),
+            _ => {}
+        }
+        review.push_str(&format!(": {}\n", finding.message.replace('\n', "\n  ")));
+        if let Some(suggestion) = &finding.suggestion {
+            review.push_str(&format!("  Suggestion: {}\n", suggestion.replace('\n', "\n  ")));
+        }
+    }
+    review
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    #[test]
+    fn parses_the_requested_shape() {
+        let text = r#"{"findings": [{"file": "src/lib.rs", "line": 2, "severity": "error", "category": "bugs",
+            "message": "unwrap panics on bad input", "suggestion": "Return a Result"}]}"#;
+        let findings = parse(text).unwrap();
+        assert_eq!(findings.len(), 1);
+        let finding = &findings[0];
+        assert_eq!(finding.file.as_deref(), Some("src/lib.rs"));
+        assert_eq!(finding.line, Some(2));
+        assert_eq!(finding.severity, Severity::Error);
+        assert_eq!(finding.category, Category::Bugs);
+        assert_eq!(finding.suggestion.as_deref(), Some("Return a Result"));
+    }
+
+    #[rstest]
+    #[case::fenced("```json\n{\"findings\": [{\"severity\": \"warning\", \"category\": \"style\", \"message\": \"m\"}]}\n```")]
+    #[case::chatter_around_it("Here is the review:\n{\"findings\": [{\"severity\": \"warning\", \"category\": \"style\", \"message\": \"m\"}]}\nHope this helps!")]
+    #[case::bare_list("[{\"severity\": \"warning\", \"category\": \"style\", \"message\": \"m\"}]")]
+    #[case::single_finding("{\"severity\": \"warning\", \"category\": \"style\", \"message\": \"m\"}")]
+    #[case::other_key_names("{\"issues\": [{\"level\": \"Warning\", \"type\": \"Code style\", \"description\": \"m\"}]}")]
+    fn parses_messy_shapes(#[case] text: &str) {
+        let findings = parse(text).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(findings[0].category, Category::Style);
+        assert_eq!(findings[0].message, "m");
+    }
+
+    #[rstest]
+    #[case::number(json!(42), (Some(42), None))]
+    #[case::string(json!("42"), (Some(42), None))]
+    #[case::range(json!("42-50"), (Some(42), Some(50)))]
+    #[case::null(json!(null), (None, None))]
+    #[case::nonsense(json!("the top"), (None, None))]
+    fn reads_line_numbers(#[case] line: Value, #[case] expected: (Option<u32>, Option<u32>)) {
+        assert_eq!(lines(&line), expected);
+    }
+
+    #[test]
+    fn infers_unknown_severities_and_categories() {
+        let text = r#"{"findings": [{"severity": "critical", "category": "SQL injection", "message": "m"},
+            {"message": "Consider caching the parsed config"}]}"#;
+        let findings = parse(text).unwrap();
+        assert_eq!((findings[0].severity, findings[0].category), (Severity::Error, Category::Security));
+        assert_eq!((findings[1].severity, findings[1].category), (Severity::Suggestion, Category::Performance));
+    }
+
+    #[test]
+    fn drops_entries_without_a_message() {
+        let text = r#"{"findings": [{"severity": "error"}, "not a finding", {"message": "  "}, {"message": "kept"}]}"#;
+        let findings = parse(text).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].message, "kept");
+    }
+
+    #[test]
+    fn empty_list_is_no_findings() {
+        assert!(parse(r#"{"findings": []}"#).unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case::prose("The code looks good overall. Consider adding tests.")]
+    #[case::truncated("{\"findings\": [{\"severity\": \"error\", \"message\": \"unterminated")]
+    #[case::wrong_object("{\"summary\": \"looks fine\"}")]
+    #[case::findings_not_a_list("{\"findings\": \"none\"}")]
+    fn rejects_what_is_not_findings(#[case] text: &str) {
+        assert!(parse(text).is_err());
+    }
+
+    #[test]
+    fn rendered_review_lists_every_finding() {
+        let findings = parse(r#"[{"file": "src/lib.rs", "line": 2, "severity": "error", "category": "bugs",
+            "message": "unwrap panics", "suggestion": "Use ?"}]"#).unwrap();
+        assert_eq!(render(&findings), "- **error** (bugs) `src/lib.rs:2`: unwrap panics\n  Suggestion: Use ?\n");
+        assert_eq!(render(&[]), "No issues found.\n");
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::{BTreeMap, BTreeSet};
+use tree_sitter::{Language, Node, Parser};
+use crate::diff::{self, LineKind};
+
+/// Grammar for a file, by extension, and the separator between a symbol and its enclosing
+/// type or module.
+fn language(path: &str) -> Option<(Language, &'static str)> {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension)?;
+    match extension {
+        "rs" => Some((tree_sitter_rust::LANGUAGE.into(), "::")),
+        "py" | "pyi" => Some((tree_sitter_python::LANGUAGE.into(), ".")),
+        "ts" | "mts" | "cts" => Some((tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(), ".")),
+        // The TSX grammar reads plain JavaScript and JSX too
+        "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some((tree_sitter_typescript::LANGUAGE_TSX.into(), ".")),
+        "go" => Some((tree_sitter_go::LANGUAGE.into(), ".")),
+        _ => None,
+    }
+}
+
+/// A function, method or type and the lines it spans, counted from 1.
+#[derive(Debug)]
+struct Symbol {
+    name: String,
+    lines: (usize, usize),
+}
+
+/// Names of the functions, methods and types in Rust, Python, TypeScript, JavaScript and Go
+/// files that the diff changes, keyed by file. Methods are qualified with their type, e.g.
+/// `Parser::parse` in Rust or `Parser.parse` elsewhere. `read` gives the contents of a file
+/// after the change; files it can't read, and files in other languages, are left out.
+pub fn parse_changed_symbols(diff: &str, read: impl Fn(&str) -> Option<String>) -> BTreeMap<String, Vec<String>> {
+    let mut changed = BTreeMap::new();
+
+    for file in diff::parse(diff) {
+        let Some(path) = file.new_path.as_deref().filter(|_| !file.binary) else {
+            continue;
+        };
+        let Some((language, separator)) = language(path) else {
+            continue;
+        };
+        let Some(source) = read(path) else {
+            continue;
+        };
+
+        let mut parser = Parser::new();
+        if parser.set_language(&language).is_err() {
+            continue;
+        }
+        let Some(tree) = parser.parse(&source, None) else {
+            continue;
+        };
+        let mut symbols = Vec::new();
+        collect(tree.root_node(), source.as_bytes(), separator, &mut Vec::new(), &mut symbols);
+
+        let mut names: Vec<String> = Vec::new();
+        for line in changed_lines(&file.hunks) {
+            // Symbols nest, so the narrowest one around a line is the one it belongs to
+            let innermost = symbols
+                .iter()
+                .filter(|symbol| symbol.lines.0 <= line && line <= symbol.lines.1)
+                .min_by_key(|symbol| symbol.lines.1 - symbol.lines.0);
+            if let Some(symbol) = innermost {
+                if !names.contains(&symbol.name) {
+                    names.push(symbol.name.clone());
+                }
+            }
+        }
+        if !names.is_empty() {
+            changed.insert(path.to_string(), names);
+        }
+    }
+
+    changed
+}
+
+/// Lines of the new file the hunks add, and for removed lines the line that now stands where
+/// they were.
+fn changed_lines(hunks: &[diff::Hunk]) -> BTreeSet<usize> {
+    let mut lines = BTreeSet::new();
+    for hunk in hunks {
+        let mut next = hunk.new_start as usize;
+        for line in &hunk.lines {
+            match line.kind {
+                LineKind::Added => {
+                    lines.insert(next);
+                    next += 1;
+                }
+                LineKind::Removed => {
+                    lines.insert(next);
+                }
+                _ => next += 1,
+            }
+        }
+    }
+    lines
+}
+
+fn collect(node: Node, source: &[u8], separator: &str, scope: &mut Vec<String>, symbols: &mut Vec<Symbol>) {
+    let text = |node: Node| node.utf8_text(source).ok().map(str::to_string);
+    let field = |name: &str| node.child_by_field_name(name).and_then(text);
+
+    // A symbol of its own, and the scope its children are named in
+    let (name, inner) = match node.kind() {
+        "function_item" | "function_signature_item" | "struct_item" | "enum_item" | "union_item" | "type_item"
+        | "function_definition" | "function_declaration" | "generator_function_declaration"
+        | "method_definition" | "interface_declaration" | "type_alias_declaration" | "enum_declaration"
+        | "type_spec" => (field("name"), None),
+        "trait_item" | "class_definition" | "class_declaration" | "abstract_class_declaration" => {
+            (field("name"), field("name"))
+        }
+        // Not symbols, but their items are named after them
+        "mod_item" => (None, field("name")),
+        "impl_item" => (None, field("type").map(|name| base_type(&name))),
+        // `const handler = () => ...` and `const handler = function () { ... }`
+        "variable_declarator"
+            if node
+                .child_by_field_name("value")
+                .is_some_and(|value| matches!(value.kind(), "arrow_function" | "function_expression" | "function")) =>
+        {
+            (field("name"), None)
+        }
+        // Go methods are named after the type of their receiver
+        "method_declaration" => {
+            let receiver = node
+                .child_by_field_name("receiver")
+                .and_then(|receiver| receiver.named_child(0))
+                .and_then(|parameter| parameter.child_by_field_name("type"))
+                .and_then(text)
+                .map(|name| base_type(&name));
+            let name = field("name").map(|name| match receiver {
+                Some(receiver) => format!("{}{}{}", receiver, separator, name),
+                None => name,
+            });
+            (name, None)
+        }
+        _ => (None, None),
+    };
+
+    if let Some(name) = name {
+        let mut qualified = scope.clone();
+        qualified.push(name);
+        symbols.push(Symbol {
+            name: qualified.join(separator),
+            lines: (node.start_position().row + 1, node.end_position().row + 1),
+        });
+    }
+
+    let pushed = inner.is_some();
+    scope.extend(inner);
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect(child, source, separator, scope, symbols);
+    }
+    if pushed {
+        scope.pop();
+    }
+}
+
+/// `Parser` for `*Parser`, `&mut Parser<'a>` or `Parser[T]`.
+fn base_type(name: &str) -> String {
+    let name = name.trim_start_matches(['*', '&']).trim_start_matches("mut ").trim();
+    name.split(['<', '[']).next().unwrap_or(name).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A diff replacing line `line` of `path`, where `source` is the file after the change.
+    fn symbols(path: &str, source: &str, line: usize) -> Vec<String> {
+        let new = source.lines().nth(line - 1).unwrap();
+        let diff = format!(
+            "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -{1},1 +{1},1 @@\n-old\n+{2}\n",
+            path, line, new
+        );
+        parse_changed_symbols(&diff, |_| Some(source.to_string())).remove(path).unwrap_or_default()
+    }
+
+    const RUST: &str = "\
+mod parser {
+    pub struct Parser {
+        input: String,
+    }
+
+    impl<'a> Parser {
+        pub fn parse(&self) -> u32 {
+            self.input.parse().unwrap()
+        }
+    }
+}
+
+fn main() {
+    println!(\"hi\");
+}
+";
+
+    #[test]
+    fn rust_methods_are_qualified_with_their_type_and_module() {
+        assert_eq!(symbols("src/lib.rs", RUST, 8), ["parser::Parser::parse"]);
+        assert_eq!(symbols("src/lib.rs", RUST, 3), ["parser::Parser"]);
+        assert_eq!(symbols("src/lib.rs", RUST, 14), ["main"]);
+    }
+
+    #[test]
+    fn python_methods() {
+        let source = "class Cart:\n    def total(self):\n        return sum(self.items)\n\ndef helper():\n    pass\n";
+        assert_eq!(symbols("cart.py", source, 3), ["Cart.total"]);
+        assert_eq!(symbols("cart.py", source, 6), ["helper"]);
+    }
+
+    #[test]
+    fn typescript_classes_and_arrow_functions() {
+        let source = "\
+export class Api {
+  get(path: string) {
+    return fetch(path);
+  }
+}
+
+export const handler = (event: Event) => {
+  console.log(event);
+};
+";
+        assert_eq!(symbols("src/api.ts", source, 3), ["Api.get"]);
+        assert_eq!(symbols("src/api.ts", source, 8), ["handler"]);
+    }
+
+    #[test]
+    fn go_methods_are_named_after_their_receiver() {
+        let source = "package main\n\ntype Server struct {\n\tport int\n}\n\nfunc (s *Server) Start() error {\n\treturn nil\n}\n";
+        assert_eq!(symbols("main.go", source, 8), ["Server.Start"]);
+        assert_eq!(symbols("main.go", source, 4), ["Server"]);
+    }
+
+    #[test]
+    fn other_languages_and_top_level_lines_have_no_symbols() {
+        assert!(symbols("README.md", "# Title\n", 1).is_empty());
+        assert!(symbols("src/lib.rs", "use std::fs;\n", 1).is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use reqwest::{Certificate, ClientBuilder, Identity};
+use crate::Settings;
+
+/// PEM files for servers that require TLS client certificates (mTLS) or are signed by a
+/// private certificate authority.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsFiles {
+    /// Client certificate presented to the server
+    pub cert: Option<PathBuf>,
+    /// Private key of the client certificate, in PKCS#8
+    pub key: Option<PathBuf>,
+    /// Certificate authority trusted in addition to the system's
+    pub ca_cert: Option<PathBuf>,
+}
+
+impl TlsFiles {
+    pub fn from_settings(settings: &Settings) -> Self {
+        TlsFiles {
+            cert: settings.tls_cert_path.clone(),
+            key: settings.tls_key_path.clone(),
+            ca_cert: settings.tls_ca_cert_path.clone(),
+        }
+    }
+
+    /// Adds the client certificate and the certificate authority to `builder`. Fails when a
+    /// file can't be read or parsed, or when only one of the certificate and key is given.
+    pub fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder, Box<dyn Error>> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                let identity = Identity::from_pkcs8_pem(&read("tls_cert_path", cert)?, &read("tls_key_path", key)?)
+                    .map_err(|e| format!("invalid client certificate {}: {}", cert.display(), e))?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => return Err("tls_cert_path and tls_key_path must be set together".into()),
+        }
+        if let Some(ca_cert) = &self.ca_cert {
+            let certificate = Certificate::from_pem(&read("tls_ca_cert_path", ca_cert)?)
+                .map_err(|e| format!("invalid CA certificate {}: {}", ca_cert.display(), e))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        Ok(builder)
+    }
+}
+
+fn read(setting: &str, path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    fs::read(path).map_err(|e| format!("could not read {} {}: {}", setting, path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Option<PathBuf> {
+        Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name))
+    }
+
+    #[test]
+    fn loads_the_client_certificate_and_ca() {
+        let files = TlsFiles {
+            cert: fixture("client.crt"),
+            key: fixture("client.key"),
+            ca_cert: fixture("client.crt"),
+        };
+        assert!(files.apply(reqwest::Client::builder()).unwrap().build().is_ok());
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        let files = TlsFiles { ca_cert: fixture("missing.crt"), ..TlsFiles::default() };
+        let error = files.apply(reqwest::Client::builder()).unwrap_err().to_string();
+        assert!(error.contains("tls_ca_cert_path"), "{}", error);
+    }
+
+    #[test]
+    fn certificate_without_key_is_an_error() {
+        let files = TlsFiles { cert: fixture("client.crt"), ..TlsFiles::default() };
+        assert!(files.apply(reqwest::Client::builder()).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::future::Future;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use glob::{MatchOptions, Pattern};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use code_reviewer::{git, warning};
+
+/// How long the files must stay unchanged before a new review starts, so that saving many
+/// files at once, a formatter rewriting them or a branch switch triggers one review rather
+/// than dozens.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+// As for the codebase filter: `*` stays within a directory, `**` crosses them
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Decides which changed paths are worth a new review: the same files the codebase walk
+/// reads, so hidden, gitignored and `.code_reviewer_ignore`d paths are left alone, as are
+/// the files the review itself writes. With `only` patterns, paths must also match one of
+/// them, which keeps build output out even when the ignore files miss it.
+struct ChangeFilter {
+    root: PathBuf,
+    ignore_file: Gitignore,
+    own_output: Vec<PathBuf>,
+    only: Vec<Pattern>,
+}
+
+impl ChangeFilter {
+    fn new(root: &Path, ignore_file: Option<&Path>, own_output: &[PathBuf], only: Vec<Pattern>) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        if let Some(e) = ignore_file.and_then(|path| builder.add(path)) {
+            warning!("Could not read ignore file: {}", e);
+        }
+        ChangeFilter {
+            root: root.to_path_buf(),
+            ignore_file: builder.build().unwrap_or_else(|_| Gitignore::empty()),
+            own_output: own_output.iter().filter_map(|path| std::path::absolute(path).ok()).collect(),
+            only,
+        }
+    }
+
+    /// The paths among `paths` that should trigger a review.
+    fn relevant(&self, paths: HashSet<PathBuf>) -> Vec<PathBuf> {
+        let candidates: Vec<PathBuf> = paths
+            .into_iter()
+            .filter(|path| !self.own_output.contains(path))
+            .filter(|path| {
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    return false;
+                };
+                let hidden = relative
+                    .components()
+                    .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+                let wanted = self.only.is_empty()
+                    || self.only.iter().any(|pattern| pattern.matches_path_with(relative, MATCH_OPTIONS));
+                !hidden && wanted && !self.ignore_file.matched_path_or_any_parents(relative, path.is_dir()).is_ignore()
+            })
+            .collect();
+        if candidates.is_empty() {
+            return candidates;
+        }
+
+        let ignored = git::ignored(&candidates);
+        candidates.into_iter().filter(|path| !ignored.contains(path)).collect()
+    }
+}
+
+/// Runs `review`, then again each time files under `root` change, until Ctrl-C. The
+/// terminal is cleared before each run. A failed review is reported and watching goes on.
+/// `only` holds glob patterns, relative to `root`, that a changed path must match to count.
+pub async fn watch<F, Fut>(
+    root: &Path,
+    ignore_file: Option<&Path>,
+    own_output: &[PathBuf],
+    only: &[String],
+    mut review: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), Box<dyn Error>>>,
+{
+    let root = std::path::absolute(root)?;
+    let only = only
+        .iter()
+        .map(|pattern| Pattern::new(pattern).map_err(|e| format!("invalid --on-change-paths pattern {:?}: {}", pattern, e)))
+        .collect::<Result<_, _>>()?;
+    let filter = ChangeFilter::new(&root, ignore_file, own_output, only);
+
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            let _ = sender.send(event);
+        }
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    loop {
+        clear_terminal();
+        tokio::select! {
+            result = review() => {
+                if let Err(e) = result {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        // Changes made while the review ran, like a checkout it waited out, were already
+        // reviewed or are stale; only changes from now on start the next review
+        while events.try_recv().is_ok() {}
+        eprintln!("\nWatching {} for changes, press Ctrl-C to stop...", root.display());
+
+        tokio::select! {
+            changed = next_change(&mut events, &filter) => {
+                if !changed {
+                    return Ok(());
+                }
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+/// Waits for a relevant change and then for the files to settle. Returns false once the
+/// watcher has stopped.
+async fn next_change(events: &mut UnboundedReceiver<Event>, filter: &ChangeFilter) -> bool {
+    loop {
+        let mut paths = HashSet::new();
+        let Some(event) = events.recv().await else {
+            return false;
+        };
+        collect_paths(event, &mut paths);
+        loop {
+            match tokio::time::timeout(DEBOUNCE, events.recv()).await {
+                Ok(Some(event)) => collect_paths(event, &mut paths),
+                Ok(None) => return false,
+                Err(_) => break,
+            }
+        }
+
+        let changed = filter.relevant(paths);
+        if !changed.is_empty() {
+            if code_reviewer::debug_enabled() {
+                for path in &changed {
+                    eprintln!("Changed: {}", path.display());
+                }
+            }
+            return true;
+        }
+    }
+}
+
+/// Reading a file is not a change; the review reads the whole codebase.
+fn collect_paths(event: Event, paths: &mut HashSet<PathBuf>) {
+    if !matches!(event.kind, EventKind::Access(_)) {
+        paths.extend(event.paths);
+    }
+}
+
+fn clear_terminal() {
+    let mut stdout = io::stdout();
+    if stdout.is_terminal() {
+        let _ = write!(stdout, "\x1b[2J\x1b[H");
+        let _ = stdout.flush();
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use code_reviewer::backend::anthropic::AnthropicBackend;
+use code_reviewer::backend::ollama::{OllamaApi, OllamaBackend};
+use code_reviewer::backend::ChatMessage;
+use code_reviewer::backend::BackendKind;
+use code_reviewer::proxy::Proxies;
+use code_reviewer::server::Server;
+use code_reviewer::tls::TlsFiles;
+use code_reviewer::CodeReviewTool;
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const UNWRAP_DIFF: &str = include_str!("fixtures/unwrap.diff");
+const NEW_FILE_DIFF: &str = include_str!("fixtures/new_file.diff");
+
+fn reviewer(backend: OllamaBackend) -> CodeReviewTool {
+    CodeReviewTool::new(Arc::new(backend), Some("codellama".to_string())).with_progress(false)
+}
+
+/// A backend that retries without waiting, so the tests stay fast.
+fn backend(server: &MockServer) -> OllamaBackend {
+    OllamaBackend::new(server.uri()).with_retries(2, Duration::ZERO)
+}
+
+fn generation(model: &str, review: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({ "model": model })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": { "role": "assistant", "content": review },
+            "done": true,
+            "prompt_eval_count": 100,
+            "eval_count": 20,
+        })))
+}
+
+#[tokio::test]
+async fn reviews_a_new_file() {
+    let server = MockServer::start().await;
+    generation("codellama", "`src/config.rs:4` unwrap panics when the file is missing (error)")
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let review = reviewer(backend(&server))
+        .review_changes(NEW_FILE_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap();
+
+    assert!(review.contains("src/config.rs:4"));
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    let prompt = body["messages"].as_array().unwrap().last().unwrap()["content"].as_str().unwrap();
+    assert!(prompt.contains("+pub fn read_port(path: &str) -> u16 {"));
+}
+
+#[tokio::test]
+async fn generate_api_sends_a_single_prompt() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(json!({ "model": "codellama", "system": "Be brief." })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "response": "Looks fine.", "done": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let review = reviewer(backend(&server).with_api(OllamaApi::Generate))
+        .with_system_prompt("Be brief.".to_string())
+        .review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap();
+
+    assert_eq!(review, "Looks fine.");
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert!(body["prompt"].as_str().unwrap().contains("unwrap"));
+}
+
+#[tokio::test]
+async fn server_errors_are_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("out of memory"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    generation("codellama", "Looks fine.").expect(1).mount(&server).await;
+
+    let review = reviewer(backend(&server))
+        .review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap();
+
+    assert_eq!(review, "Looks fine.");
+}
+
+#[tokio::test]
+async fn retries_give_up_after_the_limit() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let error = reviewer(backend(&server))
+        .review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("503"));
+}
+
+#[tokio::test]
+async fn missing_model_falls_back() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({ "model": "codellama" })))
+        .respond_with(
+            ResponseTemplate::new(404)
+                .set_body_json(json!({ "error": "model 'codellama' not found, try pulling it first" })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    generation("llama3", "Looks fine.").expect(1).mount(&server).await;
+
+    let review = reviewer(backend(&server).with_fallback_model(Some("llama3".to_string())))
+        .review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap();
+
+    assert_eq!(review, "Looks fine.");
+}
+
+#[tokio::test]
+async fn missing_model_without_fallback_is_an_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(
+            ResponseTemplate::new(404)
+                .set_body_json(json!({ "error": "model 'codellama' not found, try pulling it first" })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = reviewer(backend(&server))
+        .review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().contains("model 'codellama' not found"));
+}
+
+#[tokio::test]
+async fn streamed_chunks_are_joined() {
+    let server = MockServer::start().await;
+    let chunks = [
+        json!({ "message": { "role": "assistant", "content": "`src/lib.rs:2` " }, "done": false }),
+        json!({ "message": { "role": "assistant", "content": "unwrap panics " }, "done": false }),
+        json!({ "message": { "role": "assistant", "content": "(error)" }, "done": true, "prompt_eval_count": 90, "eval_count": 12 }),
+    ];
+    let body: String = chunks.iter().map(|chunk| format!("{}\n", chunk)).collect();
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/x-ndjson"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let reviewer = reviewer(backend(&server));
+    let review = reviewer
+        .review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0)
+        .await
+        .unwrap();
+
+    assert_eq!(review, "`src/lib.rs:2` unwrap panics (error)");
+    assert_eq!(reviewer.usage().completion_tokens, 12);
+}
+
+#[tokio::test]
+async fn follow_up_questions_send_the_conversation() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({
+            "model": "codellama",
+            "messages": [
+                { "role": "user", "content": "Review this" },
+                { "role": "assistant", "content": "`src/lib.rs:2` unwrap panics (error)" },
+                { "role": "user", "content": "How do I fix it?" },
+            ],
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": { "role": "assistant", "content": "Use `unwrap_or(0)`." },
+            "done": true,
+            "prompt_eval_count": 200,
+            "eval_count": 8,
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let reviewer = reviewer(backend(&server));
+    let answer = reviewer
+        .chat(&[
+            ChatMessage::user("Review this"),
+            ChatMessage::assistant("`src/lib.rs:2` unwrap panics (error)"),
+            ChatMessage::user("How do I fix it?"),
+        ])
+        .await
+        .unwrap();
+
+    assert_eq!(answer, "Use `unwrap_or(0)`.");
+    assert_eq!(reviewer.usage().completion_tokens, 8);
+}
+
+#[tokio::test]
+async fn follow_up_answers_stream_as_they_are_written() {
+    let server = MockServer::start().await;
+    let chunks = [
+        json!({ "message": { "role": "assistant", "content": "Use " }, "done": false }),
+        json!({ "message": { "role": "assistant", "content": "`unwrap_or(0)`." }, "done": false }),
+        json!({ "message": { "role": "assistant", "content": "" }, "done": true, "prompt_eval_count": 200, "eval_count": 8 }),
+    ];
+    let body: String = chunks.iter().map(|chunk| format!("{}\n", chunk)).collect();
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/x-ndjson"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let reviewer = reviewer(backend(&server));
+    let mut pieces = Vec::new();
+    let answer = reviewer
+        .chat_streaming(
+            &[
+                ChatMessage::user("Review this"),
+                ChatMessage::assistant("`src/lib.rs:2` unwrap panics (error)"),
+                ChatMessage::user("How do I fix it?"),
+            ],
+            &mut |text| pieces.push(text.to_string()),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(answer, "Use `unwrap_or(0)`.");
+    assert_eq!(pieces, ["Use ", "`unwrap_or(0)`."]);
+    assert_eq!(reviewer.usage().completion_tokens, 8);
+}
+
+#[tokio::test]
+async fn requests_go_through_the_configured_proxy() {
+    let proxy = MockServer::start().await;
+    generation("codellama", "Looks fine.").expect(1).mount(&proxy).await;
+
+    // The host doesn't resolve, so the review can only come back through the proxy
+    let proxies = Proxies { http: Some(proxy.uri()), ..Proxies::default() };
+    let backend = OllamaBackend::new("http://ollama.invalid:11434".to_string())
+        .with_connection(&proxies, &TlsFiles::default())
+        .unwrap();
+    let review = reviewer(backend).review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    assert_eq!(review, "Looks fine.");
+}
+
+/// Server-sent events of a Messages API stream, one per payload.
+fn event_stream(events: &[serde_json::Value]) -> String {
+    events.iter().map(|event| format!("event: {}\ndata: {}\n\n", event["type"].as_str().unwrap(), event)).collect()
+}
+
+#[tokio::test]
+async fn anthropic_reviews_read_the_event_stream() {
+    let server = MockServer::start().await;
+    let events = event_stream(&[
+        json!({ "type": "message_start", "message": { "usage": { "input_tokens": 310, "output_tokens": 1 } } }),
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "`src/lib.rs:2` " } }),
+        json!({ "type": "ping" }),
+        json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": "unwrap panics (error)" } }),
+        json!({ "type": "message_delta", "delta": { "stop_reason": "end_turn" }, "usage": { "output_tokens": 14 } }),
+        json!({ "type": "message_stop" }),
+    ]);
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(header("x-api-key", "test-key"))
+        .and(header("anthropic-version", "2023-06-01"))
+        .and(body_partial_json(json!({ "model": "claude-test", "max_tokens": 1024, "system": "Be brief.", "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let backend = AnthropicBackend::new(server.uri(), "test-key".to_string(), 1024);
+    let reviewer = CodeReviewTool::new(Arc::new(backend), Some("claude-test".to_string()))
+        .with_progress(false)
+        .with_system_prompt("Be brief.".to_string());
+    let review = reviewer.review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    assert_eq!(review, "`src/lib.rs:2` unwrap panics (error)");
+    assert_eq!((reviewer.usage().prompt_tokens, reviewer.usage().completion_tokens), (310, 14));
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert!(body["messages"][0]["content"].as_str().unwrap().contains("+    input.parse().unwrap()"));
+}
+
+#[tokio::test]
+async fn anthropic_errors_name_the_error_type() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "type": "error",
+            "error": { "type": "authentication_error", "message": "invalid x-api-key" },
+        })))
+        .mount(&server)
+        .await;
+    let overloaded = MockServer::start().await;
+    let events = event_stream(&[
+        json!({ "type": "message_start", "message": { "usage": { "input_tokens": 10 } } }),
+        json!({ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } }),
+    ]);
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"))
+        .mount(&overloaded)
+        .await;
+
+    for (server, expected) in [
+        (&server, "Anthropic API error (authentication_error): invalid x-api-key"),
+        (&overloaded, "Anthropic API error (overloaded_error): Overloaded"),
+    ] {
+        let backend = AnthropicBackend::new(server.uri(), "test-key".to_string(), 1024);
+        let reviewer = CodeReviewTool::new(Arc::new(backend), None).with_progress(false);
+        let error = reviewer.review_changes(UNWRAP_DIFF.to_string(), BTreeMap::new(), 0).await.unwrap_err();
+        assert_eq!(error.to_string(), expected);
+    }
+}
+
+/// Serves reviews from the Ollama mock `ollama` on a free port, returning its address.
+async fn serve(ollama: &MockServer) -> String {
+    let server = Server::new(reviewer(backend(ollama)), BackendKind::Ollama).with_max_files_context(1);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server.serve(listener, std::future::pending()));
+    address
+}
+
+#[tokio::test]
+async fn served_reviews_come_back_as_json() {
+    let ollama = MockServer::start().await;
+    let findings = json!({ "findings": [{
+        "file": "src/lib.rs", "line": 2, "severity": "error", "category": "bugs", "message": "unwrap panics",
+    }] });
+    generation("llama3", &findings.to_string()).expect(1).mount(&ollama).await;
+    let address = serve(&ollama).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/review", address))
+        .json(&json!({
+            "diff": UNWRAP_DIFF,
+            "context_files": { "src/a.rs": "fn a() {}", "src/b.rs": "fn b() {}" },
+            "model": "llama3",
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let result: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(result["model"], "llama3");
+    assert_eq!(result["files"], json!(["src/lib.rs"]));
+    assert_eq!(result["context_files"], json!(["src/a.rs"]));
+    assert_eq!(result["findings"][0]["message"], "unwrap panics");
+    assert_eq!(result["usage"]["requests"], 1);
+}
+
+#[tokio::test]
+async fn health_check_fails_when_ollama_is_down() {
+    let ollama = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/api/version"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "version": "0.6.0" })))
+        .mount(&ollama)
+        .await;
+    let address = serve(&ollama).await;
+    let client = reqwest::Client::new();
+    assert_eq!(client.get(format!("{}/healthz", address)).send().await.unwrap().status(), 200);
+
+    ollama.reset().await;
+    let response = client.get(format!("{}/healthz", address)).send().await.unwrap();
+    assert_eq!(response.status(), 503);
+    let health: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(health["status"], "unavailable");
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use code_reviewer::backend::ollama::OllamaBackend;
+use code_reviewer::findings::{self, Severity};
+use code_reviewer::language::LanguageProfile;
+use code_reviewer::stats::ReviewStats;
+use code_reviewer::CodeReviewTool;
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const DIFF: &str = include_str!("fixtures/unwrap.diff");
+
+fn reviewer(server: &MockServer) -> CodeReviewTool {
+    let backend = Arc::new(OllamaBackend::new(server.uri()));
+    CodeReviewTool::new(backend, Some("codellama".to_string())).with_progress(false)
+}
+
+async fn mock_chat(server: &MockServer, review: &str) {
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(body_partial_json(json!({ "model": "codellama", "stream": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": { "role": "assistant", "content": review },
+            "done": true,
+            "prompt_eval_count": 120,
+            "eval_count": 40,
+        })))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+/// The prompt of the first request, the user message after the system prompt.
+async fn prompt(server: &MockServer) -> String {
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    let messages = body["messages"].as_array().unwrap();
+    messages.last().unwrap()["content"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn review_changes_sends_the_diff_and_context() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+
+    let context = BTreeMap::from([("src/main.rs".to_string(), "fn main() {}".to_string())]);
+    let review = reviewer(&server).review_changes(DIFF.to_string(), context, 5).await.unwrap();
+
+    assert_eq!(review, "Looks fine.");
+    let prompt = prompt(&server).await;
+    assert!(prompt.contains("+    input.parse().unwrap()"));
+    assert!(prompt.contains("src/main.rs:\n```rust\nfn main() {}\n```"));
+}
+
+#[tokio::test]
+async fn review_without_context_has_no_context_section() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+
+    reviewer(&server).review_changes(DIFF.to_string(), BTreeMap::new(), 5).await.unwrap();
+
+    let prompt = prompt(&server).await;
+    assert!(prompt.contains("+    input.parse().unwrap()"));
+    assert!(!prompt.contains("Relevant files"));
+}
+
+#[tokio::test]
+async fn changed_symbols_are_named_before_the_diff() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+
+    let symbols = BTreeMap::from([
+        ("src/lib.rs".to_string(), vec!["parse".to_string(), "Config::load".to_string()]),
+        ("src/other.rs".to_string(), vec!["unrelated".to_string()]),
+    ]);
+    let reviewer = reviewer(&server).with_changed_symbols(symbols);
+    reviewer.review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    let prompt = prompt(&server).await;
+    assert!(prompt.starts_with("The following functions were changed: `parse`, `Config::load`.\n\n"), "{}", prompt);
+}
+
+#[tokio::test]
+async fn prompt_asks_for_the_checks_of_the_language() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+    reviewer(&server).review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+    assert!(prompt(&server).await.ends_with("This is Rust code. Check for unnecessary clones, unwrap() calls without justification, missing error propagation, and improper use of unsafe."));
+
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+    let reviewer = reviewer(&server).with_language(Some(LanguageProfile::Go));
+    reviewer.review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+    assert!(prompt(&server).await.contains("This is Go code. Check for ignored or unwrapped errors"));
+}
+
+#[tokio::test]
+async fn rust_mode_lists_the_anti_patterns_in_both_prompts() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+    let reviewer = reviewer(&server).with_system_prompt("You are a code reviewer.".to_string()).with_rust_mode(true);
+    reviewer.review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    let system = body["messages"][0]["content"].as_str().unwrap();
+    assert!(system.starts_with("You are a code reviewer.\n\nWhen reviewing Rust code"), "{}", system);
+    assert!(system.contains("\n- `unsafe` blocks without a `// SAFETY:` comment"), "{}", system);
+
+    let prompt = prompt(&server).await;
+    assert!(prompt.contains("5. Suggestions for improvement\n6. Rust: `.unwrap()` or `.expect()` in non-test code"), "{}", prompt);
+    assert!(prompt.contains("\n11. Rust: integer arithmetic that can overflow"), "{}", prompt);
+}
+
+#[tokio::test]
+async fn security_audit_replaces_the_review_request() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Injection: OK").await;
+    reviewer(&server).with_security_audit(true).review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    let prompt = prompt(&server).await;
+    assert!(!prompt.contains("Please provide a detailed code review"), "{}", prompt);
+    assert!(prompt.contains("\n- Path traversal: file paths built from input"), "{}", prompt);
+    assert!(prompt.contains("In the Rust code, look first at `std::process::Command` arguments"), "{}", prompt);
+    assert!(prompt.ends_with("and how to fix it."), "{}", prompt);
+}
+
+#[tokio::test]
+async fn prompt_lists_every_language_of_a_mixed_change() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+    let python = "diff --git a/app.py b/app.py\n--- a/app.py\n+++ b/app.py\n@@ -1 +1 @@\n-x = 1\n+x = 2\n";
+    reviewer(&server).review_changes(format!("{}{}", DIFF, python), BTreeMap::new(), 0).await.unwrap();
+
+    let prompt = prompt(&server).await;
+    assert!(prompt.contains("This change has code in several languages: Rust, Python."), "{}", prompt);
+    assert!(prompt.contains("\nIn the Rust code: Check for unnecessary clones"), "{}", prompt);
+    assert!(prompt.contains("\nIn the Python code: Check for mutable default arguments"), "{}", prompt);
+}
+
+#[tokio::test]
+async fn stats_estimate_the_prompt_size() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+
+    let reviewer = reviewer(&server).with_system_prompt(String::new());
+    reviewer.review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    let stats = ReviewStats::new(None, 0, DIFF, &reviewer.usage());
+    assert_eq!(stats.estimated_prompt_tokens, prompt(&server).await.len().div_ceil(4) as u64);
+    assert_eq!(stats.prompt_tokens, 120);
+    assert_eq!(stats.diff_lines, DIFF.lines().count());
+    assert_eq!(stats.languages, ["Rust"]);
+}
+
+#[tokio::test]
+async fn usage_counts_the_tokens_of_every_request() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+
+    let reviewer = reviewer(&server);
+    reviewer.review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+
+    let usage = reviewer.usage();
+    assert_eq!(usage.requests, 1);
+    assert_eq!(usage.prompt_tokens, 120);
+    assert_eq!(usage.completion_tokens, 40);
+    assert_eq!(usage.models, ["codellama"]);
+}
+
+#[tokio::test]
+async fn findings_are_parsed_from_the_review() {
+    let server = MockServer::start().await;
+    mock_chat(
+        &server,
+        "## Potential bugs\n\n- `src/lib.rs:2` unwrap panics on invalid input (error)\n",
+    )
+    .await;
+
+    let review = reviewer(&server).review_changes(DIFF.to_string(), BTreeMap::new(), 0).await.unwrap();
+    let findings = findings::parse_findings(&review);
+
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].file.as_deref(), Some("src/lib.rs"));
+    assert_eq!(findings[0].line, Some(2));
+    assert_eq!(findings[0].severity, Severity::Error);
+}
+
+#[tokio::test]
+async fn long_context_files_keep_the_top_and_the_changes() {
+    let server = MockServer::start().await;
+    mock_chat(&server, "Looks fine.").await;
+
+    let mut content: Vec<String> = (1..=1000).map(|line| format!("line {}", line)).collect();
+    content[499] = "fn parse(input: &str) -> u32 {".to_string();
+    let diff = "\
+diff --git a/src/parse.rs b/src/parse.rs
+--- a/src/parse.rs
++++ b/src/parse.rs
+@@ -500,1 +500,1 @@
+-fn parse(input: &str) -> u64 {
++fn parse(input: &str) -> u32 {
+";
+    let context = BTreeMap::from([("src/parse.rs".to_string(), content.join("\n"))]);
+    reviewer(&server)
+        .with_file_limits(256, 100_000, 100)
+        .review_changes(diff.to_string(), context, 5)
+        .await
+        .unwrap();
+
+    let prompt = prompt(&server).await;
+    assert!(prompt.contains("line 1\n"));
+    assert!(prompt.contains("line 50\n... [truncated 439 lines] ...\nline 490\n"));
+    assert!(prompt.contains("fn parse(input: &str) -> u32 {\nline 501\n"));
+    assert!(prompt.contains("line 510\n... [truncated 490 lines] ...\n```"));
+}
+
+#[tokio::test]
+async fn structured_review_asks_for_json_findings() {
+    let server = MockServer::start().await;
+    mock_chat(
+        &server,
+        r#"{"findings": [{"file": "src/lib.rs", "line": 2, "severity": "error", "category": "bugs",
+            "message": "unwrap panics on bad input", "suggestion": "Return a Result"}]}"#,
+    )
+    .await;
+
+    let (review, findings) = reviewer(&server).review_structured(DIFF, BTreeMap::new(), 0, None).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert_eq!(body["format"]["required"], json!(["findings"]));
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].line, Some(2));
+    assert_eq!(findings[0].suggestion.as_deref(), Some("Return a Result"));
+    assert!(review.contains("`src/lib.rs:2`: unwrap panics on bad input"));
+}
+
+#[tokio::test]
+async fn unparseable_structured_review_is_retried_then_read_as_free_text() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": { "role": "assistant", "content": "- `src/lib.rs:2` unwrap panics on bad input (error)" },
+            "done": true,
+        })))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let (review, findings) = reviewer(&server).review_structured(DIFF, BTreeMap::new(), 0, None).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests[1].body_json().unwrap();
+    let retry = body["messages"].as_array().unwrap().last().unwrap()["content"].as_str().unwrap();
+    assert!(retry.contains("could not be read as JSON findings"));
+    assert_eq!(review, "- `src/lib.rs:2` unwrap panics on bad input (error)");
+    assert_eq!(findings[0].severity, Severity::Error);
+}